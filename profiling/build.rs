@@ -0,0 +1,29 @@
+use std::env;
+use std::process::Command;
+
+// `cargo test --workspace` unifies features, which turns on
+// `pyo3/extension-module` for this binary as well. pyo3 then leaves out
+// libpython, so link it here.
+fn main() {
+    let python = env::var("PYTHON_SYS_EXECUTABLE").unwrap_or_else(|_| "python3".to_string());
+    let output = Command::new(&python)
+        .args([
+            "-c",
+            "import sysconfig; print(sysconfig.get_config_var('LIBDIR')); \
+             print(sysconfig.get_config_var('LDVERSION'))",
+        ])
+        .output()
+        .unwrap_or_else(|err| panic!("could not run {}: {}", python, err));
+    let output = String::from_utf8(output.stdout).expect("sysconfig output is not UTF-8");
+    let mut lines = output.lines();
+    let (libdir, version) = match (lines.next(), lines.next()) {
+        (Some(libdir), Some(version)) => (libdir, version),
+        _ => panic!("could not find libpython with {}", python),
+    };
+    println!("cargo:rustc-link-search=native={}", libdir);
+    println!("cargo:rustc-link-lib=python{}", version);
+    if env::var("CARGO_CFG_TARGET_OS").as_ref().map(String::as_str) != Ok("windows") {
+        println!("cargo:rustc-link-arg=-Wl,-rpath,{}", libdir);
+    }
+    println!("cargo:rerun-if-env-changed=PYTHON_SYS_EXECUTABLE");
+}
@@ -8,9 +8,23 @@ pub fn exec(iterations: u64) {
     let obj = booleans.to_object(py);
 
     for _ in 0..iterations {
-        let deserialized =
-            hyperjson::loads_impl(py, obj.clone_ref(py), None, None, None, None, None, None)
-                .unwrap();
+        let deserialized = hyperjson::loads_impl(
+            py,
+            obj.clone_ref(py),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
         println!(
             "{}",
             hyperjson::dumps(
@@ -26,6 +40,10 @@ pub fn exec(iterations: u64) {
                 None,
                 None,
                 None,
+                None,
+                None,
+                None,
+                None,
             )
             .is_ok()
         );
@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::slice;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyByteArray, PyBytes};
+use pyo3::AsPyPointer;
+
+/// How `bytes`, `bytearray` and `memoryview` values are represented in JSON.
+///
+/// The default mirrors the `json` module, which refuses to serialize bytes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BytesMode {
+    Raise,
+    Utf8,
+    Base64,
+    Base64Url,
+}
+
+impl BytesMode {
+    pub fn from_py(py: Python, mode: Option<PyObject>) -> PyResult<BytesMode> {
+        let mode = match mode {
+            Some(mode) if !mode.is_none() => mode,
+            _ => return Ok(BytesMode::Raise),
+        };
+        let name: String = mode.extract(py)?;
+        match name.as_str() {
+            "raise" => Ok(BytesMode::Raise),
+            "utf8" | "utf-8" => Ok(BytesMode::Utf8),
+            "base64" => Ok(BytesMode::Base64),
+            "base64url" => Ok(BytesMode::Base64Url),
+            _ => Err(PyValueError::py_err(format!(
+                "bytes_mode must be one of 'raise', 'utf8', 'base64' or 'base64url', got: {:?}",
+                name
+            ))),
+        }
+    }
+
+    /// Turns raw bytes into the string that gets written to the JSON document.
+    pub fn encode(self, bytes: &[u8]) -> Result<Cow<'_, str>, String> {
+        match self {
+            BytesMode::Raise => Err("bytes are not JSON serializable".to_string()),
+            BytesMode::Utf8 => std::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|e| format!("bytes are not valid UTF-8: {}", e)),
+            BytesMode::Base64 => Ok(Cow::Owned(base64::encode_config(bytes, base64::STANDARD))),
+            BytesMode::Base64Url => Ok(Cow::Owned(base64::encode_config(bytes, base64::URL_SAFE))),
+        }
+    }
+
+    /// Reverses `encode` for string values of fields marked as bytes.
+    pub fn decode(self, s: &str) -> Result<Vec<u8>, String> {
+        match self {
+            BytesMode::Raise => Err("bytes_mode is 'raise'".to_string()),
+            BytesMode::Utf8 => Ok(s.as_bytes().to_vec()),
+            BytesMode::Base64 => base64::decode_config(s, base64::STANDARD)
+                .map_err(|e| format!("invalid base64: {}", e)),
+            BytesMode::Base64Url => base64::decode_config(s, base64::URL_SAFE)
+                .map_err(|e| format!("invalid base64url: {}", e)),
+        }
+    }
+}
+
+/// Returns the contents of `bytes`, `bytearray` and `memoryview` objects,
+/// or `None` for any other type.
+pub fn extract(obj: &PyAny) -> Option<PyResult<Cow<'_, [u8]>>> {
+    if let Ok(b) = <PyBytes as PyTryFrom>::try_from(obj) {
+        return Some(Ok(Cow::Borrowed(b.as_bytes())));
+    }
+    if let Ok(b) = <PyByteArray as PyTryFrom>::try_from(obj) {
+        return Some(Ok(Cow::Owned(b.to_vec())));
+    }
+    if unsafe { pyo3::ffi::PyMemoryView_Check(obj.as_ptr()) } != 0 {
+        return Some(
+            obj.call_method0("tobytes")
+                .and_then(|b| b.downcast_ref::<PyBytes>().map_err(PyErr::from))
+                .map(|b| Cow::Borrowed(b.as_bytes())),
+        );
+    }
+    None
+}
+
+/// Borrows the contents of an object supporting the buffer protocol without
+/// copying them, unless the buffer is not contiguous.
+pub fn borrow<'a>(py: Python, buffer: &'a PyBuffer) -> PyResult<Cow<'a, [u8]>> {
+    if buffer.is_c_contiguous() {
+        let bytes =
+            unsafe { slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes()) };
+        Ok(Cow::Borrowed(bytes))
+    } else {
+        Ok(Cow::Owned(buffer.to_vec(py)?))
+    }
+}
@@ -0,0 +1,181 @@
+//! The JSON Canonicalization Scheme (RFC 8785): no whitespace, members
+//! sorted by their UTF-16 code units and numbers written like ECMAScript's
+//! `Number.prototype.toString`.
+
+use std::fmt::Write;
+
+use pyo3::class::basic::CompareOp;
+use pyo3::exceptions::TypeError as PyTypeError;
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyFloat, PyString};
+
+use crate::value::{elements, Kind};
+
+/// Appends the canonical form of `obj` to `out`.
+pub fn write(py: Python, obj: &PyAny, out: &mut String) -> PyResult<()> {
+    match Kind::of(obj) {
+        Kind::Null => out.push_str("null"),
+        Kind::Bool => out.push_str(if obj.is_true()? { "true" } else { "false" }),
+        Kind::Number => {
+            // Integers too are doubles in I-JSON, so they must convert exactly
+            let value: f64 = obj.extract()?;
+            if !value.is_finite() {
+                return Err(PyValueError::py_err(format!(
+                    "Out of range float values are not JSON compliant: {}",
+                    obj.repr()?
+                )));
+            }
+            if !PyFloat::new(py, value)
+                .rich_compare(obj, CompareOp::Eq)?
+                .is_true(py)?
+            {
+                return Err(PyValueError::py_err(format!(
+                    "{} is not exactly representable as a double",
+                    obj.repr()?
+                )));
+            }
+            number(value, out);
+        }
+        Kind::String => {
+            let value: &PyString = obj.downcast_ref()?;
+            string(&value.to_string()?, out)
+        }
+        Kind::Array => {
+            out.push('[');
+            for (i, element) in elements(obj).unwrap().into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write(py, element, out)?;
+            }
+            out.push(']');
+        }
+        Kind::Object => {
+            let dict: &PyDict = obj.downcast_ref()?;
+            let mut members = Vec::with_capacity(dict.len());
+            for (key, value) in dict {
+                let key: &PyString = key.downcast_ref().map_err(|_| {
+                    PyTypeError::py_err(format!("keys must be str, not {}", key.get_type().name()))
+                })?;
+                members.push((key.to_string()?, value));
+            }
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, value)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                string(&key, out);
+                out.push(':');
+                write(py, value, out)?;
+            }
+            out.push('}');
+        }
+        Kind::Other => {
+            return Err(PyTypeError::py_err(format!(
+                "Type is not JSON serializable: {}",
+                obj.get_type().name()
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Escapes only `"`, `\` and control characters, with the short escapes
+/// where JSON has them.
+fn string(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\u{c}' => out.push_str("\\f"),
+            '\r' => out.push_str("\\r"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Writes a finite `value` like ECMAScript's `Number.prototype.toString`.
+pub fn number(value: f64, out: &mut String) {
+    if value == 0.0 {
+        // Negative zero included
+        out.push('0');
+        return;
+    }
+    if value < 0.0 {
+        out.push('-');
+    }
+    // The shortest digits that round-trip, as in `d.ddde-n`
+    let exponential = format!("{:e}", value.abs());
+    let (mantissa, exponent) = exponential.split_at(exponential.find('e').unwrap());
+    let mut digits: String = mantissa.chars().filter(|&c| c != '.').collect();
+    if digits.ends_with(|c: char| c.to_digit(10).unwrap() % 2 == 1) {
+        if let Some(even) = even_tie(value.abs(), &digits, exponent) {
+            digits = even;
+        }
+    }
+    let k = digits.len() as i32;
+    // The decimal point goes after `n` digits
+    let n = exponent[1..].parse::<i32>().unwrap() + 1;
+    if k <= n && n <= 21 {
+        out.push_str(&digits);
+        out.extend((k..n).map(|_| '0'));
+    } else if 0 < n && n <= 21 {
+        out.push_str(&digits[..n as usize]);
+        out.push('.');
+        out.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        out.push_str("0.");
+        out.extend((n..0).map(|_| '0'));
+        out.push_str(&digits);
+    } else {
+        out.push_str(&digits[..1]);
+        if k > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        write!(out, "e{}{}", if n > 0 { '+' } else { '-' }, (n - 1).abs()).unwrap();
+    }
+}
+
+/// ECMAScript picks the even digits when `value` lies exactly halfway
+/// between the two closest candidates, where Rust may round up.
+fn even_tie(value: f64, digits: &str, exponent: &str) -> Option<String> {
+    // Enough digits for any double
+    let exact = format!("{:.767e}", value);
+    let (mantissa, exact_exponent) = exact.split_at(exact.find('e').unwrap());
+    let exact: String = mantissa.chars().filter(|&c| c != '.').collect();
+    let exact = exact.trim_end_matches('0');
+    if exact_exponent != exponent || exact.len() != digits.len() + 1 || !exact.ends_with('5') {
+        return None;
+    }
+    // Rounds the odd lower candidate up
+    let mut even = exact.as_bytes()[..digits.len()].to_vec();
+    if even.last().unwrap() % 2 == 1 {
+        let mut i = even.len();
+        loop {
+            i = i.checked_sub(1)?;
+            if even[i] == b'9' {
+                even[i] = b'0';
+            } else {
+                even[i] += 1;
+                break;
+            }
+        }
+    }
+    let even = String::from_utf8(even).unwrap();
+    let exponent = exponent[1..].parse::<i32>().unwrap() - (even.len() as i32 - 1);
+    let parsed: f64 = format!("{}e{}", even, exponent).parse().ok()?;
+    if parsed == value {
+        Some(even)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,134 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xB5, 0x2F, 0xFD];
+
+/// The compression formats of the `compression` argument.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Compression {
+    /// Detect compressed input by its magic bytes. On output, this means no
+    /// compression, except for paths ending in `.gz` or `.zst`.
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_py(py: Python, compression: Option<PyObject>) -> PyResult<Compression> {
+        let compression = match compression {
+            Some(compression) if !compression.is_none() => compression,
+            _ => return Ok(Compression::Auto),
+        };
+        let name: String = compression.extract(py)?;
+        match name.as_str() {
+            "auto" => Ok(Compression::Auto),
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(PyValueError::py_err(format!(
+                "compression must be one of 'auto', 'none', 'gzip' or 'zstd', got: {:?}",
+                name
+            ))),
+        }
+    }
+
+    /// Resolves `Auto` by looking at the first bytes of the input.
+    pub fn detect(self, head: &[u8]) -> Compression {
+        match self {
+            Compression::Auto if head.starts_with(GZIP_MAGIC) => Compression::Gzip,
+            Compression::Auto if head.starts_with(ZSTD_MAGIC) => Compression::Zstd,
+            Compression::Auto => Compression::None,
+            compression => compression,
+        }
+    }
+
+    /// Resolves `Auto` by looking at the file extension of `path`.
+    pub fn for_path(self, path: &Path) -> Compression {
+        match self {
+            Compression::Auto => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("gz") => Compression::Gzip,
+                Some("zst") => Compression::Zstd,
+                _ => Compression::None,
+            },
+            compression => compression,
+        }
+    }
+}
+
+/// Wraps `reader` in a decompressor. With `Compression::Auto`, the format is
+/// detected from the first bytes of the input.
+pub fn decompress<'a, R: Read + 'a>(
+    reader: R,
+    compression: Compression,
+) -> io::Result<Box<dyn Read + 'a>> {
+    let (head, reader) = peek(reader, ZSTD_MAGIC.len())?;
+    let compression = compression.detect(&head);
+    let reader = io::Cursor::new(head).chain(reader);
+    Ok(match compression {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        _ => Box::new(reader),
+    })
+}
+
+/// Reads up to `n` bytes from the start of `reader`, stopping early only at
+/// the end of the input.
+pub fn peek<R: Read>(mut reader: R, n: usize) -> io::Result<(Vec<u8>, R)> {
+    let mut head = Vec::with_capacity(n);
+    (&mut reader).take(n as u64).read_to_end(&mut head)?;
+    Ok((head, reader))
+}
+
+/// A writer that compresses what is written to it.
+pub enum Compressor<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<W>),
+}
+
+impl<W: Write> Compressor<W> {
+    /// `Auto` is taken to mean no compression.
+    pub fn new(writer: W, compression: Compression) -> io::Result<Compressor<W>> {
+        Ok(match compression {
+            Compression::Auto | Compression::None => Compressor::None(writer),
+            Compression::Gzip => {
+                Compressor::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+            }
+            Compression::Zstd => Compressor::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+
+    /// Writes the end of the compressed stream and returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            Compressor::None(writer) => Ok(writer),
+            Compressor::Gzip(encoder) => encoder.finish(),
+            Compressor::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::None(writer) => writer.write(buf),
+            Compressor::Gzip(encoder) => encoder.write(buf),
+            Compressor::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::None(writer) => writer.flush(),
+            Compressor::Gzip(encoder) => encoder.flush(),
+            Compressor::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::char;
+use std::ffi::CString;
+use std::ops::Range;
+use std::str;
+
+use pyo3::exceptions::{UnicodeDecodeError, ValueError as PyValueError};
+use pyo3::prelude::*;
+
+use crate::surrogates::{self, SurrogatePolicy};
+
+/// The Unicode encodings JSON byte input may use (RFC 8259 only allows
+/// UTF-8, but Python's `json` module also accepts UTF-16 and UTF-32).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Be,
+    Utf16Le,
+    Utf32Be,
+    Utf32Le,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Be => "utf-16-be",
+            Encoding::Utf16Le => "utf-16-le",
+            Encoding::Utf32Be => "utf-32-be",
+            Encoding::Utf32Le => "utf-32-le",
+        }
+    }
+}
+
+/// Detects the encoding of JSON bytes and the length of its byte order mark.
+///
+/// This follows `json.detect_encoding`: a BOM wins, otherwise the position
+/// of zero bytes at the start tells the encodings apart, since a JSON
+/// document starts with an ASCII character.
+pub fn detect(b: &[u8]) -> (Encoding, usize) {
+    if b.starts_with(&[0, 0, 0xFE, 0xFF]) {
+        return (Encoding::Utf32Be, 4);
+    }
+    if b.starts_with(&[0xFF, 0xFE, 0, 0]) {
+        return (Encoding::Utf32Le, 4);
+    }
+    if b.starts_with(&[0xFE, 0xFF]) {
+        return (Encoding::Utf16Be, 2);
+    }
+    if b.starts_with(&[0xFF, 0xFE]) {
+        return (Encoding::Utf16Le, 2);
+    }
+    if b.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return (Encoding::Utf8, 3);
+    }
+    if b.len() >= 4 {
+        if b[0] == 0 {
+            // 00 XX -- -- is UTF-16-BE, 00 00 -- -- is UTF-32-BE
+            let encoding = if b[1] != 0 {
+                Encoding::Utf16Be
+            } else {
+                Encoding::Utf32Be
+            };
+            return (encoding, 0);
+        }
+        if b[1] == 0 {
+            // XX 00 00 00 is UTF-32-LE, anything else is UTF-16-LE
+            let encoding = if b[2] != 0 || b[3] != 0 {
+                Encoding::Utf16Le
+            } else {
+                Encoding::Utf32Le
+            };
+            return (encoding, 0);
+        }
+    } else if b.len() == 2 {
+        if b[0] == 0 {
+            return (Encoding::Utf16Be, 0);
+        }
+        if b[1] == 0 {
+            return (Encoding::Utf16Le, 0);
+        }
+    }
+    (Encoding::Utf8, 0)
+}
+
+/// How undecodable bytes are handled, named after Python's error handlers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ErrorPolicy {
+    /// Raise `UnicodeDecodeError`
+    Strict,
+    /// Replace with U+FFFD REPLACEMENT CHARACTER
+    Replace,
+    /// Replace every byte with a lone surrogate from U+DC80 to U+DCFF
+    SurrogateEscape,
+}
+
+impl ErrorPolicy {
+    pub fn from_py(py: Python, errors: Option<PyObject>) -> PyResult<ErrorPolicy> {
+        let errors = match errors {
+            Some(errors) if !errors.is_none() => errors,
+            _ => return Ok(ErrorPolicy::Strict),
+        };
+        let name: String = errors.extract(py)?;
+        match name.as_str() {
+            "strict" => Ok(ErrorPolicy::Strict),
+            "replace" => Ok(ErrorPolicy::Replace),
+            "surrogateescape" => Ok(ErrorPolicy::SurrogateEscape),
+            _ => Err(PyValueError::py_err(format!(
+                "errors must be one of 'strict', 'replace' or 'surrogateescape', got: {:?}",
+                name
+            ))),
+        }
+    }
+}
+
+/// JSON text decoded from bytes
+pub struct Decoded<'a> {
+    /// UTF-8 text, unless `lone` is set
+    pub text: Cow<'a, [u8]>,
+    /// Set if the text holds lone surrogates, encoded like any other code
+    /// point (as Python's `surrogatepass` error handler does)
+    pub lone: bool,
+}
+
+/// Decodes JSON bytes in any of the supported encodings.
+///
+/// Lone surrogates, which Python's `json` module lets through, are handled
+/// according to `surrogates`. Everything else that cannot be decoded, as
+/// well as lone surrogates with the `Strict` surrogate policy, is handled
+/// according to `errors`.
+pub fn decode<'a>(
+    py: Python,
+    bytes: &'a [u8],
+    errors: ErrorPolicy,
+    surrogates: SurrogatePolicy,
+) -> PyResult<Decoded<'a>> {
+    let (encoding, bom) = detect(bytes);
+    if encoding == Encoding::Utf8 {
+        if let Ok(text) = str::from_utf8(&bytes[bom..]) {
+            return Ok(Decoded {
+                text: Cow::Borrowed(text.as_bytes()),
+                lone: false,
+            });
+        }
+    }
+
+    let mut decoder = Decoder {
+        py,
+        bytes,
+        encoding,
+        errors,
+        surrogates,
+        text: Vec::with_capacity(bytes.len()),
+        lone: false,
+    };
+    match encoding {
+        Encoding::Utf8 => decoder.utf8(bom)?,
+        Encoding::Utf16Be | Encoding::Utf16Le => decoder.utf16(bom)?,
+        Encoding::Utf32Be | Encoding::Utf32Le => decoder.utf32(bom)?,
+    }
+    Ok(Decoded {
+        text: Cow::Owned(decoder.text),
+        lone: decoder.lone,
+    })
+}
+
+struct Decoder<'p, 'a> {
+    py: Python<'p>,
+    bytes: &'a [u8],
+    encoding: Encoding,
+    errors: ErrorPolicy,
+    surrogates: SurrogatePolicy,
+    text: Vec<u8>,
+    lone: bool,
+}
+
+impl<'p, 'a> Decoder<'p, 'a> {
+    fn push(&mut self, c: char) {
+        let mut buf = [0; 4];
+        self.text
+            .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+
+    fn push_surrogate(&mut self, surrogate: u16) {
+        self.text
+            .extend_from_slice(&surrogates::encode_surrogate(surrogate));
+        self.lone = true;
+    }
+
+    fn lone_surrogate(&mut self, surrogate: u16, range: Range<usize>) -> PyResult<()> {
+        match self.surrogates {
+            SurrogatePolicy::Strict => self.invalid(range, "illegal encoding of a surrogate"),
+            SurrogatePolicy::Escape => {
+                self.push_surrogate(surrogate);
+                Ok(())
+            }
+            SurrogatePolicy::Replace => {
+                self.push(char::REPLACEMENT_CHARACTER);
+                Ok(())
+            }
+        }
+    }
+
+    fn invalid(&mut self, range: Range<usize>, reason: &str) -> PyResult<()> {
+        let escapable = self.bytes[range.clone()].iter().all(|&b| b >= 0x80);
+        match self.errors {
+            ErrorPolicy::Replace => {
+                self.push(char::REPLACEMENT_CHARACTER);
+                Ok(())
+            }
+            // Like Python, only non-ASCII bytes can be escaped
+            ErrorPolicy::SurrogateEscape if escapable => {
+                for &b in &self.bytes[range] {
+                    self.push_surrogate(0xDC00 + u16::from(b));
+                }
+                Ok(())
+            }
+            _ => {
+                let encoding = CString::new(self.encoding.name()).unwrap_or_default();
+                let reason = CString::new(reason).unwrap_or_default();
+                let err =
+                    UnicodeDecodeError::new_err(self.py, &encoding, self.bytes, range, &reason)?;
+                Err(PyErr::from_instance(err))
+            }
+        }
+    }
+
+    fn utf8(&mut self, bom: usize) -> PyResult<()> {
+        let mut pos = bom;
+        loop {
+            let rest = &self.bytes[pos..];
+            match str::from_utf8(rest) {
+                Ok(_) => {
+                    self.text.extend_from_slice(rest);
+                    return Ok(());
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    self.text.extend_from_slice(&rest[..valid]);
+                    pos += valid;
+                    let rest = &self.bytes[pos..];
+                    // Surrogates encoded like any other code point, as
+                    // Python's `surrogatepass` error handler does
+                    if rest.len() >= 3
+                        && rest[0] == 0xED
+                        && (0xA0..=0xBF).contains(&rest[1])
+                        && (0x80..=0xBF).contains(&rest[2])
+                    {
+                        let surrogate =
+                            0xD000 | (u16::from(rest[1] & 0x3F) << 6) | u16::from(rest[2] & 0x3F);
+                        self.lone_surrogate(surrogate, pos..pos + 3)?;
+                        pos += 3;
+                        continue;
+                    }
+                    let len = e.error_len().unwrap_or(rest.len());
+                    let reason = if e.error_len().is_some() {
+                        "invalid utf-8 sequence"
+                    } else {
+                        "unexpected end of data"
+                    };
+                    self.invalid(pos..pos + len, reason)?;
+                    pos += len;
+                }
+            }
+        }
+    }
+
+    fn utf16(&mut self, bom: usize) -> PyResult<()> {
+        let unit = |b: &[u8]| match self.encoding {
+            Encoding::Utf16Be => u16::from_be_bytes([b[0], b[1]]),
+            _ => u16::from_le_bytes([b[0], b[1]]),
+        };
+        let units: Vec<u16> = self.bytes[bom..].chunks_exact(2).map(unit).collect();
+
+        let mut i = 0;
+        while i < units.len() {
+            let pos = bom + 2 * i;
+            let n = units[i];
+            if (0xD800..0xDC00).contains(&n)
+                && i + 1 < units.len()
+                && (0xDC00..0xE000).contains(&units[i + 1])
+            {
+                let c =
+                    0x1_0000 + ((u32::from(n) - 0xD800) << 10) + (u32::from(units[i + 1]) - 0xDC00);
+                self.push(char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER));
+                i += 2;
+                continue;
+            }
+            match char::from_u32(u32::from(n)) {
+                Some(c) => self.push(c),
+                None => self.lone_surrogate(n, pos..pos + 2)?,
+            }
+            i += 1;
+        }
+
+        let end = bom + 2 * units.len();
+        if end < self.bytes.len() {
+            self.invalid(end..self.bytes.len(), "truncated data")?;
+        }
+        Ok(())
+    }
+
+    fn utf32(&mut self, bom: usize) -> PyResult<()> {
+        let mut pos = bom;
+        while pos + 4 <= self.bytes.len() {
+            let b = &self.bytes[pos..pos + 4];
+            let n = match self.encoding {
+                Encoding::Utf32Be => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+                _ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            };
+            match char::from_u32(n) {
+                Some(c) => self.push(c),
+                None if (0xD800..0xE000).contains(&n) => {
+                    self.lone_surrogate(n as u16, pos..pos + 4)?
+                }
+                None => self.invalid(pos..pos + 4, "code point not in range(0x110000)")?,
+            }
+            pos += 4;
+        }
+        if pos < self.bytes.len() {
+            self.invalid(pos..self.bytes.len(), "truncated data")?;
+        }
+        Ok(())
+    }
+}
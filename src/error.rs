@@ -1,5 +1,9 @@
+// failure's derive predates the `non_local_definitions` lint
+#![allow(non_local_definitions)]
+
 use failure::Fail;
-use pyo3::{exceptions::TypeError as PyTypeError, import_exception, PyErr, PyObject};
+use pyo3::exceptions::{TypeError as PyTypeError, ValueError as PyValueError};
+use pyo3::{create_exception, import_exception, AsPyRef, PyErr, PyObject, Python};
 
 #[derive(Debug, Fail)]
 pub enum HyperJsonError {
@@ -7,6 +11,9 @@ pub enum HyperJsonError {
     InvalidConversion { error: serde_json::Error },
     #[fail(display = "Python Runtime exception: {}", error)]
     PyErr { error: String },
+    // An exception that is raised again as it is
+    #[fail(display = "Python exception: {:?}", exception)]
+    Python { exception: PyObject },
     #[fail(display = "Dictionary key is not a string: {:?}", obj)]
     DictKeyNotString { obj: PyObject },
     #[fail(display = "Invalid float: {}", x)]
@@ -36,6 +43,10 @@ impl From<HyperJsonError> for PyErr {
             }
             // TODO
             HyperJsonError::PyErr { error: _error } => PyErr::new::<PyTypeError, _>("PyErr"),
+            HyperJsonError::Python { exception } => {
+                let gil = Python::acquire_gil();
+                PyErr::from_instance(&exception.as_ref(gil.python()))
+            }
             HyperJsonError::InvalidCast { t: _t, e: _e } => {
                 PyErr::new::<PyTypeError, _>("InvalidCast")
             }
@@ -55,3 +66,9 @@ impl From<PyErr> for HyperJsonError {
 }
 
 import_exception!(json, JSONDecodeError);
+
+// Raised by `hyperjson.patch`
+create_exception!(hyperjson, JsonPatchError, PyValueError);
+
+// Raised by `hyperjson.Validator` and `loads(schema=...)`
+create_exception!(hyperjson, ValidationError, PyValueError);
@@ -0,0 +1,471 @@
+// The code generated by `#[pyclass]` trips this lint
+#![allow(clippy::manual_div_ceil)]
+
+use pyo3::prelude::*;
+use pyo3::PyIterProtocol;
+
+use crate::error::JSONDecodeError;
+use crate::reader::ChunkSource;
+use crate::LoadsOptions;
+
+/// An event of the tokenizer. Scalars are left unparsed, as the position of
+/// their token in the buffer.
+pub enum Event {
+    StartMap,
+    EndMap,
+    StartArray,
+    EndArray,
+    Key(String),
+    Scalar(usize, usize),
+}
+
+/// A container the tokenizer is in, which adds to the prefix of the values
+/// inside it: the current key for objects, `item` for arrays.
+enum Frame {
+    Map(Option<String>),
+    Array,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Expect {
+    Value,
+    // A value or `]`
+    FirstItem,
+    // A key or `}`
+    FirstKey,
+    Key,
+    Colon,
+    CommaOrEnd,
+    // Only whitespace may follow the top-level value
+    End,
+}
+
+/// A pull tokenizer for a single JSON document, reading a file in chunks.
+/// Only the current token has to fit into memory, unless `keep` asks for
+/// more of the buffer to be held on to.
+pub struct Tokenizer {
+    source: ChunkSource,
+    buf: Vec<u8>,
+    pos: usize,
+    // Stream offset of the start of `buf`
+    base: usize,
+    // The buffer is not drained past this position
+    keep: Option<usize>,
+    frames: Vec<Frame>,
+    expect: Expect,
+    started: bool,
+}
+
+impl Tokenizer {
+    pub fn new(source: ChunkSource) -> Tokenizer {
+        Tokenizer {
+            source,
+            buf: Vec::new(),
+            pos: 0,
+            base: 0,
+            keep: None,
+            frames: Vec::new(),
+            expect: Expect::Value,
+            started: false,
+        }
+    }
+
+    /// The number of containers the tokenizer is in.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The dotted path of the value at the current position, like `a.item.b`.
+    pub fn prefix(&self) -> String {
+        prefix(&self.frames)
+    }
+
+    /// The prefix of the innermost container.
+    pub fn container_prefix(&self) -> String {
+        prefix(&self.frames[..self.frames.len().saturating_sub(1)])
+    }
+
+    pub fn token(&self, start: usize, end: usize) -> &[u8] {
+        &self.buf[start..end]
+    }
+
+    /// The position after the last token.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Keeps the buffer from `start` on until `release` is called, so that
+    /// `token` can return a whole value.
+    pub fn keep(&mut self, start: usize) {
+        self.keep = Some(start);
+    }
+
+    pub fn release(&mut self) -> usize {
+        self.keep.take().unwrap_or(self.pos)
+    }
+
+    /// Returns the next event, or `None` at the end of the document.
+    pub fn next_event(&mut self, py: Python) -> PyResult<Option<Event>> {
+        loop {
+            if !self.skip_whitespace(py)? {
+                return match self.expect {
+                    Expect::End => Ok(None),
+                    _ if !self.started => Ok(None),
+                    _ => Err(self.error("unexpected end of data")),
+                };
+            }
+            self.started = true;
+            let b = self.buf[self.pos];
+            let event = match (self.expect, b) {
+                (Expect::End, _) => return Err(self.error("trailing data")),
+                (Expect::Value, b'{') | (Expect::FirstItem, b'{') => {
+                    self.pos += 1;
+                    self.frames.push(Frame::Map(None));
+                    self.expect = Expect::FirstKey;
+                    Event::StartMap
+                }
+                (Expect::Value, b'[') | (Expect::FirstItem, b'[') => {
+                    self.pos += 1;
+                    self.frames.push(Frame::Array);
+                    self.expect = Expect::FirstItem;
+                    Event::StartArray
+                }
+                (Expect::FirstItem, b']') | (Expect::CommaOrEnd, b']') => {
+                    match self.frames.last() {
+                        Some(Frame::Array) => {}
+                        _ => return Err(self.error("unexpected ']'")),
+                    }
+                    self.pos += 1;
+                    self.end_container();
+                    Event::EndArray
+                }
+                (Expect::FirstKey, b'}') | (Expect::CommaOrEnd, b'}') => {
+                    match self.frames.last() {
+                        Some(Frame::Map(_)) => {}
+                        _ => return Err(self.error("unexpected '}'")),
+                    }
+                    self.pos += 1;
+                    self.end_container();
+                    Event::EndMap
+                }
+                (Expect::Value, b'"') | (Expect::FirstItem, b'"') => {
+                    let (start, end) = self.string(py)?;
+                    self.end_value();
+                    Event::Scalar(start, end)
+                }
+                (Expect::Value, _) | (Expect::FirstItem, _) => {
+                    let (start, end) = self.scalar(py)?;
+                    if !is_scalar(&self.buf[start..end]) {
+                        self.pos = start;
+                        return Err(self.error("expected value"));
+                    }
+                    self.end_value();
+                    Event::Scalar(start, end)
+                }
+                (Expect::FirstKey, b'"') | (Expect::Key, b'"') => {
+                    let (start, end) = self.string(py)?;
+                    let key: String = serde_json::from_slice(&self.buf[start..end])
+                        .map_err(|e| self.error(&e.to_string()))?;
+                    if let Some(Frame::Map(current)) = self.frames.last_mut() {
+                        *current = Some(key.clone());
+                    }
+                    self.expect = Expect::Colon;
+                    Event::Key(key)
+                }
+                (Expect::FirstKey, _) | (Expect::Key, _) => {
+                    return Err(self.error("key must be a string"))
+                }
+                (Expect::Colon, b':') => {
+                    self.pos += 1;
+                    self.expect = Expect::Value;
+                    continue;
+                }
+                (Expect::Colon, _) => return Err(self.error("expected ':'")),
+                (Expect::CommaOrEnd, b',') => {
+                    self.pos += 1;
+                    self.expect = match self.frames.last() {
+                        Some(Frame::Map(_)) => Expect::Key,
+                        _ => Expect::Value,
+                    };
+                    continue;
+                }
+                (Expect::CommaOrEnd, _) => {
+                    return Err(self.error("expected ',' or end of container"))
+                }
+            };
+            return Ok(Some(event));
+        }
+    }
+
+    fn end_container(&mut self) {
+        self.frames.pop();
+        self.end_value();
+    }
+
+    fn end_value(&mut self) {
+        self.expect = if self.frames.is_empty() {
+            Expect::End
+        } else {
+            Expect::CommaOrEnd
+        };
+    }
+
+    /// Moves to the next non-whitespace byte. Returns `false` at the end of
+    /// the input.
+    fn skip_whitespace(&mut self, py: Python) -> PyResult<bool> {
+        loop {
+            while self.pos < self.buf.len() {
+                match self.buf[self.pos] {
+                    b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                    _ => return Ok(true),
+                }
+            }
+            if !self.fill(py)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Moves past the string starting at `pos`, returning where it is in
+    /// the buffer.
+    fn string(&mut self, py: Python) -> PyResult<(usize, usize)> {
+        // Offsets are relative to `pos`, which `fill` may move
+        let mut i = 1;
+        let mut escape = false;
+        loop {
+            while self.pos + i < self.buf.len() {
+                let b = self.buf[self.pos + i];
+                i += 1;
+                if escape {
+                    escape = false;
+                } else if b == b'\\' {
+                    escape = true;
+                } else if b == b'"' {
+                    self.pos += i;
+                    return Ok((self.pos - i, self.pos));
+                }
+            }
+            if !self.fill(py)? {
+                return Err(self.error("EOF while parsing a string"));
+            }
+        }
+    }
+
+    /// Moves past the number or literal starting at `pos`, returning where it
+    /// is in the buffer.
+    fn scalar(&mut self, py: Python) -> PyResult<(usize, usize)> {
+        let mut i = 0;
+        loop {
+            while self.pos + i < self.buf.len() {
+                if b" \t\r\n,:[]{}\"".contains(&self.buf[self.pos + i]) {
+                    self.pos += i;
+                    return Ok((self.pos - i, self.pos));
+                }
+                i += 1;
+            }
+            if !self.fill(py)? {
+                self.pos += i;
+                return Ok((self.pos - i, self.pos));
+            }
+        }
+    }
+
+    /// Reads another chunk, dropping what is no longer needed. Returns
+    /// `false` at the end of the input.
+    fn fill(&mut self, py: Python) -> PyResult<bool> {
+        let drop = self.keep.map_or(self.pos, |keep| keep.min(self.pos));
+        self.buf.drain(..drop);
+        self.base += drop;
+        self.pos -= drop;
+        if let Some(keep) = &mut self.keep {
+            *keep -= drop;
+        }
+        let read = self.source.read_chunk(py, &mut self.buf)?;
+        if self.base == 0 && self.pos == 0 && self.buf.starts_with(b"\xEF\xBB\xBF") {
+            self.pos = 3;
+        }
+        Ok(read)
+    }
+
+    fn error(&self, message: &str) -> PyErr {
+        JSONDecodeError::py_err((
+            format!("{} at byte offset {}", message, self.base + self.pos),
+            String::new(),
+            0,
+        ))
+    }
+}
+
+fn prefix(frames: &[Frame]) -> String {
+    let mut prefix = String::new();
+    for frame in frames {
+        if !prefix.is_empty() {
+            prefix.push('.');
+        }
+        match frame {
+            Frame::Map(key) => prefix.push_str(key.as_ref().map_or("", String::as_str)),
+            Frame::Array => prefix.push_str("item"),
+        }
+    }
+    prefix
+}
+
+/// Checks the syntax of a number or literal.
+fn is_scalar(token: &[u8]) -> bool {
+    match token {
+        b"true" | b"false" | b"null" => return true,
+        _ => {}
+    }
+    let digits = |i: usize| token[i..].iter().take_while(|b| b.is_ascii_digit()).count();
+    let mut i = 0;
+    if token.get(i) == Some(&b'-') {
+        i += 1;
+    }
+    match (token.get(i), digits(i)) {
+        (Some(b'0'), _) => i += 1,
+        (_, 0) => return false,
+        (_, n) => i += n,
+    }
+    if token.get(i) == Some(&b'.') {
+        match digits(i + 1) {
+            0 => return false,
+            n => i += n + 1,
+        }
+    }
+    if let Some(b'e') | Some(b'E') = token.get(i) {
+        i += 1;
+        if let Some(b'+') | Some(b'-') = token.get(i) {
+            i += 1;
+        }
+        match digits(i) {
+            0 => return false,
+            n => i += n,
+        }
+    }
+    i == token.len()
+}
+
+/// Iterates over the parse events of a document, as
+/// `(prefix, event, value)` tuples, or with `items`, over the values whose
+/// prefix matches.
+#[pyclass]
+pub struct EventIterator {
+    options: LoadsOptions,
+    tokenizer: Tokenizer,
+    // Set for `items`
+    items: Option<String>,
+    done: bool,
+}
+
+impl EventIterator {
+    pub(crate) fn new(
+        options: LoadsOptions,
+        tokenizer: Tokenizer,
+        items: Option<String>,
+    ) -> EventIterator {
+        EventIterator {
+            options,
+            tokenizer,
+            items,
+            done: false,
+        }
+    }
+
+    fn next_event(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        // Scalars and `start_*` events have the prefix of the value, the
+        // others that of the container
+        let prefix = self.tokenizer.prefix();
+        let event = match self.tokenizer.next_event(py)? {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+        let (prefix, name, value) = match event {
+            Event::StartMap => (prefix, "start_map", py.None()),
+            Event::StartArray => (prefix, "start_array", py.None()),
+            Event::EndMap => (self.tokenizer.prefix(), "end_map", py.None()),
+            Event::EndArray => (self.tokenizer.prefix(), "end_array", py.None()),
+            Event::Key(key) => (
+                self.tokenizer.container_prefix(),
+                "map_key",
+                key.to_object(py),
+            ),
+            Event::Scalar(start, end) => {
+                let token = self.tokenizer.token(start, end);
+                let name = match token[0] {
+                    b'"' => "string",
+                    b't' | b'f' => "boolean",
+                    b'n' => "null",
+                    _ => "number",
+                };
+                (prefix, name, self.options.parse(py, token, None)?)
+            }
+        };
+        Ok(Some((prefix, name, value).to_object(py)))
+    }
+
+    fn next_item(&mut self, py: Python, target: &str) -> PyResult<Option<PyObject>> {
+        loop {
+            let depth = self.tokenizer.depth();
+            let matches = self.tokenizer.prefix() == target;
+            let event = match self.tokenizer.next_event(py)? {
+                Some(event) => event,
+                None => return Ok(None),
+            };
+            if !matches {
+                continue;
+            }
+            match event {
+                Event::Scalar(start, end) => {
+                    let token = self.tokenizer.token(start, end);
+                    return self.options.parse(py, token, None).map(Some);
+                }
+                Event::StartMap | Event::StartArray => {
+                    // Collect the whole value, then parse it in one go
+                    self.tokenizer.keep(self.tokenizer.pos() - 1);
+                    while self.tokenizer.depth() > depth {
+                        if self.tokenizer.next_event(py)?.is_none() {
+                            break;
+                        }
+                    }
+                    let start = self.tokenizer.release();
+                    let token = self.tokenizer.token(start, self.tokenizer.pos());
+                    return self.options.parse(py, token, None).map(Some);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for EventIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<EventIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        if slf.done {
+            return Ok(None);
+        }
+        let result = match slf.items.take() {
+            Some(target) => {
+                let result = slf.next_item(py, &target);
+                slf.items = Some(target);
+                result
+            }
+            None => slf.next_event(py),
+        };
+        match result {
+            Ok(Some(obj)) => Ok(Some(obj)),
+            Ok(None) => {
+                slf.done = true;
+                Ok(None)
+            }
+            Err(err) => {
+                slf.done = true;
+                Err(err)
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use pyo3::exceptions::{TypeError as PyTypeError, ValueError as PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyString};
+
+/// The keys `loads` keeps when given `fields`, as a tree. A key mapped to
+/// `None` keeps its whole value. Arrays are transparent, so `items.id`
+/// selects `id` in each element of `items`.
+#[derive(Debug, Default)]
+pub struct Fields(HashMap<String, Option<Fields>>);
+
+impl Fields {
+    /// Takes a collection of dotted paths like `{"id", "user.name"}`, or a
+    /// nested dict like `{"id": True, "user": {"name": True}}`.
+    pub fn from_py(spec: &PyAny) -> PyResult<Fields> {
+        let mut fields = Fields::default();
+        if let Ok(dict) = spec.downcast_ref::<PyDict>() {
+            for (key, value) in dict.iter() {
+                let key: String = key.extract()?;
+                let nested = if value.downcast_ref::<PyDict>().is_ok() {
+                    Some(Fields::from_py(value)?)
+                } else if value.is_true()? {
+                    None
+                } else {
+                    continue;
+                };
+                fields.0.insert(key, nested);
+            }
+            return Ok(fields);
+        }
+        if spec.downcast_ref::<PyString>().is_ok() {
+            return Err(PyTypeError::py_err(
+                "fields must be a collection of paths or a dict, not a single str",
+            ));
+        }
+        for path in spec.iter()? {
+            let path: String = path?.extract()?;
+            fields.insert(&path)?;
+        }
+        Ok(fields)
+    }
+
+    fn insert(&mut self, path: &str) -> PyResult<()> {
+        if path.split('.').any(str::is_empty) {
+            return Err(PyValueError::py_err(format!(
+                "invalid field path: {:?}",
+                path
+            )));
+        }
+        let mut fields = self;
+        let mut keys = path.split('.').peekable();
+        while let Some(key) = keys.next() {
+            if keys.peek().is_none() {
+                // A shorter path takes the whole value
+                fields.0.insert(key.to_string(), None);
+                break;
+            }
+            fields = match fields
+                .0
+                .entry(key.to_string())
+                .or_insert_with(|| Some(Fields::default()))
+            {
+                Some(nested) => nested,
+                // Already taken as a whole
+                None => break,
+            };
+        }
+        Ok(())
+    }
+
+    /// Looks up `key`: `None` if it is not selected, `Some(None)` if its
+    /// whole value is.
+    pub fn get(&self, key: &str) -> Option<Option<&Fields>> {
+        self.0.get(key).map(Option::as_ref)
+    }
+}
@@ -0,0 +1,162 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use memmap::Mmap;
+use pyo3::exceptions::{OSError, TypeError as PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyBytes, PyString};
+
+/// Files at least this large are memory-mapped instead of read.
+const MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+/// Turns a `str`, `bytes` or `os.PathLike` into a path, like `open` does.
+pub fn path(py: Python, obj: &PyAny) -> PyResult<PathBuf> {
+    let path = py.import("os")?.call1("fspath", (obj,))?;
+    if let Ok(s) = <PyString as PyTryFrom>::try_from(path) {
+        return Ok(PathBuf::from(s.to_string()?.into_owned()));
+    }
+    if let Ok(b) = <PyBytes as PyTryFrom>::try_from(path) {
+        return Ok(PathBuf::from(os_string(b.as_bytes())?));
+    }
+    Err(PyTypeError::py_err(
+        "expected str, bytes or os.PathLike object",
+    ))
+}
+
+#[cfg(unix)]
+fn os_string(bytes: &[u8]) -> PyResult<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(std::ffi::OsStr::from_bytes(bytes).to_os_string())
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: &[u8]) -> PyResult<OsString> {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(OsString::from(s)),
+        Err(_) => Err(PyTypeError::py_err("bytes paths must be valid UTF-8")),
+    }
+}
+
+/// Converts an I/O error into the matching `OSError` subclass (such as
+/// `FileNotFoundError`), including the file name.
+pub fn os_error(err: &io::Error, path: &Path) -> PyErr {
+    let filename = path.to_string_lossy().into_owned();
+    match err.raw_os_error() {
+        Some(errno) => OSError::py_err((errno, err.to_string(), filename)),
+        None => OSError::py_err(format!("{}: {:?}", err, filename)),
+    }
+}
+
+/// The contents of a file, either memory-mapped or read into memory.
+pub enum Contents {
+    Mapped(Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Contents::Mapped(map) => map,
+            Contents::Read(data) => data,
+        }
+    }
+}
+
+/// Opens a file for parsing. Large files are memory-mapped, which assumes
+/// that they are not modified while they are parsed.
+pub fn read(path: &Path) -> PyResult<Contents> {
+    let read = || -> io::Result<Contents> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+        if len >= MMAP_THRESHOLD {
+            if let Ok(map) = unsafe { Mmap::map(&file) } {
+                return Ok(Contents::Mapped(map));
+            }
+        }
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data)?;
+        Ok(Contents::Read(data))
+    };
+    read().map_err(|err| os_error(&err, path))
+}
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Replaces the file at `path` atomically: `write` fills a temporary file in
+/// the same directory, which is then synced to disk and renamed over `path`.
+/// Readers see either the old or the new file, never a truncated one.
+///
+/// An existing file keeps its permissions. On errors, the temporary file is
+/// removed and the original file is left alone, except if only syncing the
+/// directory fails: the file has been replaced by then, which the error
+/// tells.
+pub fn write_atomic<F>(path: &Path, write: F) -> PyResult<()>
+where
+    F: FnOnce(&mut File) -> PyResult<()>,
+{
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().ok_or_else(|| {
+        os_error(
+            &io::Error::new(io::ErrorKind::InvalidInput, "not a file name"),
+            path,
+        )
+    })?;
+
+    let (temp_path, mut file) = loop {
+        let mut temp_name = OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp_path = dir.join(temp_name);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => break (temp_path, file),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(os_error(&err, &temp_path)),
+        }
+    };
+
+    let result = write(&mut file).and_then(|()| {
+        let finish = || -> io::Result<()> {
+            if let Ok(metadata) = fs::metadata(path) {
+                file.set_permissions(metadata.permissions())?;
+            }
+            file.sync_all()?;
+            fs::rename(&temp_path, path)
+        };
+        finish().map_err(|err| os_error(&err, path))
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make the rename itself durable
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|err| {
+            OSError::py_err(format!(
+                "{:?} was written but may not be durable: {}",
+                path.to_string_lossy(),
+                err
+            ))
+        })?;
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use std::cell::Cell;
+use std::io;
+
+use serde_json::ser::{CharEscape, Formatter};
+
+use crate::surrogates::Lone;
+
+/// Which characters get written as `\uXXXX` escapes on top of the ones JSON
+/// requires.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Escaping {
+    /// Escape everything outside of ASCII, like `json.dumps` does by default
+    pub ensure_ascii: bool,
+    /// Escape `<`, `>`, `&` and `'` as well as U+2028 and U+2029, which are
+    /// line terminators in JavaScript, so that the output can be embedded
+    /// in HTML `<script>` tags
+    pub html: bool,
+    /// Write `/` as `\/`
+    pub forward_slashes: bool,
+}
+
+impl Escaping {
+    /// Cheap check whether a byte of UTF-8 text may start a character that
+    /// needs escaping.
+    #[inline]
+    fn may_escape(self, b: u8) -> bool {
+        match b {
+            b'<' | b'>' | b'&' | b'\'' => self.html,
+            b'/' => self.forward_slashes,
+            // Lead byte of U+2028 and U+2029
+            0xE2 if self.html => true,
+            _ => b >= 0x80 && self.ensure_ascii,
+        }
+    }
+
+    #[inline]
+    fn escapes(self, c: char) -> bool {
+        match c {
+            '<' | '>' | '&' | '\'' => self.html,
+            '/' => self.forward_slashes,
+            '\u{2028}' | '\u{2029}' => self.html || self.ensure_ascii,
+            _ => !c.is_ascii() && self.ensure_ascii,
+        }
+    }
+}
+
+/// Wraps serde_json's compact or pretty formatter and adds the escaping
+/// that serde_json does not know about.
+///
+/// All structural output is delegated to the inner formatter.
+pub struct HyperJsonFormatter<'a, F> {
+    inner: F,
+    escaping: Escaping,
+    // The lone surrogates of the next string, see `surrogates::Lone`. They
+    // are set aside before each string that has any is serialized, as
+    // serde_json only passes on the rest of it.
+    pending: Option<&'a Cell<Lone>>,
+    // The lone surrogates of the string being written, how many of them are
+    // written and how much of the rest of it
+    lone: Lone,
+    written: usize,
+    offset: usize,
+}
+
+impl<'a, F: Formatter> HyperJsonFormatter<'a, F> {
+    pub fn new(inner: F, escaping: Escaping, pending: Option<&'a Cell<Lone>>) -> Self {
+        HyperJsonFormatter {
+            inner,
+            escaping,
+            pending,
+            lone: Lone::new(),
+            written: 0,
+            offset: 0,
+        }
+    }
+
+    /// Writes the lone surrogates that belong before `offset`, as `\udxxx`
+    /// escapes like `json` does.
+    fn write_lone<W>(&mut self, writer: &mut W, offset: usize) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        while let Some(&(pos, surrogate)) = self.lone.get(self.written) {
+            if pos > offset {
+                break;
+            }
+            write!(writer, "\\u{:04x}", surrogate)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+
+    fn write_escaped<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        let escaping = self.escaping;
+        if !fragment.bytes().any(|b| escaping.may_escape(b)) {
+            return self.inner.write_string_fragment(writer, fragment);
+        }
+        // All escapes are written in a single pass over the fragment
+        let mut start = 0;
+        for (i, c) in fragment.char_indices() {
+            if !escaping.escapes(c) {
+                continue;
+            }
+            self.inner
+                .write_string_fragment(writer, &fragment[start..i])?;
+            if c == '/' {
+                writer.write_all(b"\\/")?;
+            } else {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    write!(writer, "\\u{:04x}", unit)?;
+                }
+            }
+            start = i + c.len_utf8();
+        }
+        self.inner.write_string_fragment(writer, &fragment[start..])
+    }
+}
+
+impl<F: Formatter> Formatter for HyperJsonFormatter<'_, F> {
+    #[inline]
+    fn begin_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if let Some(pending) = self.pending {
+            self.lone = pending.take();
+            self.written = 0;
+            self.offset = 0;
+        }
+        self.inner.begin_string(writer)
+    }
+
+    #[inline]
+    fn end_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.write_lone(writer, usize::MAX)?;
+        self.inner.end_string(writer)
+    }
+
+    #[inline]
+    fn write_string_fragment<W>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut fragment = fragment;
+        // Splits the fragment where lone surrogates belong
+        while let Some(&(pos, _)) = self.lone.get(self.written) {
+            if pos >= self.offset + fragment.len() {
+                break;
+            }
+            let (before, after) = fragment.split_at(pos - self.offset);
+            self.write_escaped(writer, before)?;
+            self.offset = pos;
+            self.write_lone(writer, pos)?;
+            fragment = after;
+        }
+        self.offset += fragment.len();
+        self.write_escaped(writer, fragment)
+    }
+
+    #[inline]
+    fn write_char_escape<W>(&mut self, writer: &mut W, char_escape: CharEscape) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.write_lone(writer, self.offset)?;
+        // Each escaped character is a single byte
+        self.offset += 1;
+        self.inner.write_char_escape(writer, char_escape)
+    }
+
+    #[inline]
+    fn begin_array<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_array(writer)
+    }
+
+    #[inline]
+    fn end_array<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_array(writer)
+    }
+
+    #[inline]
+    fn begin_array_value<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    #[inline]
+    fn end_array_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_array_value(writer)
+    }
+
+    #[inline]
+    fn begin_object<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_object(writer)
+    }
+
+    #[inline]
+    fn end_object<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_object(writer)
+    }
+
+    #[inline]
+    fn begin_object_key<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_object_key(writer, first)
+    }
+
+    #[inline]
+    fn end_object_key<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_object_key(writer)
+    }
+
+    #[inline]
+    fn begin_object_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_object_value(writer)
+    }
+
+    #[inline]
+    fn end_object_value<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_object_value(writer)
+    }
+}
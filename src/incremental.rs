@@ -0,0 +1,281 @@
+// The code generated by `#[pyclass]` trips this lint
+#![allow(clippy::manual_div_ceil)]
+
+use std::ops::Range;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{TypeError as PyTypeError, ValueError as PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyString};
+use pyo3::PyNativeType;
+
+use crate::bytes;
+use crate::error::JSONDecodeError;
+use crate::{Kwargs, LoadsOptions};
+
+/// How much partial input is buffered by default.
+const MAX_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// Parses JSON values that arrive in pieces, for example from a socket.
+///
+/// `feed` appends to a buffer and returns the top-level values completed so
+/// far. Where values end is found by `Scanner`, which picks up where it left
+/// off, so a value spanning many chunks is scanned once and then parsed
+/// once. Values that are bare numbers or literals only end at the next
+/// whitespace or value, or on `close()`.
+#[pyclass]
+pub struct IncrementalDecoder {
+    options: LoadsOptions,
+    max_buffer_size: usize,
+    buf: Vec<u8>,
+    // Stream offset of the start of `buf`
+    base: usize,
+    scanner: Scanner,
+    // Values completed before an error, returned by the next call
+    ready: Vec<PyObject>,
+}
+
+#[pymethods]
+impl IncrementalDecoder {
+    #[new]
+    #[allow(clippy::new_ret_no_self)]
+    #[args(kwargs = "**")]
+    fn new(obj: &PyRawObject, kwargs: Option<&PyDict>) -> PyResult<()> {
+        let py = obj.py();
+        let kwargs = Kwargs::new(
+            py,
+            "IncrementalDecoder",
+            kwargs,
+            &[&[
+                "parse_float",
+                "parse_int",
+                "bytes_mode",
+                "bytes_fields",
+                "max_buffer_size",
+            ]],
+        )?;
+        let options = LoadsOptions::from_py(
+            py,
+            kwargs.get("parse_float"),
+            kwargs.get("parse_int"),
+            kwargs.get("bytes_mode"),
+            kwargs.get("bytes_fields"),
+            None,
+            None,
+        )?;
+        let max_buffer_size = match kwargs.get("max_buffer_size") {
+            Some(size) => size.extract(py)?,
+            None => MAX_BUFFER_SIZE,
+        };
+        obj.init(IncrementalDecoder {
+            options,
+            max_buffer_size,
+            buf: Vec::new(),
+            base: 0,
+            scanner: Scanner::default(),
+            ready: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Adds `data`, a `str` or bytes-like object, and returns a list of the
+    /// values completed by it.
+    fn feed(&mut self, py: Python, data: &PyAny) -> PyResult<PyObject> {
+        if let Ok(s) = <PyString as PyTryFrom>::try_from(data) {
+            self.buf.extend_from_slice(s.as_bytes()?);
+        } else {
+            let buffer = PyBuffer::get(py, data).map_err(|_| {
+                PyTypeError::py_err(format!(
+                    "feed() argument must be str or a bytes-like object, got: {}",
+                    data.get_type().name()
+                ))
+            })?;
+            self.buf.extend_from_slice(&bytes::borrow(py, &buffer)?);
+        }
+        let values = self.parse(py, false)?;
+        if self.buf.len() > self.max_buffer_size {
+            self.ready = values;
+            self.reset();
+            return Err(PyValueError::py_err(format!(
+                "more than max_buffer_size ({} bytes) of incomplete input",
+                self.max_buffer_size
+            )));
+        }
+        Ok(values.to_object(py))
+    }
+
+    /// Ends the input, returning any value still pending. Raises
+    /// `JSONDecodeError` if the input ends in the middle of a value. The
+    /// decoder can be fed again afterwards.
+    fn close(&mut self, py: Python) -> PyResult<PyObject> {
+        let values = self.parse(py, true);
+        self.reset();
+        Ok(values?.to_object(py))
+    }
+
+    /// The number of bytes buffered for values that are not complete yet.
+    #[getter]
+    fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl IncrementalDecoder {
+    /// Parses the values that are complete, then drops them from the buffer.
+    /// Values parsed before an error are kept for the next call.
+    fn parse(&mut self, py: Python, eof: bool) -> PyResult<Vec<PyObject>> {
+        let mut values = std::mem::take(&mut self.ready);
+        let result = loop {
+            let range = match self.scanner.next_value(&self.buf, eof) {
+                None => break Ok(()),
+                Some(Ok(range)) => range,
+                Some(Err(pos)) => {
+                    let message = if pos < self.buf.len() {
+                        format!(
+                            "unexpected {:?} at byte offset {}",
+                            self.buf[pos] as char,
+                            self.base + pos
+                        )
+                    } else {
+                        format!("unexpected end of data at byte offset {}", self.base + pos)
+                    };
+                    break Err(JSONDecodeError::py_err((message, String::new(), 0)));
+                }
+            };
+            match self.options.parse(py, &self.buf[range], None) {
+                Ok(value) => values.push(value),
+                Err(err) => break Err(err),
+            }
+        };
+        let consumed = self.scanner.consumed();
+        self.buf.drain(..consumed);
+        self.base += consumed;
+        self.scanner.shift(consumed);
+        match result {
+            Ok(()) => Ok(values),
+            Err(err) => {
+                self.ready = values;
+                Err(err)
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.base += self.buf.len();
+        self.buf.clear();
+        self.scanner = Scanner::default();
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+enum State {
+    #[default]
+    Between,
+    // A number or literal
+    Scalar,
+    // An object, an array or a string
+    Nested,
+}
+
+/// Finds where top-level values end, keeping its state between calls.
+#[derive(Default)]
+struct Scanner {
+    state: State,
+    pos: usize,
+    start: usize,
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+}
+
+impl Scanner {
+    /// Returns the range of the next complete value in `buf`, or the
+    /// position of a byte that cannot start a value. At `eof`, a pending
+    /// number or literal is complete and any other pending value is an error.
+    fn next_value(&mut self, buf: &[u8], eof: bool) -> Option<Result<Range<usize>, usize>> {
+        while self.pos < buf.len() {
+            let b = buf[self.pos];
+            match self.state {
+                State::Between => match b {
+                    b' ' | b'\t' | b'\r' | b'\n' => {}
+                    b'}' | b']' | b',' | b':' => {
+                        self.pos += 1;
+                        return Some(Err(self.pos - 1));
+                    }
+                    _ => {
+                        // The first byte is looked at again below
+                        self.start = self.pos;
+                        self.state = match b {
+                            b'{' | b'[' | b'"' => State::Nested,
+                            _ => State::Scalar,
+                        };
+                        self.depth = 0;
+                        self.in_string = false;
+                        self.escape = false;
+                        continue;
+                    }
+                },
+                State::Scalar => {
+                    if b" \t\r\n{}[]\",:".contains(&b) {
+                        return Some(Ok(self.complete(self.pos)));
+                    }
+                }
+                State::Nested => {
+                    self.nested(b);
+                    if self.depth == 0 && !self.in_string {
+                        return Some(Ok(self.complete(self.pos + 1)));
+                    }
+                }
+            }
+            self.pos += 1;
+        }
+        match self.state {
+            State::Scalar if eof => Some(Ok(self.complete(buf.len()))),
+            State::Nested if eof => {
+                self.state = State::Between;
+                Some(Err(buf.len()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Advances over one byte of an object, array or string.
+    fn nested(&mut self, b: u8) {
+        if self.escape {
+            self.escape = false;
+        } else if self.in_string {
+            match b {
+                b'\\' => self.escape = true,
+                b'"' => self.in_string = false,
+                _ => {}
+            }
+        } else {
+            match b {
+                b'"' => self.in_string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => self.depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    fn complete(&mut self, end: usize) -> Range<usize> {
+        self.state = State::Between;
+        self.pos = end;
+        self.start..end
+    }
+
+    /// How many bytes at the start of the buffer are no longer needed.
+    fn consumed(&self) -> usize {
+        match self.state {
+            State::Between => self.pos,
+            _ => self.start,
+        }
+    }
+
+    /// Adjusts the positions after `n` bytes were dropped from the buffer.
+    fn shift(&mut self, n: usize) {
+        self.pos -= n;
+        self.start = self.start.saturating_sub(n);
+    }
+}
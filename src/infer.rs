@@ -0,0 +1,293 @@
+//! Infers a JSON Schema (Draft 2020-12) from example documents.
+
+use std::collections::{HashMap, HashSet};
+
+use pyo3::exceptions::TypeError as PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyFloat, PyList};
+
+use crate::value::{elements, Kind};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+type Matcher = fn(&str) -> bool;
+
+/// String formats, in the order they are tried
+const FORMATS: &[(&str, Matcher)] = &[
+    ("date-time", is_date_time),
+    ("date", is_date),
+    ("uuid", is_uuid),
+    ("email", is_email),
+];
+
+/// Everything seen at one path of the documents
+#[derive(Default)]
+pub struct Shape {
+    null: bool,
+    boolean: bool,
+    integer: bool,
+    number: bool,
+    strings: Option<Strings>,
+    /// The elements of all arrays, if there were arrays
+    items: Option<Box<Shape>>,
+    object: Option<Object>,
+}
+
+struct Strings {
+    count: usize,
+    /// The distinct values in the order they were seen, until there are
+    /// more than the enum limit
+    values: Option<(Vec<String>, HashSet<String>)>,
+    /// The formats all strings so far have
+    formats: Vec<&'static str>,
+}
+
+#[derive(Default)]
+struct Object {
+    count: usize,
+    /// Members in the order they were first seen, with how many objects had
+    /// them
+    properties: Vec<(String, Shape, usize)>,
+    index: HashMap<String, usize>,
+}
+
+impl Shape {
+    /// Merges `obj` into what was seen so far.
+    pub fn add(&mut self, obj: &PyAny, enum_limit: usize) -> PyResult<()> {
+        match Kind::of(obj) {
+            Kind::Null => self.null = true,
+            Kind::Bool => self.boolean = true,
+            Kind::Number if obj.downcast_ref::<PyFloat>().is_ok() => self.number = true,
+            Kind::Number => self.integer = true,
+            Kind::String => {
+                let value: String = obj.extract()?;
+                let strings = self.strings.get_or_insert_with(|| Strings {
+                    count: 0,
+                    values: Some((Vec::new(), HashSet::new())),
+                    formats: FORMATS.iter().map(|(name, _)| *name).collect(),
+                });
+                strings.count += 1;
+                strings.formats.retain(|name| {
+                    FORMATS
+                        .iter()
+                        .any(|(format, matches)| format == name && matches(&value))
+                });
+                if let Some((values, seen)) = &mut strings.values {
+                    if !seen.contains(&value) {
+                        if values.len() == enum_limit {
+                            strings.values = None;
+                        } else {
+                            seen.insert(value.clone());
+                            values.push(value);
+                        }
+                    }
+                }
+            }
+            Kind::Array => {
+                let items = self.items.get_or_insert_with(Default::default);
+                for element in elements(obj).unwrap() {
+                    items.add(element, enum_limit)?;
+                }
+            }
+            Kind::Object => {
+                let object = self.object.get_or_insert_with(Default::default);
+                object.count += 1;
+                let dict: &PyDict = obj.downcast_ref()?;
+                for (key, value) in dict {
+                    let key: String = key.extract().map_err(|_| {
+                        PyTypeError::py_err(format!(
+                            "keys must be str, not {}",
+                            key.get_type().name()
+                        ))
+                    })?;
+                    let i = match object.index.get(&key) {
+                        Some(&i) => i,
+                        None => {
+                            object.index.insert(key.clone(), object.properties.len());
+                            object.properties.push((key, Shape::default(), 0));
+                            object.properties.len() - 1
+                        }
+                    };
+                    let (_, shape, count) = &mut object.properties[i];
+                    *count += 1;
+                    shape.add(value, enum_limit)?;
+                }
+            }
+            Kind::Other => {
+                return Err(PyTypeError::py_err(format!(
+                    "Type is not JSON serializable: {}",
+                    obj.get_type().name()
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the schema for the values seen, with `"$schema"` if `root`.
+    pub fn to_schema(&self, py: Python, root: bool) -> PyResult<PyObject> {
+        let schema = PyDict::new(py);
+        if root {
+            schema.set_item("$schema", DRAFT)?;
+        }
+        let mut types = Vec::new();
+        if self.null {
+            types.push("null");
+        }
+        if self.boolean {
+            types.push("boolean");
+        }
+        if self.number {
+            types.push("number");
+        } else if self.integer {
+            types.push("integer");
+        }
+        if self.strings.is_some() {
+            types.push("string");
+        }
+        if self.items.is_some() {
+            types.push("array");
+        }
+        if self.object.is_some() {
+            types.push("object");
+        }
+        match types.as_slice() {
+            [] => {}
+            [name] => schema.set_item("type", name)?,
+            _ => schema.set_item("type", &types)?,
+        }
+        if let Some(strings) = &self.strings {
+            // `enum` applies to every type, so only nulls may come along
+            let only_strings = types.len() == 1 || (types.len() == 2 && self.null);
+            match &strings.values {
+                Some((values, _)) if only_strings && values.len() < strings.count => {
+                    let values = PyList::new(py, values);
+                    if self.null {
+                        values.append(py.None())?;
+                    }
+                    schema.set_item("enum", values)?;
+                }
+                _ => {
+                    if let Some(format) = strings.formats.first() {
+                        schema.set_item("format", format)?;
+                    }
+                }
+            }
+        }
+        if let Some(items) = &self.items {
+            if !items.is_empty() {
+                schema.set_item("items", items.to_schema(py, false)?)?;
+            }
+        }
+        if let Some(object) = &self.object {
+            let properties = PyDict::new(py);
+            let mut required = Vec::new();
+            for (key, shape, count) in &object.properties {
+                properties.set_item(key, shape.to_schema(py, false)?)?;
+                if *count == object.count {
+                    required.push(key);
+                }
+            }
+            schema.set_item("properties", properties)?;
+            if !required.is_empty() {
+                schema.set_item("required", required)?;
+            }
+        }
+        Ok(schema.to_object(py))
+    }
+
+    fn is_empty(&self) -> bool {
+        !(self.null
+            || self.boolean
+            || self.integer
+            || self.number
+            || self.strings.is_some()
+            || self.items.is_some()
+            || self.object.is_some())
+    }
+}
+
+fn digits(s: &str, range: std::ops::Range<usize>) -> Option<u32> {
+    let part = s.get(range)?;
+    if part.bytes().all(|b| b.is_ascii_digit()) {
+        part.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// `YYYY-MM-DD` as in RFC 3339
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 10
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && digits(s, 0..4).is_some()
+        && digits(s, 5..7).is_some_and(|month| (1..=12).contains(&month))
+        && digits(s, 8..10).is_some_and(|day| (1..=31).contains(&day))
+}
+
+/// An RFC 3339 `date-time` such as `2019-12-01T12:30:00.5+01:00`
+fn is_date_time(s: &str) -> bool {
+    let b = s.as_bytes();
+    if !s.is_ascii() || b.len() < 20 || !is_date(&s[..10]) || !matches!(b[10], b'T' | b't') {
+        return false;
+    }
+    let time = &s[11..];
+    let tb = time.as_bytes();
+    if tb[2] != b':'
+        || tb[5] != b':'
+        || !digits(time, 0..2).is_some_and(|hour| hour < 24)
+        || !digits(time, 3..5).is_some_and(|minute| minute < 60)
+        || !digits(time, 6..8).is_some_and(|second| second <= 60)
+    {
+        return false;
+    }
+    let mut rest = &time[8..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let end = fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+        if end == 0 {
+            return false;
+        }
+        rest = &fraction[end..];
+    }
+    match rest.as_bytes() {
+        [b'Z'] | [b'z'] => true,
+        [b'+', ..] | [b'-', ..] if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            digits(rest, 1..3).is_some_and(|hour| hour < 24)
+                && digits(rest, 4..6).is_some_and(|minute| minute < 60)
+        }
+        _ => false,
+    }
+}
+
+/// `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` with hexadecimal digits
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+}
+
+/// A plain `local@domain.tld` address, without quoting or IP literals
+fn is_email(s: &str) -> bool {
+    let (local, domain) = match s.rfind('@') {
+        Some(at) => (&s[..at], &s[at + 1..]),
+        None => return false,
+    };
+    let atext = |c: char| c.is_alphanumeric() || "!#$%&'*+/=?^_`{|}~-".contains(c);
+    !local.is_empty()
+        && local.len() <= 64
+        && local
+            .split('.')
+            .all(|atom| !atom.is_empty() && atom.chars().all(atext))
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        })
+}
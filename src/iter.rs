@@ -0,0 +1,257 @@
+// The code generated by `#[pyclass]` trips this lint
+#![allow(clippy::manual_div_ceil)]
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+use pyo3::PyIterProtocol;
+use std::fmt;
+
+use serde::de::{Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::error::JSONDecodeError;
+use crate::reader::ChunkSource;
+use crate::LoadsOptions;
+
+/// Where the values come from.
+pub enum Source {
+    /// A `str` or `bytes` object, parsed in place
+    Object(PyObject),
+    /// A copy of some other bytes-like object, kept in `buf`
+    Buffer,
+    /// A file object, read into `buf` as needed
+    File(ChunkSource),
+}
+
+/// Iterates over a sequence of JSON values, such as JSON Lines or values
+/// that are simply concatenated, yielding one Python object per value.
+///
+/// serde_json's `StreamDeserializer` first finds where each value ends,
+/// without creating any Python objects, so that a value cut off at the end
+/// of a chunk is only built once it is complete. With
+/// `skip_invalid`, a malformed record is skipped up to the end of the line
+/// it starts on, and its line number, byte offset and error message are
+/// added to `skipped`.
+#[pyclass]
+pub struct JsonIterator {
+    options: LoadsOptions,
+    source: Source,
+    buf: Vec<u8>,
+    // Position of the next value in the data
+    pos: usize,
+    // Stream offset of the start of `buf`, which is drained for files
+    base: usize,
+    // Number of lines before `pos`
+    line: usize,
+    skip_invalid: bool,
+    done: bool,
+    #[pyo3(get)]
+    skipped: Vec<(usize, usize, String)>,
+}
+
+impl JsonIterator {
+    pub(crate) fn new(
+        options: LoadsOptions,
+        source: Source,
+        buf: Vec<u8>,
+        skip_invalid: bool,
+    ) -> JsonIterator {
+        JsonIterator {
+            options,
+            source,
+            buf,
+            pos: 0,
+            base: 0,
+            line: 0,
+            skip_invalid,
+            done: false,
+            skipped: Vec::new(),
+        }
+    }
+
+    fn next_value(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        loop {
+            let eof = match &self.source {
+                Source::File(source) => source.is_eof(),
+                _ => true,
+            };
+            let data = match &self.source {
+                Source::Object(obj) => match obj.cast_as::<PyString>(py) {
+                    Ok(s) => s.as_bytes()?,
+                    Err(_) => obj.cast_as::<PyBytes>(py)?.as_bytes(),
+                },
+                _ => &self.buf[..],
+            };
+            if self.base + self.pos == 0 && data.starts_with(b"\xEF\xBB\xBF") {
+                self.pos = 3;
+            }
+            let rest = &data[self.pos..];
+
+            let mut stream = serde_json::Deserializer::from_slice(rest).into_iter::<Skip>();
+            let result = stream.next();
+            let end = stream.byte_offset();
+            let e = match result {
+                None if !eof => {
+                    self.fill(py)?;
+                    continue;
+                }
+                None => {
+                    self.done = true;
+                    return Ok(None);
+                }
+                // A number at the end of the buffer may continue in the
+                // next chunk
+                Some(Ok(_)) if end == rest.len() && !eof => {
+                    self.fill(py)?;
+                    continue;
+                }
+                Some(Ok(_)) => {
+                    let mut deserializer = serde_json::Deserializer::from_slice(&rest[..end]);
+                    match self.options.seed(py, None).deserialize(&mut deserializer) {
+                        Ok(value) => {
+                            self.line += count_lines(&rest[..end]);
+                            self.pos += end;
+                            return Ok(Some(value));
+                        }
+                        Err(e) => e,
+                    }
+                }
+                Some(Err(ref e)) if e.is_eof() && !eof => {
+                    self.fill(py)?;
+                    continue;
+                }
+                Some(Err(e)) => e,
+            };
+
+            let start = rest
+                .iter()
+                .position(|b| !b" \t\r\n".contains(b))
+                .unwrap_or(rest.len());
+            let lineno = self.line + count_lines(&rest[..start]) + 1;
+            let offset = self.base + self.pos + start;
+            let message = message(&e);
+            if !self.skip_invalid {
+                self.done = true;
+                let message = format!("{} (line {}, byte offset {})", message, lineno, offset);
+                // Errors raised by Python while building the value, such as
+                // in `parse_float`, are not about the syntax
+                return Err(if e.is_data() {
+                    PyValueError::py_err(message)
+                } else {
+                    JSONDecodeError::py_err((message, String::new(), 0))
+                });
+            }
+            let skip = match rest[start..].iter().position(|&b| b == b'\n') {
+                Some(n) => start + n + 1,
+                None if !eof => {
+                    self.fill(py)?;
+                    continue;
+                }
+                None => rest.len(),
+            };
+            self.line += count_lines(&rest[..skip]);
+            self.pos += skip;
+            self.skipped.push((lineno, offset, message));
+        }
+    }
+
+    /// Reads more of a file, dropping what has been parsed. At least as much
+    /// is read as is left over, so that a record spanning many chunks is
+    /// only scanned again a logarithmic number of times.
+    fn fill(&mut self, py: Python) -> PyResult<()> {
+        if let Source::File(source) = &mut self.source {
+            self.buf.drain(..self.pos);
+            self.base += self.pos;
+            self.pos = 0;
+            let pending = self.buf.len();
+            while source.read_chunk(py, &mut self.buf)? && self.buf.len() < 2 * pending {}
+        }
+        Ok(())
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for JsonIterator {
+    fn __iter__(slf: PyRefMut<Self>) -> PyResult<Py<JsonIterator>> {
+        Ok(slf.into())
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = unsafe { Python::assume_gil_acquired() };
+        if slf.done {
+            return Ok(None);
+        }
+        slf.next_value(py)
+    }
+}
+
+/// A value that is parsed but not kept. Unlike `IgnoredAny`, numbers are
+/// parsed the way values are built, so one cut off at the end of the
+/// buffer is reported as such.
+struct Skip;
+
+impl<'de> Deserialize<'de> for Skip {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Skip, D::Error> {
+        deserializer.deserialize_any(Skip)
+    }
+}
+
+impl<'de> Visitor<'de> for Skip {
+    type Value = Skip;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_bytes<E>(self, _: &[u8]) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_unit<E>(self) -> Result<Skip, E> {
+        Ok(Skip)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Skip, A::Error> {
+        while seq.next_element::<Skip>()?.is_some() {}
+        Ok(Skip)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Skip, A::Error> {
+        while map.next_entry::<Skip, Skip>()?.is_some() {}
+        Ok(Skip)
+    }
+}
+
+fn count_lines(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b == b'\n').count()
+}
+
+/// The error message without the position, which is relative to the start
+/// of the record.
+fn message(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    match message.rfind(" at line ") {
+        Some(n) => message[..n].to_string(),
+        None => message,
+    }
+}
@@ -0,0 +1,1025 @@
+//! JSONPath queries as specified in RFC 9535.
+//!
+//! A query is parsed into a tree of segments and selectors, checking the
+//! types of function calls and comparisons the way the RFC requires, and is
+//! then evaluated against Python objects. Dicts are JSON objects, lists and
+//! tuples are arrays, like in `dumps`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use pyo3::class::basic::CompareOp;
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyList, PyTuple};
+use regex::Regex;
+
+use crate::pointer::Token;
+use crate::value::{elements, is_array, is_object, is_string, json_equal, Kind};
+
+/// The largest integer an index or a slice bound may be, as in I-JSON.
+const MAX_INT: i64 = (1 << 53) - 1;
+
+pub struct Query {
+    // `$` or `@`
+    root: bool,
+    segments: Vec<Segment>,
+}
+
+struct Segment {
+    descendant: bool,
+    selectors: Vec<Selector>,
+}
+
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Expr),
+}
+
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    // A query that selects at least one node, or a function returning a
+    // logical value
+    Test(Operand),
+    Compare(Operand, Op, Operand),
+}
+
+enum Operand {
+    Literal(PyObject),
+    Query(Query),
+    Function(Function, Vec<Operand>),
+}
+
+#[derive(Copy, Clone)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Function {
+    Length,
+    Count,
+    Match,
+    Search,
+    Value,
+}
+
+/// The types of function parameters and results in RFC 9535.
+#[derive(Copy, Clone, PartialEq)]
+enum Type {
+    Value,
+    Logical,
+    Nodes,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "length" => Some(Function::Length),
+            "count" => Some(Function::Count),
+            "match" => Some(Function::Match),
+            "search" => Some(Function::Search),
+            "value" => Some(Function::Value),
+            _ => None,
+        }
+    }
+
+    fn parameters(self) -> &'static [Type] {
+        match self {
+            Function::Length => &[Type::Value],
+            Function::Count | Function::Value => &[Type::Nodes],
+            Function::Match | Function::Search => &[Type::Value, Type::Value],
+        }
+    }
+
+    fn result(self) -> Type {
+        match self {
+            Function::Match | Function::Search => Type::Logical,
+            _ => Type::Value,
+        }
+    }
+}
+
+impl Query {
+    /// Tells whether the query always selects at most one node.
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|segment| {
+            !segment.descendant
+                && matches!(
+                    segment.selectors.as_slice(),
+                    [Selector::Name(_)] | [Selector::Index(_)]
+                )
+        })
+    }
+
+    /// The leading segments that select a single member or a single
+    /// non-negative index, which can be followed without parsing anything
+    /// else. Queries that refer to the root in a filter need the whole
+    /// document, so for them this is empty.
+    pub fn tokens(&self) -> Vec<Token> {
+        if self.uses_root() {
+            return Vec::new();
+        }
+        let mut tokens = Vec::new();
+        for segment in &self.segments {
+            let token = match (segment.descendant, segment.selectors.as_slice()) {
+                (false, [Selector::Name(name)]) => Token {
+                    name: Some(name.clone()),
+                    index: None,
+                },
+                (false, [Selector::Index(index)]) if *index >= 0 => Token {
+                    name: None,
+                    index: Some(*index as usize),
+                },
+                _ => break,
+            };
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn uses_root(&self) -> bool {
+        self.segments
+            .iter()
+            .flat_map(|segment| &segment.selectors)
+            .any(|selector| match selector {
+                Selector::Filter(expr) => expr.uses_root(),
+                _ => false,
+            })
+    }
+
+    /// Returns the values of the nodes the query selects in `root`, skipping
+    /// the first `skip` segments, which `root` has already been narrowed
+    /// down by.
+    pub fn select<'a>(
+        &'a self,
+        py: Python<'a>,
+        root: &'a PyAny,
+        skip: usize,
+    ) -> PyResult<Vec<&'a PyAny>> {
+        let context = Context {
+            py,
+            root,
+            regexes: RefCell::new(HashMap::new()),
+        };
+        let mut nodes = vec![root];
+        for segment in &self.segments[skip..] {
+            nodes = context.segment(segment, &nodes)?;
+        }
+        Ok(nodes)
+    }
+}
+
+impl Expr {
+    fn uses_root(&self) -> bool {
+        match self {
+            Expr::Or(exprs) | Expr::And(exprs) => exprs.iter().any(Expr::uses_root),
+            Expr::Not(expr) => expr.uses_root(),
+            Expr::Test(operand) => operand.uses_root(),
+            Expr::Compare(left, _, right) => left.uses_root() || right.uses_root(),
+        }
+    }
+}
+
+impl Operand {
+    fn uses_root(&self) -> bool {
+        match self {
+            Operand::Literal(_) => false,
+            Operand::Query(query) => query.root || query.uses_root(),
+            Operand::Function(_, args) => args.iter().any(Operand::uses_root),
+        }
+    }
+
+    /// Whether the operand can stand for a single value: in a comparison or
+    /// as an argument for a `ValueType` parameter.
+    fn is_value(&self) -> bool {
+        match self {
+            Operand::Literal(_) => true,
+            Operand::Query(query) => query.is_singular(),
+            Operand::Function(function, _) => function.result() == Type::Value,
+        }
+    }
+}
+
+/// Parses a JSONPath query, raising `ValueError` if it is not valid.
+pub fn parse(py: Python, query: &str) -> PyResult<Query> {
+    let mut parser = Parser {
+        py,
+        query,
+        chars: query.chars().collect(),
+        pos: 0,
+    };
+    parser.expect('$')?;
+    let segments = parser.segments()?;
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected character"));
+    }
+    Ok(Query {
+        root: true,
+        segments,
+    })
+}
+
+struct Parser<'a> {
+    py: Python<'a>,
+    query: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> PyErr {
+        PyValueError::py_err(format!(
+            "invalid JSONPath query {:?}: {} at position {}",
+            self.query, message, self.pos
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let end = self.pos + s.chars().count();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().cloned().eq(s.chars()) {
+            self.pos = end;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> PyResult<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", c)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn segments(&mut self) -> PyResult<Vec<Segment>> {
+        let mut segments = Vec::new();
+        loop {
+            // Whitespace is only allowed before another segment
+            let start = self.pos;
+            self.skip_whitespace();
+            match self.peek() {
+                Some('[') | Some('.') => segments.push(self.segment()?),
+                _ => {
+                    self.pos = start;
+                    return Ok(segments);
+                }
+            }
+        }
+    }
+
+    fn segment(&mut self) -> PyResult<Segment> {
+        if self.eat('[') {
+            return Ok(Segment {
+                descendant: false,
+                selectors: self.bracketed()?,
+            });
+        }
+        self.expect('.')?;
+        let descendant = self.eat('.');
+        let selectors = if self.eat('*') {
+            vec![Selector::Wildcard]
+        } else if descendant && self.eat('[') {
+            self.bracketed()?
+        } else {
+            vec![Selector::Name(self.member_name()?)]
+        };
+        Ok(Segment {
+            descendant,
+            selectors,
+        })
+    }
+
+    fn member_name(&mut self) -> PyResult<String> {
+        let first = |c: char| c.is_ascii_alphabetic() || c == '_' || c as u32 >= 0x80;
+        match self.peek() {
+            Some(c) if first(c) => {}
+            _ => return Err(self.error("expected a member name")),
+        }
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if !first(c) && !c.is_ascii_digit() {
+                break;
+            }
+            name.push(c);
+            self.pos += 1;
+        }
+        Ok(name)
+    }
+
+    /// Parses the selectors after a `[`.
+    fn bracketed(&mut self) -> PyResult<Vec<Selector>> {
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.selector()?);
+            self.skip_whitespace();
+            if !self.eat(',') {
+                self.expect(']')?;
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn selector(&mut self) -> PyResult<Selector> {
+        match self.peek() {
+            Some('\'') | Some('"') => return Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.pos += 1;
+                return Ok(Selector::Wildcard);
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                return Ok(Selector::Filter(self.or()?));
+            }
+            _ => {}
+        }
+        let start = self.optional_int()?;
+        self.skip_whitespace();
+        if !self.eat(':') {
+            return match start {
+                Some(index) => Ok(Selector::Index(index)),
+                None => Err(self.error("expected a selector")),
+            };
+        }
+        self.skip_whitespace();
+        let end = self.optional_int()?;
+        self.skip_whitespace();
+        let step = if self.eat(':') {
+            self.skip_whitespace();
+            self.optional_int()?
+        } else {
+            None
+        };
+        Ok(Selector::Slice(start, end, step))
+    }
+
+    fn optional_int(&mut self) -> PyResult<Option<i64>> {
+        match self.peek() {
+            Some('-') => {}
+            Some(c) if c.is_ascii_digit() => {}
+            _ => return Ok(None),
+        }
+        let start = self.pos;
+        self.eat('-');
+        let digits = self.digits();
+        let text: String = self.chars[start..self.pos].iter().collect();
+        if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) || text == "-0" {
+            self.pos = start;
+            return Err(self.error("invalid integer"));
+        }
+        match text.parse::<i64>() {
+            Ok(int) if (-MAX_INT..=MAX_INT).contains(&int) => Ok(Some(int)),
+            _ => {
+                self.pos = start;
+                Err(self.error("integer out of range"))
+            }
+        }
+    }
+
+    fn digits(&mut self) -> String {
+        let mut digits = String::new();
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.pos += 1;
+        }
+        digits
+    }
+
+    /// Parses a string literal in single or double quotes.
+    fn string(&mut self) -> PyResult<String> {
+        let quote = self.chars[self.pos];
+        self.pos += 1;
+        let mut string = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match c {
+                _ if c == quote => return Ok(string),
+                '\\' => string.push(self.escape(quote)?),
+                '\u{0}'..='\u{1f}' => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                _ => string.push(c),
+            }
+        }
+    }
+
+    fn escape(&mut self, quote: char) -> PyResult<char> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("unterminated string")),
+        };
+        self.pos += 1;
+        Ok(match c {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '/' | '\\' => c,
+            _ if c == quote => c,
+            'u' => {
+                let high = self.hex()?;
+                let code = match high {
+                    0xD800..=0xDBFF => {
+                        if !self.eat_str("\\u") {
+                            return Err(self.error("lone surrogate in string"));
+                        }
+                        match self.hex()? {
+                            low @ 0xDC00..=0xDFFF => {
+                                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            _ => return Err(self.error("lone surrogate in string")),
+                        }
+                    }
+                    0xDC00..=0xDFFF => return Err(self.error("lone surrogate in string")),
+                    _ => high,
+                };
+                std::char::from_u32(code).expect("surrogates are handled above")
+            }
+            _ => return Err(self.error("invalid escape")),
+        })
+    }
+
+    fn hex(&mut self) -> PyResult<u32> {
+        let end = self.pos + 4;
+        let hex: String = self.chars[self.pos..end.min(self.chars.len())]
+            .iter()
+            .collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(code) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                self.pos = end;
+                Ok(code)
+            }
+            _ => Err(self.error("invalid \\u escape")),
+        }
+    }
+
+    fn or(&mut self) -> PyResult<Expr> {
+        let mut exprs = vec![self.and()?];
+        loop {
+            let start = self.pos;
+            self.skip_whitespace();
+            if !self.eat_str("||") {
+                self.pos = start;
+                break;
+            }
+            self.skip_whitespace();
+            exprs.push(self.and()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::Or(exprs),
+        })
+    }
+
+    fn and(&mut self) -> PyResult<Expr> {
+        let mut exprs = vec![self.basic()?];
+        loop {
+            let start = self.pos;
+            self.skip_whitespace();
+            if !self.eat_str("&&") {
+                self.pos = start;
+                break;
+            }
+            self.skip_whitespace();
+            exprs.push(self.basic()?);
+        }
+        Ok(match exprs.len() {
+            1 => exprs.pop().unwrap(),
+            _ => Expr::And(exprs),
+        })
+    }
+
+    fn basic(&mut self) -> PyResult<Expr> {
+        if self.eat('!') {
+            self.skip_whitespace();
+            let expr = if self.peek() == Some('(') {
+                self.parenthesized()?
+            } else {
+                let operand = self.operand()?;
+                self.test(operand)?
+            };
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        if self.peek() == Some('(') {
+            return self.parenthesized();
+        }
+        let left = self.operand()?;
+        let start = self.pos;
+        self.skip_whitespace();
+        let op = match self.comparison_op() {
+            Some(op) => op,
+            None => {
+                self.pos = start;
+                return self.test(left);
+            }
+        };
+        self.skip_whitespace();
+        let right = self.operand()?;
+        if !left.is_value() || !right.is_value() {
+            return Err(self.error(
+                "comparisons need literals, singular queries or functions returning a value",
+            ));
+        }
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    fn parenthesized(&mut self) -> PyResult<Expr> {
+        self.expect('(')?;
+        self.skip_whitespace();
+        let expr = self.or()?;
+        self.skip_whitespace();
+        self.expect(')')?;
+        Ok(expr)
+    }
+
+    fn test(&self, operand: Operand) -> PyResult<Expr> {
+        match &operand {
+            Operand::Query(_) => {}
+            Operand::Function(function, _) if function.result() != Type::Value => {}
+            Operand::Function(..) => {
+                return Err(self.error("a function returning a value is not a test"))
+            }
+            Operand::Literal(_) => return Err(self.error("a literal is not a test")),
+        }
+        Ok(Expr::Test(operand))
+    }
+
+    fn comparison_op(&mut self) -> Option<Op> {
+        for &(s, op) in &[
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ] {
+            if self.eat_str(s) {
+                return Some(op);
+            }
+        }
+        None
+    }
+
+    fn operand(&mut self) -> PyResult<Operand> {
+        let py = self.py;
+        match self.peek() {
+            Some('@') | Some('$') => {
+                let root = self.chars[self.pos] == '$';
+                self.pos += 1;
+                let segments = self.segments()?;
+                Ok(Operand::Query(Query { root, segments }))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Literal(self.string()?.to_object(py))),
+            Some('-') => self.number(),
+            Some(c) if c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_ascii_lowercase() => {
+                let start = self.pos;
+                let mut name = String::new();
+                while let Some(c) = self.peek() {
+                    if !c.is_ascii_lowercase() && !c.is_ascii_digit() && c != '_' {
+                        break;
+                    }
+                    name.push(c);
+                    self.pos += 1;
+                }
+                if self.peek() == Some('(') {
+                    self.pos = start;
+                    return self.function(&name);
+                }
+                match name.as_str() {
+                    "true" => Ok(Operand::Literal(true.to_object(py))),
+                    "false" => Ok(Operand::Literal(false.to_object(py))),
+                    "null" => Ok(Operand::Literal(py.None())),
+                    _ => {
+                        self.pos = start;
+                        Err(self.error("expected a query, a literal or a function"))
+                    }
+                }
+            }
+            _ => Err(self.error("expected a query, a literal or a function")),
+        }
+    }
+
+    fn function(&mut self, name: &str) -> PyResult<Operand> {
+        let function = match Function::from_name(name) {
+            Some(function) => function,
+            None => return Err(self.error(&format!("unknown function {}()", name))),
+        };
+        self.pos += name.len();
+        self.expect('(')?;
+        self.skip_whitespace();
+        let mut args = Vec::new();
+        if !self.eat(')') {
+            loop {
+                args.push(self.operand()?);
+                self.skip_whitespace();
+                if !self.eat(',') {
+                    self.expect(')')?;
+                    break;
+                }
+                self.skip_whitespace();
+            }
+        }
+        let parameters = function.parameters();
+        if args.len() != parameters.len() {
+            return Err(self.error(&format!(
+                "{}() takes {} argument(s)",
+                name,
+                parameters.len()
+            )));
+        }
+        for (arg, parameter) in args.iter().zip(parameters) {
+            let valid = match (parameter, arg) {
+                (Type::Nodes, Operand::Query(_)) => true,
+                (Type::Value, arg) => arg.is_value(),
+                _ => false,
+            };
+            if !valid {
+                return Err(self.error(&format!("invalid argument for {}()", name)));
+            }
+        }
+        Ok(Operand::Function(function, args))
+    }
+
+    fn number(&mut self) -> PyResult<Operand> {
+        let start = self.pos;
+        self.eat('-');
+        let int = self.digits();
+        let mut float = false;
+        if int.is_empty() || (int.len() > 1 && int.starts_with('0')) {
+            self.pos = start;
+            return Err(self.error("invalid number"));
+        }
+        if self.eat('.') {
+            float = true;
+            if self.digits().is_empty() {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if self.eat('e') || self.eat('E') {
+            float = true;
+            if !self.eat('+') {
+                self.eat('-');
+            }
+            if self.digits().is_empty() {
+                return Err(self.error("invalid number"));
+            }
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let py = self.py;
+        match text.parse::<i64>() {
+            Ok(int) if !float => Ok(Operand::Literal(int.to_object(py))),
+            _ => match text.parse::<f64>() {
+                Ok(float) => Ok(Operand::Literal(float.to_object(py))),
+                Err(_) => Err(self.error("invalid number")),
+            },
+        }
+    }
+}
+
+/// The value of an operand, which is missing for a singular query that
+/// selects nothing. Function results are new objects.
+enum Value<'a> {
+    Nothing,
+    Node(&'a PyAny),
+    Owned(PyObject),
+}
+
+impl<'a> Value<'a> {
+    fn get<'b>(&'b self, py: Python<'b>) -> Option<&'b PyAny> {
+        match self {
+            Value::Nothing => None,
+            Value::Node(node) => Some(node),
+            Value::Owned(obj) => obj.extract(py).ok(),
+        }
+    }
+}
+
+struct Context<'a> {
+    py: Python<'a>,
+    root: &'a PyAny,
+    // Compiled patterns of `match` and `search`, which are `None` if invalid
+    regexes: RefCell<HashMap<(String, bool), Option<Regex>>>,
+}
+
+impl<'a> Context<'a> {
+    fn query(&self, query: &'a Query, current: &'a PyAny) -> PyResult<Vec<&'a PyAny>> {
+        let mut nodes = vec![if query.root { self.root } else { current }];
+        for segment in &query.segments {
+            nodes = self.segment(segment, &nodes)?;
+        }
+        Ok(nodes)
+    }
+
+    fn segment(&self, segment: &'a Segment, nodes: &[&'a PyAny]) -> PyResult<Vec<&'a PyAny>> {
+        let mut selected = Vec::new();
+        for &node in nodes {
+            if segment.descendant {
+                self.descend(segment, node, &mut selected)?;
+            } else {
+                for selector in &segment.selectors {
+                    self.select(selector, node, &mut selected)?;
+                }
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Applies the selectors to `node` and then to its descendants, each
+    /// node before its children.
+    fn descend(
+        &self,
+        segment: &'a Segment,
+        node: &'a PyAny,
+        selected: &mut Vec<&'a PyAny>,
+    ) -> PyResult<()> {
+        for selector in &segment.selectors {
+            self.select(selector, node, selected)?;
+        }
+        for child in children(node) {
+            self.descend(segment, child, selected)?;
+        }
+        Ok(())
+    }
+
+    fn select(
+        &self,
+        selector: &'a Selector,
+        node: &'a PyAny,
+        selected: &mut Vec<&'a PyAny>,
+    ) -> PyResult<()> {
+        match selector {
+            Selector::Name(name) => {
+                if let Ok(dict) = node.downcast_ref::<PyDict>() {
+                    selected.extend(dict.get_item(name.as_str()));
+                }
+            }
+            Selector::Wildcard => selected.extend(children(node)),
+            Selector::Index(index) => selected.extend(element(node, *index)),
+            Selector::Slice(start, end, step) => {
+                if let Some(elements) = elements(node) {
+                    for index in slice(elements.len() as i64, *start, *end, *step) {
+                        selected.push(elements[index as usize]);
+                    }
+                }
+            }
+            Selector::Filter(expr) => {
+                for child in children(node) {
+                    if self.test(expr, child)? {
+                        selected.push(child);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn test(&self, expr: &'a Expr, current: &'a PyAny) -> PyResult<bool> {
+        match expr {
+            Expr::Or(exprs) => {
+                for expr in exprs {
+                    if self.test(expr, current)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Expr::And(exprs) => {
+                for expr in exprs {
+                    if !self.test(expr, current)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Expr::Not(expr) => Ok(!self.test(expr, current)?),
+            Expr::Test(Operand::Query(query)) => Ok(!self.query(query, current)?.is_empty()),
+            Expr::Test(Operand::Function(function, args)) => self.logical(*function, args, current),
+            Expr::Test(Operand::Literal(_)) => Ok(false),
+            Expr::Compare(left, op, right) => {
+                let left = self.value(left, current)?;
+                let right = self.value(right, current)?;
+                compare(self.py, left.get(self.py), *op, right.get(self.py))
+            }
+        }
+    }
+
+    fn value(&self, operand: &'a Operand, current: &'a PyAny) -> PyResult<Value<'a>> {
+        let py = self.py;
+        Ok(match operand {
+            Operand::Literal(obj) => Value::Node(obj.extract(py)?),
+            Operand::Query(query) => match self.query(query, current)?.first() {
+                Some(node) => Value::Node(node),
+                None => Value::Nothing,
+            },
+            Operand::Function(Function::Length, args) => {
+                let arg = self.value(&args[0], current)?;
+                let len = match arg.get(py) {
+                    Some(obj) if is_string(obj) || is_array(obj) || is_object(obj) => obj.len()?,
+                    _ => return Ok(Value::Nothing),
+                };
+                Value::Owned(len.to_object(py))
+            }
+            Operand::Function(Function::Count, args) => {
+                Value::Owned(self.nodes(&args[0], current)?.len().to_object(py))
+            }
+            Operand::Function(Function::Value, args) => {
+                match self.nodes(&args[0], current)?.as_slice() {
+                    [node] => Value::Node(node),
+                    _ => Value::Nothing,
+                }
+            }
+            Operand::Function(function, args) => {
+                Value::Owned(self.logical(*function, args, current)?.to_object(py))
+            }
+        })
+    }
+
+    fn nodes(&self, operand: &'a Operand, current: &'a PyAny) -> PyResult<Vec<&'a PyAny>> {
+        match operand {
+            Operand::Query(query) => self.query(query, current),
+            _ => unreachable!("arguments are checked by the parser"),
+        }
+    }
+
+    /// Evaluates `match` or `search`.
+    fn logical(
+        &self,
+        function: Function,
+        args: &'a [Operand],
+        current: &'a PyAny,
+    ) -> PyResult<bool> {
+        let py = self.py;
+        let value = self.value(&args[0], current)?;
+        let pattern = self.value(&args[1], current)?;
+        let (value, pattern) = match (value.get(py), pattern.get(py)) {
+            (Some(value), Some(pattern)) if is_string(value) && is_string(pattern) => {
+                (value.extract::<String>()?, pattern.extract::<String>()?)
+            }
+            _ => return Ok(false),
+        };
+        let full = function == Function::Match;
+        let mut regexes = self.regexes.borrow_mut();
+        let regex = regexes
+            .entry((pattern, full))
+            .or_insert_with_key(|(pattern, full)| compile(pattern, *full));
+        Ok(match regex {
+            Some(regex) => regex.is_match(&value),
+            None => false,
+        })
+    }
+}
+
+/// Translates an I-Regexp (RFC 9485) into the syntax of `regex`, where `.`
+/// would also match `\r`.
+fn compile(pattern: &str, full: bool) -> Option<Regex> {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                translated.push(c);
+                translated.extend(chars.next());
+            }
+            '.' if !in_class => translated.push_str("[^\\n\\r]"),
+            '[' => {
+                in_class = true;
+                translated.push(c);
+            }
+            ']' => {
+                in_class = false;
+                translated.push(c);
+            }
+            _ => translated.push(c),
+        }
+    }
+    if full {
+        translated = format!("\\A(?:{})\\z", translated);
+    }
+    Regex::new(&translated).ok()
+}
+
+/// The indexes a slice selects, following section 2.3.4.2.2 of RFC 9535.
+fn slice(len: i64, start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Vec<i64> {
+    let step = step.unwrap_or(1);
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut indexes = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        let mut i = lower;
+        while i < upper {
+            indexes.push(i);
+            i += step;
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).max(-1).min(len - 1);
+        let mut i = upper;
+        while lower < i {
+            indexes.push(i);
+            i += step;
+        }
+    }
+    indexes
+}
+
+fn compare(py: Python, left: Option<&PyAny>, op: Op, right: Option<&PyAny>) -> PyResult<bool> {
+    Ok(match op {
+        Op::Eq => equal(py, left, right)?,
+        Op::Ne => !equal(py, left, right)?,
+        Op::Lt => less(py, left, right)?,
+        Op::Le => less(py, left, right)? || equal(py, left, right)?,
+        Op::Gt => less(py, right, left)?,
+        Op::Ge => less(py, right, left)? || equal(py, left, right)?,
+    })
+}
+
+fn equal(py: Python, left: Option<&PyAny>, right: Option<&PyAny>) -> PyResult<bool> {
+    match (left, right) {
+        (None, None) => Ok(true),
+        (Some(left), Some(right)) => json_equal(py, left, right),
+        _ => Ok(false),
+    }
+}
+
+/// Only numbers and strings are ordered.
+fn less(py: Python, left: Option<&PyAny>, right: Option<&PyAny>) -> PyResult<bool> {
+    match (left, right) {
+        (Some(left), Some(right)) => match (Kind::of(left), Kind::of(right)) {
+            (Kind::Number, Kind::Number) | (Kind::String, Kind::String) => {
+                left.rich_compare(right, CompareOp::Lt)?.is_true(py)
+            }
+            _ => Ok(false),
+        },
+        _ => Ok(false),
+    }
+}
+
+/// The element at `index` of an array, counting from the end if negative.
+fn element(obj: &PyAny, index: i64) -> Option<&PyAny> {
+    let normalize = |len: usize| {
+        let index = if index < 0 { len as i64 + index } else { index };
+        if 0 <= index && index < len as i64 {
+            Some(index as usize)
+        } else {
+            None
+        }
+    };
+    if let Ok(list) = obj.downcast_ref::<PyList>() {
+        normalize(list.len()).map(|index| list.get_item(index as isize))
+    } else if let Ok(tuple) = obj.downcast_ref::<PyTuple>() {
+        normalize(tuple.len()).map(|index| tuple.get_item(index))
+    } else {
+        None
+    }
+}
+
+/// The member values of an object or the elements of an array.
+fn children(obj: &PyAny) -> Vec<&PyAny> {
+    if let Ok(dict) = obj.downcast_ref::<PyDict>() {
+        dict.iter().map(|(_, value)| value).collect()
+    } else {
+        elements(obj).unwrap_or_default()
+    }
+}
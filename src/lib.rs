@@ -1,47 +1,88 @@
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
+use std::str;
 
+mod bytes;
+mod canonical;
+mod compression;
+mod encoding;
 mod error;
+mod events;
+mod fields;
+mod files;
+mod formatter;
+mod incremental;
+mod infer;
+mod iter;
+mod jsonpath;
+mod lines;
+mod merge;
+mod metaclass;
+mod patch;
+mod pointer;
+mod reader;
+mod schema;
+mod seq;
+mod sort_keys;
+mod surrogates;
+mod value;
+mod writer;
+use bytes::BytesMode;
+use compression::{Compression, Compressor};
+use encoding::{Encoding, ErrorPolicy};
 use error::*;
+use events::{EventIterator, Tokenizer};
+use fields::Fields;
+use formatter::{Escaping, HyperJsonFormatter};
+use incremental::IncrementalDecoder;
+use iter::JsonIterator;
+use lines::JsonLinesWriter;
+use merge::Arrays;
+use patch::PyInit_patch;
+use reader::{ChunkSource, PyReader, Recorder};
+use schema::{Container, Cursor, Decoding, Validator};
+use sort_keys::SortKeys;
+use surrogates::{Lone, Strings, SurrogatePolicy};
+use writer::{BufferWriter, FileWriter};
 
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::KeyError as PyKeyError;
+use pyo3::exceptions::OSError;
 use pyo3::exceptions::TypeError as PyTypeError;
 use pyo3::exceptions::ValueError as PyValueError;
 use pyo3::prelude::*;
 use pyo3::{
-    types::{PyAny, PyDict, PyFloat, PyList, PyTuple},
-    wrap_pyfunction,
+    types::{PyAny, PyBytes, PyDict, PyFloat, PyList, PyString, PyTuple},
+    wrap_pyfunction, wrap_pymodule,
 };
 
-use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
 
-#[pyfunction]
+#[pyfunction(fp, kwargs = "**")]
 pub fn load(py: Python, fp: PyObject, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
     // Temporary workaround for
     // https://github.com/PyO3/pyo3/issues/145
     let io: &PyAny = fp.extract(py)?;
+    let options = LoadsOptions::from_py(py, None, None, None, None, None, None)?;
+    // Taken from `kwargs`, so that it can only be passed by keyword
+    let compression = kwargs
+        .and_then(|kwargs| kwargs.get_item("compression"))
+        .map(|compression| compression.to_object(py));
+    let compression = Compression::from_py(py, compression)?;
 
-    // Alternative workaround
-    // fp.getattr(py, "seek")?;
-    // fp.getattr(py, "read")?;
-
-    // Reset file pointer to beginning See
-    // https://github.com/PyO3/pyo3/issues/143 Note that we ignore the return
-    // value, because `seek` does not strictly need to exist on the object
-    let _success = io.call_method("seek", (0,), None);
-
-    let s_obj = io.call_method0("read")?;
-    loads(
-        py,
-        s_obj.to_object(py),
-        None,
-        None,
-        None,
-        None,
-        None,
-        kwargs,
-    )
+    let mut reader = PyReader::new(py, io, reader::CHUNK_SIZE)?;
+    let result = compression::decompress(&mut reader, compression)
+        .map_err(|err| OSError::py_err(err.to_string()))
+        .and_then(|decompressed| options.read(py, decompressed));
+    match reader.take_error() {
+        Some(err) => Err(err),
+        None => result,
+    }
 }
 
 // This function is a poor man's implementation of
@@ -49,6 +90,7 @@ pub fn load(py: Python, fp: PyObject, kwargs: Option<&PyDict>) -> PyResult<PyObj
 // because we have none of these types under our control.
 // Note: Encoding param is deprecated and ignored.
 #[pyfunction]
+#[allow(clippy::too_many_arguments)]
 pub fn loads(
     py: Python,
     s: PyObject,
@@ -57,6 +99,12 @@ pub fn loads(
     object_hook: Option<PyObject>,
     parse_float: Option<PyObject>,
     parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    errors: Option<PyObject>,
+    fields: Option<PyObject>,
+    schema: Option<PyObject>,
     kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
     // if let Some(kwargs) = kwargs {
@@ -89,13 +137,19 @@ pub fn loads(
         object_hook,
         parse_float,
         parse_int,
+        bytes_mode,
+        bytes_fields,
+        surrogates,
+        errors,
+        fields,
+        schema,
         kwargs,
     )
 }
 
 #[pyfunction]
 // ensure_ascii, check_circular, allow_nan, cls, indent, separators, default, sort_keys, kwargs = "**")]
-#[allow(unused_variables)]
+#[allow(unused_variables, clippy::too_many_arguments)]
 pub fn dumps(
     py: Python,
     obj: PyObject,
@@ -108,77 +162,740 @@ pub fn dumps(
     _separators: Option<PyObject>,
     _default: Option<PyObject>,
     sort_keys: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = DumpsOptions::from_py(
+        py,
+        ensure_ascii,
+        indent,
+        sort_keys,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
+    )?;
+    let buf = options.to_vec(py, obj.extract(py)?)?;
+    let s = String::from_utf8(buf).map_err(|error| HyperJsonError::Utf8Error { error });
+    Ok(s?.to_object(py))
+}
+
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn dump(
+    py: Python,
+    obj: PyObject,
+    fp: PyObject,
+    _skipkeys: Option<PyObject>,
+    ensure_ascii: Option<PyObject>,
+    _check_circular: Option<PyObject>,
+    _allow_nan: Option<PyObject>,
+    _cls: Option<PyObject>,
+    indent: Option<PyObject>,
+    _separators: Option<PyObject>,
+    _default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    chunk_size: Option<PyObject>,
+    compression: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = DumpsOptions::from_py(
+        py,
+        ensure_ascii,
+        indent,
+        sort_keys,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
+    )?;
+    let chunk_size = match chunk_size {
+        Some(chunk_size) => chunk_size.extract(py)?,
+        None => writer::CHUNK_SIZE,
+    };
+    let compression = Compression::from_py(py, compression)?;
+    let mut writer = FileWriter::new(py, fp.extract(py)?, chunk_size)?;
+    let result = options
+        .write_compressed(py, obj.extract(py)?, &mut writer, compression, |err| {
+            OSError::py_err(err.to_string())
+        })
+        .map(|_| ());
+    writer.finish(result)?;
+    Ok(pyo3::Python::None(py))
+}
+
+/// Parses the JSON file at `path`, which may be a `str`, `bytes` or
+/// `os.PathLike` object. Large files are memory-mapped.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn load_path(
+    py: Python,
+    path: PyObject,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    errors: Option<PyObject>,
+    compression: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        surrogates,
+        errors,
+    )?;
+    let compression = Compression::from_py(py, compression)?;
+    let contents = files::read(&files::path(py, path.extract(py)?)?)?;
+    match compression.detect(&contents) {
+        Compression::None => options.loads_bytes(py, &contents),
+        compression => {
+            let decompressed = compression::decompress(&contents[..], compression)
+                .map_err(|err| OSError::py_err(err.to_string()))?;
+            options.read(py, decompressed)
+        }
+    }
+}
+
+/// Iterates over the JSON values in `s`, a `str` or bytes-like object
+/// holding JSON Lines or concatenated JSON. With `skip_invalid`, malformed
+/// records are skipped and listed in the iterator's `skipped` attribute as
+/// `(lineno, offset, message)` tuples.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn loads_iter(
+    py: Python,
+    s: PyObject,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    skip_invalid: Option<bool>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<Py<JsonIterator>> {
+    let options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        None,
+        None,
+    )?;
+    let obj: &PyAny = s.extract(py)?;
+    let (source, buf) =
+        if obj.downcast_ref::<PyString>().is_ok() || obj.downcast_ref::<PyBytes>().is_ok() {
+            (iter::Source::Object(s.clone_ref(py)), Vec::new())
+        } else {
+            let buffer = PyBuffer::get(py, obj).map_err(|_| {
+                PyTypeError::py_err(format!(
+                    "the JSON object must be str or a bytes-like object, got: {}",
+                    obj.get_type().name()
+                ))
+            })?;
+            let buf = bytes::borrow(py, &buffer)?.into_owned();
+            (iter::Source::Buffer, buf)
+        };
+    let iterator = JsonIterator::new(options, source, buf, skip_invalid.unwrap_or(false));
+    Py::new(py, iterator)
+}
+
+/// Like `loads_iter`, but reads the values from the file object `fp` in
+/// chunks of `chunk_size`.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn load_iter(
+    py: Python,
+    fp: PyObject,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    skip_invalid: Option<bool>,
+    chunk_size: Option<usize>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<Py<JsonIterator>> {
+    let options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        None,
+        None,
+    )?;
+    let source = chunk_source(py, fp, chunk_size)?;
+    let iterator = JsonIterator::new(
+        options,
+        iter::Source::File(source),
+        Vec::new(),
+        skip_invalid.unwrap_or(false),
+    );
+    Py::new(py, iterator)
+}
+
+/// Parses an RFC 7464 JSON text sequence into a list, skipping records
+/// that cannot be parsed. See `seq::parse`.
+#[pyfunction]
+pub fn loads_seq(
+    py: Python,
+    s: PyObject,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
     _kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let indent_data: Option<Vec<u8>> = if let Some(indent_py) = indent {
-        let indent_number = indent_py.extract(py)?;
-        Some(vec![b' '; indent_number])
+    let options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        None,
+        None,
+    )?;
+    let obj: &PyAny = s.extract(py)?;
+    let values = if let Ok(string) = <PyString as PyTryFrom>::try_from(obj) {
+        seq::parse(py, &options, string.as_bytes()?)?
     } else {
-        None
+        let buffer = PyBuffer::get(py, obj).map_err(|_| {
+            PyTypeError::py_err(format!(
+                "the JSON object must be str or a bytes-like object, got: {}",
+                obj.get_type().name()
+            ))
+        })?;
+        seq::parse(py, &options, &bytes::borrow(py, &buffer)?)?
     };
+    Ok(values.to_object(py))
+}
 
-    let v = SerializePyObject {
+/// Writes each object of `iterable` to `fp` as a record of an RFC 7464 JSON
+/// text sequence.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn dump_seq(
+    py: Python,
+    iterable: PyObject,
+    fp: PyObject,
+    _skipkeys: Option<PyObject>,
+    ensure_ascii: Option<PyObject>,
+    _check_circular: Option<PyObject>,
+    _allow_nan: Option<PyObject>,
+    _cls: Option<PyObject>,
+    indent: Option<PyObject>,
+    _separators: Option<PyObject>,
+    _default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    chunk_size: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = DumpsOptions::from_py(
         py,
-        obj: obj.extract(py)?,
-        sort_keys: match sort_keys {
-            Some(sort_keys) => sort_keys.is_true(py)?,
-            None => false,
-        },
+        ensure_ascii,
+        indent,
+        sort_keys,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
+    )?;
+    let chunk_size = match chunk_size {
+        Some(chunk_size) => chunk_size.extract(py)?,
+        None => writer::CHUNK_SIZE,
     };
+    let mut writer = FileWriter::new(py, fp.extract(py)?, chunk_size)?;
+    let result = seq::write(py, &options, iterable.extract(py)?, &mut writer).map(|_| ());
+    writer.finish(result)?;
+    Ok(pyo3::Python::None(py))
+}
 
-    let s: Result<String, HyperJsonError> = if let Some(indent) = indent_data {
-        let buf = Vec::new();
-        let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
-        let mut ser = serde_json::Serializer::with_formatter(buf, formatter);
-        v.serialize(&mut ser)
-            .map_err(|error| HyperJsonError::InvalidConversion { error })?;
-        String::from_utf8(ser.into_inner()).map_err(|error| HyperJsonError::Utf8Error { error })
+/// Reads the document in `fp` in chunks and returns an iterator over its
+/// parse events, as `(prefix, event, value)` tuples. See `events`.
+#[pyfunction]
+pub fn iterparse(
+    py: Python,
+    fp: PyObject,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    chunk_size: Option<usize>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<Py<EventIterator>> {
+    let options = LoadsOptions::from_py(py, parse_float, parse_int, None, None, None, None)?;
+    let tokenizer = Tokenizer::new(chunk_source(py, fp, chunk_size)?);
+    Py::new(py, EventIterator::new(options, tokenizer, None))
+}
+
+/// Like `iterparse`, but only builds and yields the values at `prefix`, for
+/// example `item` for the elements of a top-level array.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+pub fn items(
+    py: Python,
+    fp: PyObject,
+    prefix: String,
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    chunk_size: Option<usize>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<Py<EventIterator>> {
+    let options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        None,
+        None,
+    )?;
+    let tokenizer = Tokenizer::new(chunk_source(py, fp, chunk_size)?);
+    Py::new(py, EventIterator::new(options, tokenizer, Some(prefix)))
+}
+
+fn chunk_source(py: Python, fp: PyObject, chunk_size: Option<usize>) -> PyResult<ChunkSource> {
+    let chunk_size = chunk_size.unwrap_or(reader::CHUNK_SIZE);
+    if chunk_size == 0 {
+        return Err(PyValueError::py_err("chunk_size must be positive"));
+    }
+    ChunkSource::new(py, fp.extract(py)?, chunk_size)
+}
+
+/// Returns the value the RFC 6901 JSON Pointer `pointer` refers to in the
+/// JSON document `doc`, without building the rest of it. Raises `KeyError`
+/// if there is no such value, unless a default is given.
+#[pyfunction(doc, pointer, args = "*", kwargs = "**")]
+pub fn get_pointer(
+    py: Python,
+    doc: PyObject,
+    pointer: &str,
+    args: &PyTuple,
+    kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let default = match (
+        args.len(),
+        kwargs.and_then(|kwargs| kwargs.get_item("default")),
+    ) {
+        (0, default) => default,
+        (1, None) => Some(args.get_item(0)),
+        _ => {
+            return Err(PyTypeError::py_err(
+                "get_pointer() takes at most one default value",
+            ))
+        }
+    };
+    if let Some(kwargs) = kwargs {
+        if let Some(key) = kwargs
+            .keys()
+            .iter()
+            .find(|key| key.to_string() != "default")
+        {
+            return Err(PyTypeError::py_err(format!(
+                "get_pointer() got an unexpected keyword argument '{}'",
+                key
+            )));
+        }
+    }
+    let tokens: Vec<_> = pointer::parse(pointer)?
+        .into_iter()
+        .map(pointer::Token::new)
+        .collect();
+    let options = LoadsOptions::from_py(py, None, None, None, None, None, None)?;
+    let obj: &PyAny = doc.extract(py)?;
+    let found = if let Ok(string) = <PyString as PyTryFrom>::try_from(obj) {
+        pointer::get(py, &options, string.as_bytes()?, &tokens)?
     } else {
-        serde_json::to_string(&v).map_err(|error| HyperJsonError::InvalidConversion { error })
+        let buffer = PyBuffer::get(py, obj).map_err(|_| {
+            PyTypeError::py_err(format!(
+                "the JSON object must be str or a bytes-like object, got: {}",
+                obj.get_type().name()
+            ))
+        })?;
+        pointer::get(py, &options, &bytes::borrow(py, &buffer)?, &tokens)?
     };
+    match (found, default) {
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.to_object(py)),
+        (None, None) => Err(PyKeyError::py_err(pointer.to_string())),
+    }
+}
 
-    Ok(s?.to_object(py))
+/// Returns the list of values the RFC 9535 JSONPath query `path` selects in
+/// `doc`. A `str` or bytes-like `doc` is parsed as JSON, and only the part
+/// of it the query can reach is turned into Python objects. Anything else
+/// is taken to be decoded already.
+#[pyfunction]
+pub fn query(py: Python, doc: PyObject, path: &str) -> PyResult<PyObject> {
+    let query = jsonpath::parse(py, path)?;
+    let obj: &PyAny = doc.extract(py)?;
+    // Follows the leading member names and indexes in the raw document
+    let tokens = query.tokens();
+    let options = LoadsOptions::from_py(py, None, None, None, None, None, None)?;
+    let found = if let Ok(string) = <PyString as PyTryFrom>::try_from(obj) {
+        pointer::get(py, &options, string.as_bytes()?, &tokens)?
+    } else if let Ok(buffer) = PyBuffer::get(py, obj) {
+        pointer::get(py, &options, &bytes::borrow(py, &buffer)?, &tokens)?
+    } else {
+        return Ok(query.select(py, obj, 0)?.to_object(py));
+    };
+    let root = match found {
+        Some(root) => root,
+        None => return Ok(PyList::empty(py).to_object(py)),
+    };
+    let values = query.select(py, root.extract(py)?, tokens.len())?;
+    Ok(values.to_object(py))
 }
 
+/// Applies the RFC 7396 JSON Merge Patch `patch` to `target` and returns
+/// the result as a new object: `None` members of `patch` remove keys and
+/// arrays are replaced like any other value.
 #[pyfunction]
-pub fn dump(
+pub fn merge_patch(py: Python, target: PyObject, patch: PyObject) -> PyResult<PyObject> {
+    merge::merge_patch(py, Some(target.extract(py)?), patch.extract(py)?)
+}
+
+/// Merges `layers` from left to right into a new object. Dicts are merged
+/// recursively and later values win. For arrays, `arrays` picks between
+/// `'replace'`, `'concat'`, `'index'` (merging elements at the same
+/// position) and `'key'` (merging the objects whose `key` member is equal).
+#[pyfunction(layers = "*", kwargs = "**")]
+pub fn deep_merge(py: Python, layers: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let (mut arrays, mut key) = (None, None);
+    for (name, value) in kwargs.into_iter().flatten() {
+        match name.to_string().as_str() {
+            "arrays" => arrays = Some(value.to_object(py)),
+            "key" => key = Some(value.to_object(py)),
+            name => {
+                return Err(PyTypeError::py_err(format!(
+                    "deep_merge() got an unexpected keyword argument '{}'",
+                    name
+                )))
+            }
+        }
+    }
+    let arrays = Arrays::from_py(py, arrays, key)?;
+    let mut layers = layers.iter();
+    let mut merged = match layers.next() {
+        Some(base) => value::deep_copy(py, base)?,
+        None => {
+            return Err(PyTypeError::py_err(
+                "deep_merge expected at least 1 argument, got 0",
+            ))
+        }
+    };
+    for layer in layers {
+        merged = merge::deep_merge(py, merged.extract(py)?, layer, &arrays)?;
+    }
+    Ok(merged)
+}
+
+/// Infers a JSON Schema (Draft 2020-12) that all of `docs` are valid
+/// against. `docs` can be any iterable of decoded documents or of JSON
+/// texts, such as the lines of an NDJSON file; blank lines are skipped.
+/// Members are `required` if every object at their path has them. String
+/// fields with at most `enum_limit` distinct values, one of them seen
+/// twice, get an `enum`, and otherwise a `format` if all values have one.
+#[pyfunction]
+pub fn infer_schema(py: Python, docs: PyObject, enum_limit: Option<usize>) -> PyResult<PyObject> {
+    let enum_limit = enum_limit.unwrap_or(10);
+    let mut shape = infer::Shape::default();
+    for doc in docs.as_ref(py).iter()? {
+        let doc = doc?;
+        let blank = if let Ok(string) = <PyString as PyTryFrom>::try_from(doc) {
+            string.to_string()?.trim().is_empty()
+        } else if let Ok(bytes) = doc.downcast_ref::<PyBytes>() {
+            bytes.as_bytes().iter().all(u8::is_ascii_whitespace)
+        } else {
+            false
+        };
+        if blank {
+            continue;
+        }
+        match value::parse_text(py, doc)? {
+            Some(parsed) => shape.add(parsed.extract(py)?, enum_limit)?,
+            None => shape.add(doc, enum_limit)?,
+        }
+    }
+    shape.to_schema(py, true)
+}
+
+/// Serializes `obj` to the file at `path`, replacing it atomically: the
+/// file either keeps its old contents or gets the complete new document,
+/// even if the process dies while writing.
+#[pyfunction]
+#[allow(unused_variables, clippy::too_many_arguments)]
+pub fn dump_path(
     py: Python,
     obj: PyObject,
-    fp: PyObject,
-    skipkeys: Option<PyObject>,
+    path: PyObject,
+    _skipkeys: Option<PyObject>,
     ensure_ascii: Option<PyObject>,
-    check_circular: Option<PyObject>,
-    allow_nan: Option<PyObject>,
-    cls: Option<PyObject>,
+    _check_circular: Option<PyObject>,
+    _allow_nan: Option<PyObject>,
+    _cls: Option<PyObject>,
     indent: Option<PyObject>,
-    separators: Option<PyObject>,
-    default: Option<PyObject>,
+    _separators: Option<PyObject>,
+    _default: Option<PyObject>,
     sort_keys: Option<PyObject>,
-    kwargs: Option<&PyDict>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    compression: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let s = dumps(
+    let options = DumpsOptions::from_py(
         py,
-        obj,
-        skipkeys,
         ensure_ascii,
-        check_circular,
-        allow_nan,
-        cls,
         indent,
-        separators,
-        default,
         sort_keys,
-        kwargs,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
     )?;
-    let fp_ref: &PyAny = fp.extract(py)?;
-    fp_ref.call_method1("write", (s,))?;
-    // TODO: Will this always return None?
+    let obj: &PyAny = obj.extract(py)?;
+    let path = files::path(py, path.extract(py)?)?;
+    let compression = Compression::from_py(py, compression)?.for_path(&path);
+    files::write_atomic(&path, |file| {
+        let io_error = |err: io::Error| files::os_error(&err, &path);
+        let writer = io::BufWriter::new(file);
+        let mut writer = options.write_compressed(py, obj, writer, compression, io_error)?;
+        writer.flush().map_err(io_error)
+    })?;
     Ok(pyo3::Python::None(py))
 }
 
+/// Like `dumps`, but returns the serialized document as `bytes`.
+///
+/// This saves the UTF-8 validation and the copy into a `str` that `dumps`
+/// needs, as well as the `.encode()` callers would otherwise do.
+#[pyfunction]
+#[allow(unused_variables, clippy::too_many_arguments)]
+pub fn dumps_bytes(
+    py: Python,
+    obj: PyObject,
+    _skipkeys: Option<PyObject>,
+    ensure_ascii: Option<PyObject>,
+    _check_circular: Option<PyObject>,
+    _allow_nan: Option<PyObject>,
+    _cls: Option<PyObject>,
+    indent: Option<PyObject>,
+    _separators: Option<PyObject>,
+    _default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<PyObject> {
+    let options = DumpsOptions::from_py(
+        py,
+        ensure_ascii,
+        indent,
+        sort_keys,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
+    )?;
+    let buf = options.to_vec(py, obj.extract(py)?)?;
+    Ok(PyBytes::new(py, &buf).to_object(py))
+}
+
+/// Serializes `obj` as specified by the JSON Canonicalization Scheme
+/// (RFC 8785), for hashing and signing: members sorted by their UTF-16 code
+/// units, no whitespace, only the required escapes and numbers written the
+/// way ECMAScript does. Integers must be exactly representable as doubles.
+#[pyfunction]
+pub fn dumps_canonical(py: Python, obj: PyObject) -> PyResult<PyObject> {
+    let mut out = String::new();
+    canonical::write(py, obj.extract(py)?, &mut out)?;
+    Ok(out.to_object(py))
+}
+
+/// Serializes `obj` into `buffer` and returns the number of bytes written.
+///
+/// A `bytearray` is appended to and grows as needed, so that one buffer can
+/// be reused for many documents. Any other writable buffer (such as a
+/// `memoryview` slice) is filled from its start and must be large enough.
+#[pyfunction]
+#[allow(unused_variables, clippy::too_many_arguments)]
+pub fn dumps_into(
+    py: Python,
+    obj: PyObject,
+    buffer: PyObject,
+    _skipkeys: Option<PyObject>,
+    ensure_ascii: Option<PyObject>,
+    _check_circular: Option<PyObject>,
+    _allow_nan: Option<PyObject>,
+    _cls: Option<PyObject>,
+    indent: Option<PyObject>,
+    _separators: Option<PyObject>,
+    _default: Option<PyObject>,
+    sort_keys: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    encode_html_chars: Option<PyObject>,
+    escape_forward_slashes: Option<PyObject>,
+    _kwargs: Option<&PyDict>,
+) -> PyResult<usize> {
+    let options = DumpsOptions::from_py(
+        py,
+        ensure_ascii,
+        indent,
+        sort_keys,
+        bytes_mode,
+        surrogates,
+        encode_html_chars,
+        escape_forward_slashes,
+    )?;
+    let mut writer = BufferWriter::new(py, buffer.extract(py)?)?;
+    let result = options.write(py, obj.extract(py)?, &mut writer).map(|_| ());
+    writer.finish(result)
+}
+
+/// The parsed keyword arguments of `dumps` and friends
+struct DumpsOptions {
+    indent: Option<Vec<u8>>,
+    sort_keys: SortKeys,
+    bytes_mode: BytesMode,
+    surrogates: SurrogatePolicy,
+    escaping: Escaping,
+}
+
+impl DumpsOptions {
+    #[allow(clippy::too_many_arguments)]
+    fn from_py(
+        py: Python,
+        ensure_ascii: Option<PyObject>,
+        indent: Option<PyObject>,
+        sort_keys: Option<PyObject>,
+        bytes_mode: Option<PyObject>,
+        surrogates: Option<PyObject>,
+        encode_html_chars: Option<PyObject>,
+        escape_forward_slashes: Option<PyObject>,
+    ) -> PyResult<DumpsOptions> {
+        let indent = match indent {
+            Some(indent) => Some(vec![b' '; indent.extract(py)?]),
+            None => None,
+        };
+        let surrogates = SurrogatePolicy::from_py(py, surrogates)?;
+        let html = match encode_html_chars {
+            Some(html) => html.is_true(py)?,
+            None => false,
+        };
+        let escaping = Escaping {
+            ensure_ascii: match ensure_ascii {
+                Some(ensure_ascii) => ensure_ascii.is_true(py)?,
+                None => false,
+            },
+            html,
+            // `</` needs no escaping once `<` is escaped, but this is what
+            // ujson does
+            forward_slashes: match escape_forward_slashes {
+                Some(slashes) => slashes.is_true(py)?,
+                None => html,
+            },
+        };
+        Ok(DumpsOptions {
+            indent,
+            sort_keys: SortKeys::from_py(py, sort_keys)?,
+            bytes_mode: BytesMode::from_py(py, bytes_mode)?,
+            surrogates,
+            escaping,
+        })
+    }
+
+    /// Serializes `obj` into `writer` and hands the writer back.
+    fn write<W: io::Write>(&self, py: Python, obj: &PyAny, writer: W) -> Result<W, HyperJsonError> {
+        let raised = Cell::new(None);
+        let lone = Cell::new(Lone::new());
+        let v = SerializePyObject {
+            py,
+            obj,
+            sort_keys: &self.sort_keys,
+            bytes_mode: self.bytes_mode,
+            surrogates: self.surrogates,
+            raised: &raised,
+            lone: &lone,
+        };
+        let pending = match self.surrogates {
+            SurrogatePolicy::Escape => Some(&lone),
+            _ => None,
+        };
+        // Passes on exceptions raised by Python code as they are
+        let error = |error: serde_json::Error| match raised.take() {
+            Some(err) => HyperJsonError::Python {
+                exception: err.to_object(py),
+            },
+            None => error.into(),
+        };
+        if let Some(indent) = &self.indent {
+            let formatter = HyperJsonFormatter::new(
+                serde_json::ser::PrettyFormatter::with_indent(indent),
+                self.escaping,
+                pending,
+            );
+            let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+            v.serialize(&mut ser).map_err(error)?;
+            Ok(ser.into_inner())
+        } else {
+            let formatter =
+                HyperJsonFormatter::new(serde_json::ser::CompactFormatter, self.escaping, pending);
+            let mut ser = serde_json::Serializer::with_formatter(writer, formatter);
+            v.serialize(&mut ser).map_err(error)?;
+            Ok(ser.into_inner())
+        }
+    }
+
+    /// Like `write`, but compresses the output. I/O errors are reported
+    /// through `io_error`.
+    fn write_compressed<W, F>(
+        &self,
+        py: Python,
+        obj: &PyAny,
+        writer: W,
+        compression: Compression,
+        io_error: F,
+    ) -> PyResult<W>
+    where
+        W: io::Write,
+        F: Fn(io::Error) -> PyErr,
+    {
+        let compressor = Compressor::new(writer, compression).map_err(&io_error)?;
+        let compressor = self.write(py, obj, compressor).map_err(|err| match err {
+            HyperJsonError::InvalidConversion { error } if error.is_io() => io_error(error.into()),
+            err => err.into(),
+        })?;
+        compressor.finish().map_err(io_error)
+    }
+
+    fn to_vec(&self, py: Python, obj: &PyAny) -> Result<Vec<u8>, HyperJsonError> {
+        self.write(py, obj, Vec::new())
+    }
+}
+
 /// A hyper-fast JSON encoder/decoder written in Rust
 #[pymodule]
-fn hyperjson(_py: Python, m: &PyModule) -> PyResult<()> {
+fn hyperjson(py: Python, m: &PyModule) -> PyResult<()> {
     // See https://github.com/PyO3/pyo3/issues/171
     // Use JSONDecodeError from stdlib until issue is resolved.
     // py_exception!(_hyperjson, JSONDecodeError);
@@ -190,10 +907,81 @@ fn hyperjson(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(loads))?;
     m.add_wrapped(wrap_pyfunction!(dump))?;
     m.add_wrapped(wrap_pyfunction!(dumps))?;
+    m.add_wrapped(wrap_pyfunction!(dumps_bytes))?;
+    m.add_wrapped(wrap_pyfunction!(dumps_canonical))?;
+    m.add_wrapped(wrap_pyfunction!(dumps_into))?;
+    m.add_wrapped(wrap_pyfunction!(load_path))?;
+    m.add_wrapped(wrap_pyfunction!(dump_path))?;
+    m.add_wrapped(wrap_pyfunction!(loads_iter))?;
+    m.add_wrapped(wrap_pyfunction!(load_iter))?;
+    m.add_wrapped(wrap_pyfunction!(loads_seq))?;
+    m.add_wrapped(wrap_pyfunction!(dump_seq))?;
+    m.add_wrapped(wrap_pyfunction!(iterparse))?;
+    m.add_wrapped(wrap_pyfunction!(items))?;
+    m.add_wrapped(wrap_pyfunction!(get_pointer))?;
+    m.add_wrapped(wrap_pyfunction!(query))?;
+    m.add_wrapped(wrap_pyfunction!(merge_patch))?;
+    m.add_wrapped(wrap_pyfunction!(deep_merge))?;
+    m.add_wrapped(wrap_pyfunction!(infer_schema))?;
+    m.add_wrapped(wrap_pymodule!(patch))?;
+    // Makes `import hyperjson.patch` work
+    py.import("sys")?
+        .get("modules")?
+        .set_item("hyperjson.patch", m.getattr("patch")?)?;
+
+    let metaclass = metaclass::new(py)?;
+    metaclass::set::<JsonIterator>(py, metaclass);
+    metaclass::set::<EventIterator>(py, metaclass);
+    metaclass::set::<JsonLinesWriter>(py, metaclass);
+    metaclass::set::<IncrementalDecoder>(py, metaclass);
+    metaclass::set::<Validator>(py, metaclass);
+    m.add_class::<JsonLinesWriter>()?;
+    m.add_class::<IncrementalDecoder>()?;
+    m.add_class::<Validator>()?;
+    m.add("ValidationError", py.get_type::<ValidationError>())?;
 
     Ok(())
 }
 
+/// The `**kwargs` of a pyclass constructor. `None` counts as a missing
+/// argument.
+struct Kwargs<'p> {
+    py: Python<'p>,
+    dict: Option<&'p PyDict>,
+}
+
+impl<'p> Kwargs<'p> {
+    /// Rejects any keyword argument not in one of the `allowed` lists, like
+    /// Python does.
+    fn new(
+        py: Python<'p>,
+        class: &str,
+        dict: Option<&'p PyDict>,
+        allowed: &[&[&str]],
+    ) -> PyResult<Kwargs<'p>> {
+        if let Some(dict) = dict {
+            for key in dict.keys() {
+                let key: String = key.extract()?;
+                if !allowed.iter().any(|names| names.contains(&key.as_str())) {
+                    return Err(PyTypeError::py_err(format!(
+                        "{}() got an unexpected keyword argument '{}'",
+                        class, key
+                    )));
+                }
+            }
+        }
+        Ok(Kwargs { py, dict })
+    }
+
+    fn get(&self, name: &str) -> Option<PyObject> {
+        self.dict
+            .and_then(|dict| dict.get_item(name))
+            .filter(|value| !value.is_none())
+            .map(|value| value.to_object(self.py))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn loads_impl(
     py: Python,
     s: PyObject,
@@ -202,60 +990,235 @@ pub fn loads_impl(
     _object_hook: Option<PyObject>,
     parse_float: Option<PyObject>,
     parse_int: Option<PyObject>,
+    bytes_mode: Option<PyObject>,
+    bytes_fields: Option<PyObject>,
+    surrogates: Option<PyObject>,
+    errors: Option<PyObject>,
+    fields: Option<PyObject>,
+    schema: Option<PyObject>,
     _kwargs: Option<&PyDict>,
 ) -> PyResult<PyObject> {
-    let string_result: Result<String, _> = s.extract(py);
-    match string_result {
-        Ok(string) => {
-            let mut deserializer = serde_json::Deserializer::from_str(&string);
-            let seed = HyperJsonValue::new(py, &parse_float, &parse_int);
-            match seed.deserialize(&mut deserializer) {
-                Ok(py_object) => {
-                    deserializer
-                        .end()
-                        .map_err(|e| JSONDecodeError::py_err((e.to_string(), string.clone(), 0)))?;
-                    Ok(py_object)
+    let mut options = LoadsOptions::from_py(
+        py,
+        parse_float,
+        parse_int,
+        bytes_mode,
+        bytes_fields,
+        surrogates,
+        errors,
+    )?;
+    if let Some(fields) = fields.filter(|fields| !fields.is_none()) {
+        options.fields = Some(Fields::from_py(fields.extract(py)?)?);
+    }
+    if let Some(schema) = schema.filter(|schema| !schema.is_none()) {
+        schema::validator(schema.extract(py)?)?;
+        options.schema = Some(schema);
+    }
+    options.loads(py, s.extract(py)?)
+}
+
+/// The parsed keyword arguments of `loads` and friends
+struct LoadsOptions {
+    parse_float: Option<PyObject>,
+    parse_int: Option<PyObject>,
+    bytes_mode: BytesMode,
+    bytes_fields: Option<HashSet<String>>,
+    surrogates: SurrogatePolicy,
+    errors: ErrorPolicy,
+    // Only set by `loads`
+    fields: Option<Fields>,
+    // A `Validator`, only set by `loads`
+    schema: Option<PyObject>,
+}
+
+impl LoadsOptions {
+    fn from_py(
+        py: Python,
+        parse_float: Option<PyObject>,
+        parse_int: Option<PyObject>,
+        bytes_mode: Option<PyObject>,
+        bytes_fields: Option<PyObject>,
+        surrogates: Option<PyObject>,
+        errors: Option<PyObject>,
+    ) -> PyResult<LoadsOptions> {
+        let bytes_mode = BytesMode::from_py(py, bytes_mode)?;
+        let bytes_fields = match bytes_fields {
+            Some(fields) if !fields.is_none() => {
+                if bytes_mode == BytesMode::Raise {
+                    return Err(PyValueError::py_err(
+                        "bytes_fields requires a bytes_mode other than 'raise'",
+                    ));
                 }
-                Err(e) => {
-                    return convert_special_floats(py, &string, &parse_int).or_else(|err| {
-                        if e.is_syntax() {
-                            return Err(JSONDecodeError::py_err((
-                                format!("Value: {:?}, Error: {:?}", s, err),
-                                string.clone(),
-                                0,
-                            )));
-                        } else {
-                            return Err(PyValueError::py_err(format!(
-                                "Value: {:?}, Error: {:?}",
-                                s, e
-                            )));
-                        }
-                    });
+                let mut names = HashSet::new();
+                for name in fields.as_ref(py).iter()? {
+                    names.insert(name?.extract()?);
                 }
+                Some(names)
             }
+            _ => None,
+        };
+        Ok(LoadsOptions {
+            parse_float,
+            parse_int,
+            bytes_mode,
+            bytes_fields,
+            surrogates: SurrogatePolicy::from_py(py, surrogates)?,
+            errors: ErrorPolicy::from_py(py, errors)?,
+            fields: None,
+            schema: None,
+        })
+    }
+
+    fn seed<'a>(&'a self, py: Python<'a>, strings: Option<&'a Strings>) -> HyperJsonValue<'a> {
+        HyperJsonValue::new(
+            py,
+            &self.parse_float,
+            &self.parse_int,
+            self.bytes_mode,
+            self.bytes_fields.as_ref(),
+            self.fields.as_ref(),
+            strings,
+        )
+    }
+
+    /// The validation of the document being decoded, if `schema` is set.
+    fn decoding<'a>(&'a self, py: Python<'a>) -> PyResult<Option<Decoding<'a>>> {
+        match &self.schema {
+            Some(schema) => Ok(Some(Decoding::new(schema::validator(schema.extract(py)?)?))),
+            None => Ok(None),
         }
-        _ => {
-            let bytes: Vec<u8> = s.extract(py).or_else(|e| {
-                Err(PyTypeError::py_err(format!(
-                    "the JSON object must be str, bytes or bytearray, got: {:?}",
-                    e
-                )))
-            })?;
-            let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
-            let seed = HyperJsonValue::new(py, &parse_float, &parse_int);
-            match seed.deserialize(&mut deserializer) {
-                Ok(py_object) => {
-                    deserializer
-                        .end()
-                        .map_err(|e| JSONDecodeError::py_err((e.to_string(), bytes.clone(), 0)))?;
-                    Ok(py_object)
+    }
+
+    /// Parses a document given as `str` or as a bytes-like object.
+    fn loads(&self, py: Python, s: &PyAny) -> PyResult<PyObject> {
+        if let Ok(string) = <PyString as PyTryFrom>::try_from(s) {
+            // Lone surrogates get rewritten before parsing, because serde_json
+            // rejects them. With the `escape` policy they are put back into
+            // the decoded strings.
+            let bytes = surrogates::as_bytes(string, self.surrogates)?;
+            return match surrogates::prepare(&bytes, self.surrogates, self.surrogates) {
+                Some(prepared) => self.parse(py, &prepared.text, prepared.strings.as_ref()),
+                None => self.parse(py, &bytes, None),
+            };
+        }
+        // Keeps the buffer exported (and therefore unmodifiable) while the
+        // document is parsed straight from it
+        let buffer = PyBuffer::get(py, s).map_err(|_| {
+            PyTypeError::py_err(format!(
+                "the JSON object must be str or a bytes-like object, got: {}",
+                s.get_type().name()
+            ))
+        })?;
+        self.loads_bytes(py, &bytes::borrow(py, &buffer)?)
+    }
+
+    /// Parses a document given as bytes in any of the supported encodings.
+    fn loads_bytes(&self, py: Python, bytes: &[u8]) -> PyResult<PyObject> {
+        let decoded = encoding::decode(py, bytes, self.errors, self.surrogates)?;
+        // The decoder only leaves in lone surrogates that are to be kept
+        let raw = if decoded.lone {
+            SurrogatePolicy::Escape
+        } else {
+            SurrogatePolicy::Strict
+        };
+        match surrogates::prepare(&decoded.text, self.surrogates, raw) {
+            Some(prepared) => self.parse(py, &prepared.text, prepared.strings.as_ref()),
+            None => self.parse(py, &decoded.text, None),
+        }
+    }
+
+    /// Parses a document from a stream, reading it in chunks.
+    fn read<R: io::Read>(&self, py: Python, reader: R) -> PyResult<PyObject> {
+        let io_error = |err: io::Error| OSError::py_err(err.to_string());
+        let (head, reader) = compression::peek(reader, 4).map_err(io_error)?;
+        let (encoding, bom) = encoding::detect(&head);
+        let mut head = io::Cursor::new(head);
+        if encoding != Encoding::Utf8
+            || self.surrogates != SurrogatePolicy::Strict
+            || self.errors != ErrorPolicy::Strict
+        {
+            // UTF-16 and UTF-32 are rare, and lone surrogates and decoding
+            // errors can only be handled in one piece
+            let mut bytes = Vec::new();
+            head.chain(reader)
+                .read_to_end(&mut bytes)
+                .map_err(io_error)?;
+            return self.loads_bytes(py, &bytes);
+        }
+        head.set_position(bom as u64);
+
+        let mut reader =
+            io::BufReader::with_capacity(reader::CHUNK_SIZE, Recorder::new(head.chain(reader)));
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut reader);
+        let result = self
+            .seed(py, None)
+            .deserialize(&mut deserializer)
+            .and_then(|py_object| deserializer.end().map(|()| py_object));
+        let e = match result {
+            Ok(py_object) => return Ok(py_object),
+            Err(e) => e,
+        };
+        if e.is_io() {
+            return Err(io_error(e.into()));
+        }
+        let short = reader.get_mut().short_document().map_err(io_error)?;
+        if let Some(s) = short.and_then(|s| str::from_utf8(s).ok()) {
+            if let Ok(py_object) = convert_special_floats(py, s.trim(), &self.parse_int) {
+                return Ok(py_object);
+            }
+        }
+        if e.is_syntax() || e.is_eof() {
+            Err(JSONDecodeError::py_err((e.to_string(), String::new(), 0)))
+        } else {
+            Err(PyValueError::py_err(format!("Error: {:?}", e)))
+        }
+    }
+
+    /// Parses UTF-8 text that has been through `surrogates::prepare`.
+    fn parse(&self, py: Python, json: &[u8], strings: Option<&Strings>) -> PyResult<PyObject> {
+        let decoding = self.decoding(py)?;
+        // Names that lone surrogates were taken out of are not matched
+        // against the schema, so the parts are not checked early
+        let root = match &decoding {
+            Some(decoding) if strings.is_none() => decoding.root(),
+            _ => None,
+        };
+        let mut deserializer = serde_json::Deserializer::from_slice(json);
+        let seed = HyperJsonValue {
+            decoding: decoding.as_ref(),
+            cursor: root.as_ref(),
+            ..self.seed(py, strings)
+        };
+        match seed.deserialize(&mut deserializer) {
+            Ok(py_object) => {
+                deserializer.end().map_err(|e| {
+                    JSONDecodeError::py_err((
+                        e.to_string(),
+                        String::from_utf8_lossy(json).into_owned(),
+                        0,
+                    ))
+                })?;
+                if let Some(decoding) = &decoding {
+                    decoding.finish(py, py_object.extract(py)?)?;
                 }
-                Err(e) => {
-                    return Err(PyTypeError::py_err(format!(
-                        "the JSON object must be str, bytes or bytearray, got: {:?}",
-                        e
-                    )));
+                Ok(py_object)
+            }
+            Err(e) => {
+                if let Some(err) = decoding.as_ref().and_then(Decoding::raised) {
+                    return Err(err);
                 }
+                let string = String::from_utf8_lossy(json);
+                convert_special_floats(py, &string, &self.parse_int).map_err(|err| {
+                    if e.is_syntax() {
+                        JSONDecodeError::py_err((
+                            format!("Value: {:?}, Error: {:?}", string, err),
+                            string.to_string(),
+                            0,
+                        ))
+                    } else {
+                        PyValueError::py_err(format!("Value: {:?}, Error: {:?}", string, e))
+                    }
+                })
             }
         }
     }
@@ -264,7 +1227,13 @@ pub fn loads_impl(
 struct SerializePyObject<'p, 'a> {
     py: Python<'p>,
     obj: &'a PyAny,
-    sort_keys: bool,
+    sort_keys: &'a SortKeys,
+    bytes_mode: BytesMode,
+    surrogates: SurrogatePolicy,
+    // An exception raised by Python code, which serde can only carry as text
+    raised: &'a Cell<Option<PyErr>>,
+    // The lone surrogates of the next string, see `HyperJsonFormatter`
+    lone: &'a Cell<Lone>,
 }
 
 impl<'p, 'a> Serialize for SerializePyObject<'p, 'a> {
@@ -292,43 +1261,70 @@ impl<'p, 'a> Serialize for SerializePyObject<'p, 'a> {
             E::custom(format_args!("{:?}", err))
         }
 
+        /// Turns a dict key into an object member name, like `json` does.
+        fn name<E: ser::Error>(
+            key: &PyAny,
+            surrogates: SurrogatePolicy,
+        ) -> Result<(Cow<'_, str>, Lone), E> {
+            if key.is_none() {
+                Ok((Cow::Borrowed("null"), Lone::new()))
+            } else if let Ok(key) = key.extract::<bool>() {
+                Ok((
+                    Cow::Borrowed(if key { "true" } else { "false" }),
+                    Lone::new(),
+                ))
+            } else if let Ok(key) = key.str() {
+                surrogates::to_string(key, surrogates).map_err(debug_py_err)
+            } else {
+                Err(E::custom(format_args!(
+                    "Dictionary key is not a string: {:?}",
+                    key
+                )))
+            }
+        }
+
         cast!(|x: &PyDict| {
-            if self.sort_keys {
-                // TODO: this could be implemented more efficiently by building
-                // a `Vec<Cow<str>, &PyAny>` of the map entries, sorting
-                // by key, and serializing as in the `else` branch. That avoids
-                // buffering every map value into a serde_json::Value.
-                let no_sort_keys = SerializePyObject {
-                    py: self.py,
-                    obj: self.obj,
-                    sort_keys: false,
-                };
-                let jv = serde_json::to_value(no_sort_keys).map_err(ser::Error::custom)?;
-                jv.serialize(serializer)
+            let mut map = serializer.serialize_map(Some(x.len()))?;
+            if let SortKeys::Unsorted = self.sort_keys {
+                for (key, value) in x {
+                    let (name, lone) = name(key, self.surrogates)?;
+                    self.lone.set(lone);
+                    map.serialize_key(&name)?;
+                    map.serialize_value(&SerializePyObject {
+                        py: self.py,
+                        obj: value,
+                        sort_keys: self.sort_keys,
+                        bytes_mode: self.bytes_mode,
+                        surrogates: self.surrogates,
+                        raised: self.raised,
+                        lone: self.lone,
+                    })?;
+                }
             } else {
-                let mut map = serializer.serialize_map(Some(x.len()))?;
+                let mut members = Vec::with_capacity(x.len());
                 for (key, value) in x {
-                    if key.is_none() {
-                        map.serialize_key("null")?;
-                    } else if let Ok(key) = key.extract::<bool>() {
-                        map.serialize_key(if key { "true" } else { "false" })?;
-                    } else if let Ok(key) = key.str() {
-                        let key = key.to_string().map_err(debug_py_err)?;
-                        map.serialize_key(&key)?;
-                    } else {
-                        return Err(ser::Error::custom(format_args!(
-                            "Dictionary key is not a string: {:?}",
-                            key
-                        )));
-                    }
+                    let (name, lone) = name(key, self.surrogates)?;
+                    members.push((name, lone, key, value));
+                }
+                if let Err(err) = self.sort_keys.sort(self.py, &mut members) {
+                    self.raised.set(Some(err));
+                    return Err(ser::Error::custom("sort_keys raised an exception"));
+                }
+                for (name, lone, _, value) in members {
+                    self.lone.set(lone);
+                    map.serialize_key(&name)?;
                     map.serialize_value(&SerializePyObject {
                         py: self.py,
                         obj: value,
                         sort_keys: self.sort_keys,
+                        bytes_mode: self.bytes_mode,
+                        surrogates: self.surrogates,
+                        raised: self.raised,
+                        lone: self.lone,
                     })?;
                 }
-                map.end()
             }
+            map.end()
         });
 
         cast!(|x: &PyList| {
@@ -338,6 +1334,10 @@ impl<'p, 'a> Serialize for SerializePyObject<'p, 'a> {
                     py: self.py,
                     obj: element,
                     sort_keys: self.sort_keys,
+                    bytes_mode: self.bytes_mode,
+                    surrogates: self.surrogates,
+                    raised: self.raised,
+                    lone: self.lone,
                 })?
             }
             seq.end()
@@ -349,14 +1349,39 @@ impl<'p, 'a> Serialize for SerializePyObject<'p, 'a> {
                     py: self.py,
                     obj: element,
                     sort_keys: self.sort_keys,
+                    bytes_mode: self.bytes_mode,
+                    surrogates: self.surrogates,
+                    raised: self.raised,
+                    lone: self.lone,
                 })?
             }
             seq.end()
         });
 
+        if self.surrogates == SurrogatePolicy::Escape {
+            cast!(|x: &PyString| {
+                let (s, lone) = surrogates::to_string(x, self.surrogates).map_err(debug_py_err)?;
+                self.lone.set(lone);
+                serializer.serialize_str(&s)
+            });
+        }
         extract!(String);
+        if self.surrogates == SurrogatePolicy::Replace {
+            cast!(|x: &PyString| {
+                let (s, _) = surrogates::to_string(x, self.surrogates).map_err(debug_py_err)?;
+                serializer.serialize_str(&s)
+            });
+        }
         extract!(bool);
 
+        if self.bytes_mode != BytesMode::Raise {
+            if let Some(bytes) = bytes::extract(self.obj) {
+                let bytes = bytes.map_err(debug_py_err)?;
+                let s = self.bytes_mode.encode(&bytes).map_err(ser::Error::custom)?;
+                return serializer.serialize_str(&s);
+            }
+        }
+
         cast!(|x: &PyFloat| x.value().serialize(serializer));
         extract!(u64);
         extract!(i64);
@@ -386,9 +1411,9 @@ fn convert_special_floats(
     match s {
         // TODO: If `allow_nan` is false (default: True), then this should be a ValueError
         // https://docs.python.org/3/library/json.html
-        "NaN" => Ok(std::f64::NAN.to_object(py)),
-        "Infinity" => Ok(std::f64::INFINITY.to_object(py)),
-        "-Infinity" => Ok(std::f64::NEG_INFINITY.to_object(py)),
+        "NaN" => Ok(f64::NAN.to_object(py)),
+        "Infinity" => Ok(f64::INFINITY.to_object(py)),
+        "-Infinity" => Ok(f64::NEG_INFINITY.to_object(py)),
         _ => Err(PyValueError::py_err(format!("Value: {:?}", s))),
     }
 }
@@ -398,6 +1423,18 @@ struct HyperJsonValue<'a> {
     py: Python<'a>,
     parse_float: &'a Option<PyObject>,
     parse_int: &'a Option<PyObject>,
+    bytes_mode: BytesMode,
+    bytes_fields: Option<&'a HashSet<String>>,
+    // Set while decoding the value of one of the `bytes_fields`
+    decode_bytes: bool,
+    // The keys to keep in the objects at this level, see `fields`
+    fields: Option<&'a Fields>,
+    // Set if lone surrogates are to be put back, see `surrogates::Strings`
+    strings: Option<&'a Strings>,
+    // Set while validating, see `loads(schema=...)`
+    decoding: Option<&'a Decoding<'a>>,
+    // Where the value is, if it is checked as soon as it is complete
+    cursor: Option<&'a Cursor<'a>>,
 }
 
 impl<'a> HyperJsonValue<'a> {
@@ -405,6 +1442,10 @@ impl<'a> HyperJsonValue<'a> {
         py: Python<'a>,
         parse_float: &'a Option<PyObject>,
         parse_int: &'a Option<PyObject>,
+        bytes_mode: BytesMode,
+        bytes_fields: Option<&'a HashSet<String>>,
+        fields: Option<&'a Fields>,
+        strings: Option<&'a Strings>,
     ) -> HyperJsonValue<'a> {
         // We cannot borrow the runtime here,
         // because it wouldn't live long enough
@@ -414,8 +1455,33 @@ impl<'a> HyperJsonValue<'a> {
             py,
             parse_float,
             parse_int,
+            bytes_mode,
+            bytes_fields,
+            decode_bytes: false,
+            fields,
+            strings,
+            decoding: None,
+            cursor: None,
         }
     }
+
+    /// The object or array being decoded, if its parts are checked early.
+    fn container(&self) -> Option<(&'a Decoding<'a>, Container<'a, 'a>)> {
+        match (self.decoding, self.cursor) {
+            (Some(decoding), Some(cursor)) => Some((decoding, decoding.container(cursor))),
+            _ => None,
+        }
+    }
+
+    /// Checks a complete member or element, see `schema::Decoding`.
+    fn check<E: de::Error>(&self, cursor: Option<&Cursor>, value: &PyObject) -> Result<(), E> {
+        if let (Some(decoding), Some(cursor)) = (self.decoding, cursor) {
+            if !decoding.check(self.py, cursor, &value.as_ref(self.py)) {
+                return Err(de::Error::custom("the document is not valid"));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'de, 'a> DeserializeSeed<'de> for HyperJsonValue<'a> {
@@ -490,7 +1556,20 @@ impl<'de, 'a> Visitor<'de> for HyperJsonValue<'a> {
     where
         E: de::Error,
     {
-        Ok(value.to_object(self.py))
+        let lone = self.strings.and_then(Strings::next);
+        if self.decode_bytes {
+            if lone.is_some() {
+                return Err(de::Error::custom(
+                    "cannot decode bytes with lone surrogates",
+                ));
+            }
+            let bytes = self.bytes_mode.decode(value).map_err(de::Error::custom)?;
+            return Ok(PyBytes::new(self.py, &bytes).to_object(self.py));
+        }
+        match lone {
+            Some(lone) => Ok(surrogates::to_py(self.py, value, lone)),
+            None => Ok(value.to_object(self.py)),
+        }
     }
 
     fn visit_unit<E>(self) -> Result<Self::Value, E> {
@@ -502,9 +1581,23 @@ impl<'de, 'a> Visitor<'de> for HyperJsonValue<'a> {
         A: SeqAccess<'de>,
     {
         let mut elements = Vec::new();
+        let container = self.container();
 
-        while let Some(elem) = seq.next_element_seed(self)? {
-            elements.push(elem);
+        loop {
+            let cursor = container
+                .as_ref()
+                .and_then(|(decoding, container)| decoding.element(container, elements.len()));
+            let seed = HyperJsonValue {
+                cursor: cursor.as_ref(),
+                ..self
+            };
+            match seq.next_element_seed(seed)? {
+                Some(elem) => {
+                    self.check(cursor.as_ref(), &elem)?;
+                    elements.push(elem);
+                }
+                None => break,
+            }
         }
 
         Ok(elements.to_object(self.py))
@@ -515,11 +1608,76 @@ impl<'de, 'a> Visitor<'de> for HyperJsonValue<'a> {
         A: MapAccess<'de>,
     {
         let mut entries = BTreeMap::new();
+        // Members whose names have lone surrogates, by their names as WTF-8
+        let mut lone_entries = BTreeMap::new();
+        let container = self.container();
 
-        while let Some((key, value)) = map.next_entry_seed(PhantomData::<String>, self)? {
-            entries.insert(key, value);
+        while let Some(key) = map.next_key_seed(PhantomData::<String>)? {
+            let lone = self.strings.and_then(Strings::next);
+            // Unselected values are skipped without creating Python objects.
+            // Names with lone surrogates cannot be selected.
+            let selected = match lone {
+                Some(_) => self.fields.map(|_| None),
+                None => self.fields.map(|fields| fields.get(&key)),
+            };
+            let fields = match selected {
+                Some(None) => {
+                    match self.strings {
+                        // Its strings still have to be counted
+                        Some(_) => {
+                            map.next_value_seed(HyperJsonValue {
+                                fields: None,
+                                decode_bytes: false,
+                                ..self
+                            })?;
+                        }
+                        None => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                    continue;
+                }
+                Some(Some(nested)) => nested,
+                None => None,
+            };
+            let decode_bytes = match self.bytes_fields {
+                Some(fields) => lone.is_none() && fields.contains(&key),
+                None => false,
+            };
+            let cursor = container
+                .as_ref()
+                .and_then(|(decoding, container)| decoding.member(container, &key));
+            let value = map.next_value_seed(HyperJsonValue {
+                decode_bytes,
+                fields,
+                cursor: cursor.as_ref(),
+                ..self
+            })?;
+            self.check(cursor.as_ref(), &value)?;
+            match lone {
+                Some(lone) => {
+                    lone_entries.insert(surrogates::wtf8(&key, lone), value);
+                }
+                None => {
+                    entries.insert(key, value);
+                }
+            }
         }
 
+        if !lone_entries.is_empty() {
+            // WTF-8 sorts by code point just like UTF-8
+            let mut all: BTreeMap<Vec<u8>, PyObject> = entries
+                .into_iter()
+                .map(|(key, value)| (key.into_bytes(), value))
+                .collect();
+            all.append(&mut lone_entries);
+            let dict = PyDict::new(self.py);
+            for (key, value) in all {
+                dict.set_item(surrogates::from_wtf8(self.py, &key), value)
+                    .map_err(|e| de::Error::custom(HyperJsonError::from(e)))?;
+            }
+            return Ok(dict.to_object(self.py));
+        }
         Ok(entries.to_object(self.py))
     }
 }
@@ -0,0 +1,203 @@
+// The code generated by `#[pyclass]` trips this lint
+#![allow(clippy::manual_div_ceil)]
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+use pyo3::{ffi, AsPyPointer, PyNativeType};
+
+use crate::writer;
+use crate::{DumpsOptions, Kwargs};
+
+/// The keyword arguments `JsonLinesWriter` passes on to `DumpsOptions`.
+const OPTIONS: &[&str] = &[
+    "ensure_ascii",
+    "sort_keys",
+    "bytes_mode",
+    "surrogates",
+    "encode_html_chars",
+    "escape_forward_slashes",
+];
+
+/// Writes one JSON document per line to a file object, as in JSON Lines.
+///
+/// Records are serialized into a buffer, which is passed to `fp.write` once
+/// it holds `chunk_size` bytes, on `flush()` and on `close()`, which leaving
+/// a `with` block calls. Like Python's buffered files, a writer that goes
+/// away unclosed passes what is left to `fp.write`. Records are always
+/// compact, so the only newline is the one that ends each record. A record
+/// that cannot be serialized is dropped from the buffer as a whole.
+#[pyclass]
+pub struct JsonLinesWriter {
+    fp: PyObject,
+    binary: bool,
+    options: DumpsOptions,
+    chunk_size: usize,
+    buf: Vec<u8>,
+    closed: bool,
+}
+
+#[pymethods]
+impl JsonLinesWriter {
+    #[new]
+    #[allow(clippy::new_ret_no_self)]
+    #[args(kwargs = "**")]
+    fn new(obj: &PyRawObject, fp: PyObject, kwargs: Option<&PyDict>) -> PyResult<()> {
+        let py = obj.py();
+        let kwargs = Kwargs::new(
+            py,
+            "JsonLinesWriter",
+            kwargs,
+            &[OPTIONS, &["indent", "chunk_size"]],
+        )?;
+        if kwargs.get("indent").is_some() {
+            return Err(PyValueError::py_err(
+                "JSON Lines records cannot be indented",
+            ));
+        }
+        let options = DumpsOptions::from_py(
+            py,
+            kwargs.get(OPTIONS[0]),
+            None,
+            kwargs.get(OPTIONS[1]),
+            kwargs.get(OPTIONS[2]),
+            kwargs.get(OPTIONS[3]),
+            kwargs.get(OPTIONS[4]),
+            kwargs.get(OPTIONS[5]),
+        )?;
+        let chunk_size = match kwargs.get("chunk_size") {
+            Some(chunk_size) => chunk_size.extract(py)?,
+            None => writer::CHUNK_SIZE,
+        };
+        if chunk_size == 0 {
+            return Err(PyValueError::py_err("chunk_size must be positive"));
+        }
+        // Fail early, like `dump` does
+        fp.getattr(py, "write")?;
+        let binary = writer::is_binary(py, fp.extract(py)?)?;
+        obj.init(JsonLinesWriter {
+            fp,
+            binary,
+            options,
+            chunk_size,
+            buf: Vec::with_capacity(chunk_size),
+            closed: false,
+        });
+        Ok(())
+    }
+
+    /// Writes `obj` as one line.
+    fn write(&mut self, py: Python, obj: &PyAny) -> PyResult<()> {
+        self.check_closed()?;
+        self.push(py, obj)?;
+        self.write_full(py)
+    }
+
+    /// Writes each object of `iterable` as one line.
+    fn write_many(&mut self, py: Python, iterable: &PyAny) -> PyResult<()> {
+        self.check_closed()?;
+        for obj in iterable.iter()? {
+            self.push(py, obj?)?;
+            self.write_full(py)?;
+        }
+        Ok(())
+    }
+
+    /// Passes the buffered records to `fp.write`, then flushes `fp` if it
+    /// has a `flush` method.
+    fn flush(&mut self, py: Python) -> PyResult<()> {
+        self.check_closed()?;
+        self.write_buffer(py)?;
+        let fp: &PyAny = self.fp.extract(py)?;
+        if fp.hasattr("flush")? {
+            fp.call_method0("flush")?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the writer, after which it cannot be written to. The file
+    /// object is left open.
+    fn close(&mut self, py: Python) -> PyResult<()> {
+        if !self.closed {
+            self.flush(py)?;
+            self.closed = true;
+        }
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exc_type: &PyAny,
+        _exc_value: &PyAny,
+        _traceback: &PyAny,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
+
+impl JsonLinesWriter {
+    fn check_closed(&self) -> PyResult<()> {
+        if self.closed {
+            return Err(PyValueError::py_err(
+                "I/O operation on closed JsonLinesWriter",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Appends a record to the buffer.
+    fn push(&mut self, py: Python, obj: &PyAny) -> PyResult<()> {
+        let start = self.buf.len();
+        match self.options.write(py, obj, &mut self.buf) {
+            Ok(_) => {
+                self.buf.push(b'\n');
+                Ok(())
+            }
+            Err(err) => {
+                self.buf.truncate(start);
+                Err(err.into())
+            }
+        }
+    }
+
+    fn write_full(&mut self, py: Python) -> PyResult<()> {
+        if self.buf.len() >= self.chunk_size {
+            self.write_buffer(py)?;
+        }
+        Ok(())
+    }
+
+    fn write_buffer(&mut self, py: Python) -> PyResult<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        // Records end in `\n`, so the buffer never ends mid-character
+        let write = self.fp.getattr(py, "write")?;
+        writer::send(py, write.extract(py)?, self.binary, &self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+/// Passes what is left to `fp.write`, like Python's buffered files. There
+/// is nowhere to raise an error from here, so it is reported the way Python
+/// reports exceptions in `__del__`.
+impl Drop for JsonLinesWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+        if let Err(err) = self.write_buffer(py) {
+            err.restore(py);
+            unsafe { ffi::PyErr_WriteUnraisable(self.fp.as_ptr()) };
+        }
+    }
+}
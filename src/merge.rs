@@ -0,0 +1,173 @@
+//! Merging documents: RFC 7396 JSON Merge Patch and a deep merge with a
+//! choice of what happens to arrays. Neither changes its inputs.
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict};
+
+use crate::value::{deep_copy, elements, json_equal};
+
+/// Applies `patch` to `target`, which is `None` for a missing member.
+/// Objects are merged, `None` members are removed and anything else,
+/// arrays included, replaces the target.
+pub fn merge_patch(py: Python, target: Option<&PyAny>, patch: &PyAny) -> PyResult<PyObject> {
+    let patch = match patch.downcast_ref::<PyDict>() {
+        Ok(patch) => patch,
+        Err(_) => return deep_copy(py, patch),
+    };
+    let result = PyDict::new(py);
+    if let Some(target) = target.and_then(|target| target.downcast_ref::<PyDict>().ok()) {
+        for (key, value) in target {
+            if patch.get_item(key).is_none() {
+                result.set_item(key, deep_copy(py, value)?)?;
+            }
+        }
+    }
+    for (key, value) in patch {
+        if value.is_none() {
+            continue;
+        }
+        let target = target
+            .and_then(|target| target.downcast_ref::<PyDict>().ok())
+            .and_then(|target| target.get_item(key));
+        result.set_item(key, merge_patch(py, target, value)?)?;
+    }
+    Ok(result.to_object(py))
+}
+
+/// What `deep_merge` does when both sides are arrays.
+pub enum Arrays {
+    Replace,
+    Concat,
+    /// Merges elements at the same index
+    Index,
+    /// Merges objects with the same value for a member
+    Key(String),
+}
+
+impl Arrays {
+    pub fn from_py(
+        py: Python,
+        arrays: Option<PyObject>,
+        key: Option<PyObject>,
+    ) -> PyResult<Arrays> {
+        let key: Option<String> = match key {
+            Some(key) if !key.is_none() => Some(key.extract(py)?),
+            _ => None,
+        };
+        let name: String = match arrays {
+            Some(arrays) if !arrays.is_none() => arrays.extract(py)?,
+            _ if key.is_some() => "key".to_string(),
+            _ => return Ok(Arrays::Replace),
+        };
+        let arrays = match name.as_str() {
+            "replace" => Arrays::Replace,
+            "concat" => Arrays::Concat,
+            "index" => Arrays::Index,
+            "key" => match key {
+                Some(key) => return Ok(Arrays::Key(key)),
+                None => return Err(PyValueError::py_err("arrays='key' requires key")),
+            },
+            _ => {
+                return Err(PyValueError::py_err(format!(
+                    "arrays must be one of 'replace', 'concat', 'index' or 'key', got: {:?}",
+                    name
+                )))
+            }
+        };
+        if key.is_some() {
+            return Err(PyValueError::py_err("key requires arrays='key'"));
+        }
+        Ok(arrays)
+    }
+}
+
+/// Merges `other` into `base`. Objects are merged member by member, arrays
+/// according to `arrays`, and any other value in `other` wins, `None`
+/// included.
+pub fn deep_merge(py: Python, base: &PyAny, other: &PyAny, arrays: &Arrays) -> PyResult<PyObject> {
+    if let (Ok(base), Ok(other)) = (
+        base.downcast_ref::<PyDict>(),
+        other.downcast_ref::<PyDict>(),
+    ) {
+        let result = PyDict::new(py);
+        for (key, value) in base {
+            if other.get_item(key).is_none() {
+                result.set_item(key, deep_copy(py, value)?)?;
+            }
+        }
+        for (key, value) in other {
+            let merged = match base.get_item(key) {
+                Some(base) => deep_merge(py, base, value, arrays)?,
+                None => deep_copy(py, value)?,
+            };
+            result.set_item(key, merged)?;
+        }
+        return Ok(result.to_object(py));
+    }
+    let (base, other) = match (elements(base), elements(other)) {
+        (Some(base), Some(other)) => (base, other),
+        _ => return deep_copy(py, other),
+    };
+    let mut result = Vec::with_capacity(base.len().max(other.len()));
+    match arrays {
+        Arrays::Replace => {
+            for element in other {
+                result.push(deep_copy(py, element)?);
+            }
+        }
+        Arrays::Concat => {
+            for element in base.into_iter().chain(other) {
+                result.push(deep_copy(py, element)?);
+            }
+        }
+        Arrays::Index => {
+            for i in 0..base.len().max(other.len()) {
+                result.push(match (base.get(i), other.get(i)) {
+                    (Some(base), Some(other)) => deep_merge(py, base, other, arrays)?,
+                    (Some(element), None) | (None, Some(element)) => deep_copy(py, element)?,
+                    (None, None) => unreachable!(),
+                });
+            }
+        }
+        Arrays::Key(key) => {
+            let id = |element: &PyAny| {
+                element
+                    .downcast_ref::<PyDict>()
+                    .ok()
+                    .and_then(|dict| dict.get_item(key.as_str()))
+                    .map(|id| id.to_object(py))
+            };
+            let mut ids = Vec::with_capacity(result.capacity());
+            for element in base {
+                ids.push(id(element));
+                result.push(deep_copy(py, element)?);
+            }
+            for element in other {
+                let element_id = id(element);
+                let mut found = None;
+                if let Some(element_id) = &element_id {
+                    for (i, id) in ids.iter().enumerate() {
+                        if let Some(id) = id {
+                            if json_equal(py, id.extract(py)?, element_id.extract(py)?)? {
+                                found = Some(i);
+                                break;
+                            }
+                        }
+                    }
+                }
+                match found {
+                    Some(i) => {
+                        let merged = deep_merge(py, result[i].extract(py)?, element, arrays)?;
+                        result[i] = merged;
+                    }
+                    None => {
+                        ids.push(element_id);
+                        result.push(deep_copy(py, element)?);
+                    }
+                }
+            }
+        }
+    }
+    Ok(result.to_object(py))
+}
@@ -0,0 +1,52 @@
+//! Makes the classes of this module safe to call on Python 3.8 and later.
+//!
+//! pyo3 0.8 predates `tp_vectorcall`, which Python 3.8 and later read
+//! whenever a class is called, so the slot would be read from past the end
+//! of the type object. Classes created in Python never support vectorcall
+//! themselves, so giving a class such a metaclass makes Python call it
+//! through `tp_call` instead. Python 3.11 and later also read the slot when
+//! specializing calls to immutable classes, so the flag is cleared as well,
+//! and the metaclass keeps the classes immutable in its place.
+
+use std::os::raw::c_ulong;
+
+use pyo3::prelude::*;
+use pyo3::type_object::PyTypeObject;
+use pyo3::types::{PyAny, PyDict};
+use pyo3::{ffi, AsPyPointer};
+
+// Not in pyo3 0.8
+const PY_TPFLAGS_IMMUTABLETYPE: c_ulong = 1 << 8;
+
+const METACLASS: &str = r#"
+class type(type):
+    __module__ = "hyperjson"
+
+    def __setattr__(cls, name, value):
+        raise TypeError(
+            "cannot set %r attribute of immutable type %r" % (name, cls.__name__)
+        )
+
+    def __delattr__(cls, name):
+        raise TypeError(
+            "cannot delete %r attribute of immutable type %r" % (name, cls.__name__)
+        )
+"#;
+
+/// Creates the metaclass, `hyperjson.type`.
+pub fn new<'p>(py: Python<'p>) -> PyResult<&'p PyAny> {
+    let locals = PyDict::new(py);
+    py.run(METACLASS, None, Some(locals))?;
+    Ok(locals.get_item("type").expect("METACLASS defines `type`"))
+}
+
+/// Gives `T` the metaclass made by `new`.
+pub fn set<T: PyTypeObject>(py: Python, metaclass: &PyAny) {
+    unsafe {
+        let class = py.get_type::<T>().as_type_ptr();
+        ffi::Py_INCREF(metaclass.as_ptr());
+        (*(class as *mut ffi::PyObject)).ob_type = metaclass.as_ptr() as *mut ffi::PyTypeObject;
+        (*class).tp_flags &= !PY_TPFLAGS_IMMUTABLETYPE;
+        ffi::PyType_Modified(class);
+    }
+}
@@ -0,0 +1,493 @@
+//! JSON Patch (RFC 6902) for Python objects and JSON documents.
+
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyBytes, PyDict, PyList, PyTuple};
+use pyo3::wrap_pyfunction;
+
+use crate::error::{HyperJsonError, JsonPatchError};
+use crate::pointer;
+use crate::value::{deep_copy, elements, is_string, json_equal, parse_text, Kind};
+use crate::DumpsOptions;
+
+/// Longer arrays are diffed by position instead of with an edit distance,
+/// which takes quadratic time and memory.
+const MAX_DIFF_CELLS: usize = 1 << 20;
+
+/// JSON Patch (RFC 6902)
+#[pymodule]
+pub fn patch(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(apply))?;
+    m.add_wrapped(wrap_pyfunction!(diff))?;
+    m.add("JsonPatchError", py.get_type::<JsonPatchError>())?;
+    Ok(())
+}
+
+/// Applies the operations `ops` to `doc` and returns the result. Either
+/// all operations succeed or `JsonPatchError` is raised and `doc` is left
+/// as it was. `doc` is copied first unless `in_place` is set. A `str` or
+/// bytes-like `doc` is parsed as JSON, and then the result is serialized
+/// into the same type. `ops` may be JSON text as well.
+#[pyfunction]
+fn apply(py: Python, doc: PyObject, ops: PyObject, in_place: Option<bool>) -> PyResult<PyObject> {
+    let doc: &PyAny = doc.extract(py)?;
+    let parsed_ops = parse_text(py, ops.extract(py)?)?;
+    let ops: &PyAny = match &parsed_ops {
+        Some(ops) => ops.extract(py)?,
+        None => ops.extract(py)?,
+    };
+    let (target, text) = match parse_text(py, doc)? {
+        Some(parsed) => (parsed, Some(is_string(doc))),
+        None if in_place.unwrap_or(false) => (doc.to_object(py), None),
+        None => (deep_copy(py, doc)?, None),
+    };
+    let mut patcher = Patcher {
+        py,
+        root: target,
+        undo: Vec::new(),
+        op: 0,
+    };
+    for (i, op) in ops.iter()?.enumerate() {
+        patcher.op = i;
+        if let Err(err) = op.and_then(|op| patcher.apply(op)) {
+            patcher.rollback();
+            return Err(err);
+        }
+    }
+    let result = patcher.root;
+    match text {
+        None => Ok(result),
+        Some(is_str) => {
+            let options = DumpsOptions::from_py(py, None, None, None, None, None, None, None)?;
+            let buf = options.to_vec(py, result.extract(py)?)?;
+            if is_str {
+                let s = String::from_utf8(buf).map_err(|error| HyperJsonError::Utf8Error { error });
+                Ok(s?.to_object(py))
+            } else {
+                Ok(PyBytes::new(py, &buf).to_object(py))
+            }
+        }
+    }
+}
+
+/// Returns a patch that turns `a` into `b`, as a list of operations.
+/// Objects are compared member by member and arrays element by element, so
+/// only what changed is replaced. Either may be given as JSON text.
+#[pyfunction]
+fn diff(py: Python, a: PyObject, b: PyObject) -> PyResult<PyObject> {
+    let (parsed_a, parsed_b) = (
+        parse_text(py, a.extract(py)?)?,
+        parse_text(py, b.extract(py)?)?,
+    );
+    let a: &PyAny = match &parsed_a {
+        Some(a) => a.extract(py)?,
+        None => a.extract(py)?,
+    };
+    let b: &PyAny = match &parsed_b {
+        Some(b) => b.extract(py)?,
+        None => b.extract(py)?,
+    };
+    let mut ops = Vec::new();
+    diff_values(py, a, b, "", &mut ops)?;
+    Ok(ops.to_object(py))
+}
+
+/// What to do to undo a change.
+enum Undo {
+    Root(PyObject),
+    // Restores a member of an object, or removes it
+    Member(PyObject, String, Option<PyObject>),
+    Insert(PyObject, usize),
+    Remove(PyObject, usize, PyObject),
+    Replace(PyObject, usize, PyObject),
+}
+
+struct Patcher<'p> {
+    py: Python<'p>,
+    root: PyObject,
+    undo: Vec<Undo>,
+    // The index of the current operation
+    op: usize,
+}
+
+impl<'p> Patcher<'p> {
+    fn error(&self, message: &str) -> PyErr {
+        JsonPatchError::py_err(format!("operation {}: {}", self.op, message))
+    }
+
+    fn apply(&mut self, op: &PyAny) -> PyResult<()> {
+        let py = self.py;
+        let op: &PyDict = op
+            .downcast_ref()
+            .map_err(|_| self.error("operations must be objects"))?;
+        let name: String = self
+            .member(op, "op")?
+            .extract()
+            .map_err(|_| self.error("'op' must be a string"))?;
+        let path = self.path(op, "path")?;
+        match name.as_str() {
+            "add" => {
+                let value = deep_copy(py, self.member(op, "value")?)?;
+                self.add(&path, value)
+            }
+            "remove" => self.remove(&path).map(|_| ()),
+            "replace" => {
+                let value = deep_copy(py, self.member(op, "value")?)?;
+                self.replace(&path, value)
+            }
+            "move" => {
+                let from = self.path(op, "from")?;
+                if from == path {
+                    return Ok(());
+                }
+                if path.len() > from.len() && path.starts_with(&from[..]) {
+                    return Err(self.error("cannot move a value into itself"));
+                }
+                let value = self.remove(&from)?;
+                self.add(&path, value)
+            }
+            "copy" => {
+                let from = self.path(op, "from")?;
+                let value = self.get(&from)?;
+                let value = deep_copy(py, value.extract(py)?)?;
+                self.add(&path, value)
+            }
+            "test" => {
+                let expected = self.member(op, "value")?;
+                let actual = self.get(&path)?;
+                if !json_equal(py, actual.extract(py)?, expected)? {
+                    return Err(self.error(&format!(
+                        "test failed, the value at {:?} is different",
+                        pointer_string(&path)
+                    )));
+                }
+                Ok(())
+            }
+            _ => Err(self.error(&format!("unknown operation {:?}", name))),
+        }
+    }
+
+    fn member<'a>(&self, op: &'a PyDict, name: &str) -> PyResult<&'a PyAny> {
+        op.get_item(name)
+            .ok_or_else(|| self.error(&format!("missing {:?}", name)))
+    }
+
+    fn path(&self, op: &PyDict, name: &str) -> PyResult<Vec<String>> {
+        let path: String = self
+            .member(op, name)?
+            .extract()
+            .map_err(|_| self.error(&format!("{:?} must be a string", name)))?;
+        pointer::parse(&path).map_err(|_| self.error(&format!("invalid pointer {:?}", path)))
+    }
+
+    fn missing(&self, tokens: &[String]) -> PyErr {
+        self.error(&format!("{:?} does not exist", pointer_string(tokens)))
+    }
+
+    /// Returns the value at `tokens`.
+    fn get(&self, tokens: &[String]) -> PyResult<PyObject> {
+        let py = self.py;
+        let mut node: &PyAny = self.root.extract(py)?;
+        for (i, token) in tokens.iter().enumerate() {
+            let child = if let Ok(dict) = node.downcast_ref::<PyDict>() {
+                dict.get_item(token.as_str())
+            } else {
+                match (elements(node), pointer::index(token)) {
+                    (Some(elements), Some(index)) => elements.get(index).cloned(),
+                    _ => None,
+                }
+            };
+            node = child.ok_or_else(|| self.missing(&tokens[..=i]))?;
+        }
+        Ok(node.to_object(py))
+    }
+
+    /// Returns the container that holds the value at `tokens`, as well as
+    /// the last token.
+    fn parent<'t>(&self, tokens: &'t [String]) -> PyResult<(PyObject, &'t str)> {
+        let (last, parent) = tokens.split_last().expect("the root has no parent");
+        let parent = self.get(parent)?;
+        let container: &PyAny = parent.extract(self.py)?;
+        if container.downcast_ref::<PyTuple>().is_ok() {
+            return Err(self.error("tuples cannot be changed in place"));
+        }
+        Ok((parent, last))
+    }
+
+    /// Checks an array index for `add` if `end` is set, or else for an
+    /// existing element.
+    fn index(&self, list: &PyList, tokens: &[String], end: bool) -> PyResult<usize> {
+        let token = &tokens[tokens.len() - 1];
+        if end && token == "-" {
+            return Ok(list.len());
+        }
+        match pointer::index(token) {
+            Some(index) if index < list.len() || (end && index == list.len()) => Ok(index),
+            _ => Err(self.missing(tokens)),
+        }
+    }
+
+    fn add(&mut self, tokens: &[String], value: PyObject) -> PyResult<()> {
+        if tokens.is_empty() {
+            let old = std::mem::replace(&mut self.root, value);
+            self.undo.push(Undo::Root(old));
+            return Ok(());
+        }
+        let (parent, last) = self.parent(tokens)?;
+        let container: &PyAny = parent.extract(self.py)?;
+        if let Ok(dict) = container.downcast_ref::<PyDict>() {
+            let old = dict.get_item(last).map(|old| old.to_object(self.py));
+            dict.set_item(last, value)?;
+            self.undo.push(Undo::Member(parent, last.to_string(), old));
+        } else if let Ok(list) = container.downcast_ref::<PyList>() {
+            let index = self.index(list, tokens, true)?;
+            list.insert(index as isize, value)?;
+            self.undo.push(Undo::Insert(parent, index));
+        } else {
+            return Err(self.missing(&tokens[..tokens.len() - 1]));
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, tokens: &[String]) -> PyResult<PyObject> {
+        if tokens.is_empty() {
+            return Err(self.error("cannot remove the whole document"));
+        }
+        let (parent, last) = self.parent(tokens)?;
+        let container: &PyAny = parent.extract(self.py)?;
+        if let Ok(dict) = container.downcast_ref::<PyDict>() {
+            let old = match dict.get_item(last) {
+                Some(old) => old.to_object(self.py),
+                None => return Err(self.missing(tokens)),
+            };
+            dict.del_item(last)?;
+            self.undo.push(Undo::Member(
+                parent,
+                last.to_string(),
+                Some(old.clone_ref(self.py)),
+            ));
+            Ok(old)
+        } else if let Ok(list) = container.downcast_ref::<PyList>() {
+            let index = self.index(list, tokens, false)?;
+            let old = list.call_method1("pop", (index,))?.to_object(self.py);
+            self.undo
+                .push(Undo::Remove(parent, index, old.clone_ref(self.py)));
+            Ok(old)
+        } else {
+            Err(self.missing(tokens))
+        }
+    }
+
+    fn replace(&mut self, tokens: &[String], value: PyObject) -> PyResult<()> {
+        if tokens.is_empty() {
+            return self.add(tokens, value);
+        }
+        let (parent, last) = self.parent(tokens)?;
+        let container: &PyAny = parent.extract(self.py)?;
+        if let Ok(dict) = container.downcast_ref::<PyDict>() {
+            let old = match dict.get_item(last) {
+                Some(old) => old.to_object(self.py),
+                None => return Err(self.missing(tokens)),
+            };
+            dict.set_item(last, value)?;
+            self.undo
+                .push(Undo::Member(parent, last.to_string(), Some(old)));
+        } else if let Ok(list) = container.downcast_ref::<PyList>() {
+            let index = self.index(list, tokens, false)?;
+            let old = list.get_item(index as isize).to_object(self.py);
+            list.set_item(index as isize, value)?;
+            self.undo.push(Undo::Replace(parent, index, old));
+        } else {
+            return Err(self.missing(tokens));
+        }
+        Ok(())
+    }
+
+    /// Undoes the changes made so far, latest first.
+    fn rollback(&mut self) {
+        let py = self.py;
+        while let Some(undo) = self.undo.pop() {
+            // The containers were changed by us just before, so none of
+            // this is expected to fail
+            let _ = match undo {
+                Undo::Root(old) => {
+                    self.root = old;
+                    Ok(())
+                }
+                Undo::Member(dict, key, old) => {
+                    let dict: &PyDict = dict.cast_as(py).expect("checked before");
+                    match old {
+                        Some(old) => dict.set_item(key, old),
+                        None => dict.del_item(key),
+                    }
+                }
+                Undo::Insert(list, index) => list.call_method1(py, "pop", (index,)).map(|_| ()),
+                Undo::Remove(list, index, old) => {
+                    let list: &PyList = list.cast_as(py).expect("checked before");
+                    list.insert(index as isize, old)
+                }
+                Undo::Replace(list, index, old) => {
+                    let list: &PyList = list.cast_as(py).expect("checked before");
+                    list.set_item(index as isize, old)
+                }
+            };
+        }
+    }
+}
+
+fn pointer_string(tokens: &[String]) -> String {
+    tokens
+        .iter()
+        .map(|token| format!("/{}", pointer::escape(token)))
+        .collect()
+}
+
+fn operation(py: Python, name: &str, path: &str, value: Option<&PyAny>) -> PyResult<PyObject> {
+    let op = PyDict::new(py);
+    op.set_item("op", name)?;
+    op.set_item("path", path)?;
+    if let Some(value) = value {
+        op.set_item("value", value)?;
+    }
+    Ok(op.to_object(py))
+}
+
+fn diff_values(
+    py: Python,
+    a: &PyAny,
+    b: &PyAny,
+    path: &str,
+    ops: &mut Vec<PyObject>,
+) -> PyResult<()> {
+    if json_equal(py, a, b)? {
+        return Ok(());
+    }
+    match (Kind::of(a), Kind::of(b)) {
+        (Kind::Object, Kind::Object) => {
+            let (a, b): (&PyDict, &PyDict) = (a.downcast_ref()?, b.downcast_ref()?);
+            for (key, value) in a {
+                let member = format!("{}/{}", path, pointer::escape(&key.str()?.to_string()?));
+                match b.get_item(key) {
+                    Some(other) => diff_values(py, value, other, &member, ops)?,
+                    None => ops.push(operation(py, "remove", &member, None)?),
+                }
+            }
+            for (key, value) in b {
+                if a.get_item(key).is_none() {
+                    let member = format!("{}/{}", path, pointer::escape(&key.str()?.to_string()?));
+                    ops.push(operation(py, "add", &member, Some(value))?);
+                }
+            }
+            Ok(())
+        }
+        (Kind::Array, Kind::Array) => {
+            diff_arrays(py, &elements(a).unwrap(), &elements(b).unwrap(), path, ops)
+        }
+        _ => {
+            ops.push(operation(py, "replace", path, Some(b))?);
+            Ok(())
+        }
+    }
+}
+
+/// An edit of an array, in the order of the elements
+enum Edit {
+    Keep,
+    /// Replaces `a[i]` with `b[j]`, diffed recursively
+    Replace(usize, usize),
+    Remove,
+    /// Adds `b[j]`
+    Add(usize),
+}
+
+/// Diffs arrays with the fewest replacements, removals and additions of
+/// elements, keeping equal elements where possible.
+fn diff_arrays(
+    py: Python,
+    a: &[&PyAny],
+    b: &[&PyAny],
+    path: &str,
+    ops: &mut Vec<PyObject>,
+) -> PyResult<()> {
+    let mut prefix = 0;
+    while prefix < a.len().min(b.len()) && json_equal(py, a[prefix], b[prefix])? {
+        prefix += 1;
+    }
+    let mut suffix = 0;
+    while suffix < a.len().min(b.len()) - prefix
+        && json_equal(py, a[a.len() - 1 - suffix], b[b.len() - 1 - suffix])?
+    {
+        suffix += 1;
+    }
+    let (a, b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let (n, m) = (a.len(), b.len());
+    let mut edits = Vec::with_capacity(n.max(m));
+    if (n + 1) * (m + 1) > MAX_DIFF_CELLS {
+        let pairs = n.min(m);
+        edits.extend((0..pairs).map(|k| Edit::Replace(k, k)));
+        edits.extend((pairs..n).map(|_| Edit::Remove));
+        edits.extend((pairs..m).map(Edit::Add));
+    } else {
+        let mut equal = vec![false; n * m];
+        for i in 0..n {
+            for j in 0..m {
+                equal[i * m + j] = json_equal(py, a[i], b[j])?;
+            }
+        }
+        // The fewest edits turning a[i..] into b[j..]
+        let mut cost = vec![0u32; (n + 1) * (m + 1)];
+        let at = |i: usize, j: usize| i * (m + 1) + j;
+        for i in (0..=n).rev() {
+            for j in (0..=m).rev() {
+                cost[at(i, j)] = if i == n {
+                    (m - j) as u32
+                } else if j == m {
+                    (n - i) as u32
+                } else if equal[i * m + j] {
+                    cost[at(i + 1, j + 1)]
+                } else {
+                    1 + cost[at(i + 1, j + 1)]
+                        .min(cost[at(i + 1, j)])
+                        .min(cost[at(i, j + 1)])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            let here = cost[at(i, j)];
+            if i < n && j < m && equal[i * m + j] {
+                edits.push(Edit::Keep);
+                i += 1;
+                j += 1;
+            } else if i < n && j < m && here == 1 + cost[at(i + 1, j + 1)] {
+                edits.push(Edit::Replace(i, j));
+                i += 1;
+                j += 1;
+            } else if i < n && here == 1 + cost[at(i + 1, j)] {
+                edits.push(Edit::Remove);
+                i += 1;
+            } else {
+                edits.push(Edit::Add(j));
+                j += 1;
+            }
+        }
+    }
+
+    let mut pos = prefix;
+    let element = |pos| format!("{}/{}", path, pos);
+    for edit in edits {
+        match edit {
+            Edit::Keep => pos += 1,
+            Edit::Replace(i, j) => {
+                diff_values(py, a[i], b[j], &element(pos), ops)?;
+                pos += 1;
+            }
+            Edit::Remove => ops.push(operation(py, "remove", &element(pos), None)?),
+            Edit::Add(j) => {
+                ops.push(operation(py, "add", &element(pos), Some(b[j]))?);
+                pos += 1;
+            }
+        }
+    }
+    Ok(())
+}
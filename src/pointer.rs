@@ -0,0 +1,199 @@
+use std::fmt;
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use serde::de::{DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use crate::error::JSONDecodeError;
+use crate::{HyperJsonValue, LoadsOptions};
+
+/// Splits an RFC 6901 JSON Pointer like `/a/b~1c/0` into its unescaped
+/// reference tokens. The empty pointer refers to the whole document.
+pub fn parse(pointer: &str) -> PyResult<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !pointer.starts_with('/') {
+        return Err(PyValueError::py_err(format!(
+            "invalid JSON pointer {:?}: must be empty or start with '/'",
+            pointer
+        )));
+    }
+    pointer[1..]
+        .split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    unescaped.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => unescaped.push('~'),
+                    Some('1') => unescaped.push('/'),
+                    _ => {
+                        return Err(PyValueError::py_err(format!(
+                            "invalid JSON pointer {:?}: '~' must be followed by '0' or '1'",
+                            pointer
+                        )))
+                    }
+                }
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// Escapes a key for use as a reference token.
+pub fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Turns a reference token into an array index. `-`, which refers to the
+/// position after the last element, and indexes with leading zeros never
+/// match.
+pub fn index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+/// A step into a document: the key it takes in objects and the index it
+/// takes in arrays.
+pub struct Token {
+    pub name: Option<String>,
+    pub index: Option<usize>,
+}
+
+impl Token {
+    /// A reference token, which depending on the value may be either.
+    pub fn new(token: String) -> Token {
+        Token {
+            index: index(&token),
+            name: Some(token),
+        }
+    }
+}
+
+/// Finds the value `tokens` refer to in a JSON document, and only builds
+/// Python objects for it. Everything else is skipped, but still checked.
+pub fn get(
+    py: Python,
+    options: &LoadsOptions,
+    json: &[u8],
+    tokens: &[Token],
+) -> PyResult<Option<PyObject>> {
+    let mut deserializer = serde_json::Deserializer::from_slice(json);
+    let seed = Pointer {
+        tokens,
+        value: options.seed(py, None),
+    };
+    seed.deserialize(&mut deserializer)
+        .and_then(|value| deserializer.end().map(|()| value))
+        .map_err(|e| {
+            if e.is_syntax() || e.is_eof() {
+                JSONDecodeError::py_err((e.to_string(), String::new(), 0))
+            } else {
+                PyValueError::py_err(e.to_string())
+            }
+        })
+}
+
+struct Pointer<'t, 'a> {
+    tokens: &'t [Token],
+    value: HyperJsonValue<'a>,
+}
+
+impl<'de, 't, 'a> DeserializeSeed<'de> for Pointer<'t, 'a> {
+    type Value = Option<PyObject>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if self.tokens.is_empty() {
+            return self.value.deserialize(deserializer).map(Some);
+        }
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 't, 'a> Visitor<'de> for Pointer<'t, 'a> {
+    type Value = Option<PyObject>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    // A scalar has nothing to refer to
+
+    fn visit_bool<E>(self, _value: bool) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_i64<E>(self, _value: i64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_u64<E>(self, _value: u64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_f64<E>(self, _value: f64) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_str<E>(self, _value: &str) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut found = None;
+        if let Some(target) = self.tokens[0].index {
+            for _ in 0..target {
+                if seq.next_element::<IgnoredAny>()?.is_none() {
+                    return Ok(None);
+                }
+            }
+            let rest = Pointer {
+                tokens: &self.tokens[1..],
+                value: self.value,
+            };
+            found = seq.next_element_seed(rest)?.and_then(|value| value);
+        }
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(found)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut found = None;
+        while let Some(key) = map.next_key::<String>()? {
+            // Like `loads`, the last of duplicate keys wins
+            if self.tokens[0].name.as_ref() == Some(&key) {
+                let rest = Pointer {
+                    tokens: &self.tokens[1..],
+                    value: self.value,
+                };
+                found = map.next_value_seed(rest)?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(found)
+    }
+}
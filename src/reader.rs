@@ -0,0 +1,203 @@
+use std::io;
+
+use pyo3::exceptions::{TypeError as PyTypeError, ValueError as PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyByteArray, PyBytes, PyString};
+use pyo3::AsPyPointer;
+
+/// How many bytes (or characters, for text files) are requested from the
+/// file object at a time.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How much of the start of the document is kept around for error handling.
+const HEAD_SIZE: usize = 16;
+
+/// Reads chunks from a Python file object.
+///
+/// Binary files are read with `readinto` into a reused `bytearray` where
+/// possible, otherwise `read(n)` is used. Text files are read as `str` and
+/// encoded to UTF-8. The file position is never moved back.
+pub struct ChunkSource {
+    fp: PyObject,
+    chunk: Option<PyObject>,
+    chunk_size: usize,
+    // Cleared for file-like objects whose `read` takes no size
+    sized: bool,
+    started: bool,
+    eof: bool,
+}
+
+impl ChunkSource {
+    pub fn new(py: Python, fp: &PyAny, chunk_size: usize) -> PyResult<ChunkSource> {
+        let chunk = if fp.hasattr("readinto")? {
+            Some(PyByteArray::new(py, &vec![0; chunk_size]).to_object(py))
+        } else {
+            None
+        };
+        Ok(ChunkSource {
+            fp: fp.to_object(py),
+            chunk,
+            chunk_size,
+            sized: true,
+            started: false,
+            eof: false,
+        })
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.eof
+    }
+
+    /// Appends the next chunk from the file to `out`. Returns `false` if
+    /// there was nothing left to read.
+    pub fn read_chunk(&mut self, py: Python, out: &mut Vec<u8>) -> PyResult<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+        let fp = self.fp.as_ref(py);
+        let n = match &self.chunk {
+            Some(chunk) => {
+                let chunk: &PyByteArray = chunk.cast_as(py)?;
+                let n = fp.call_method1("readinto", (chunk,))?;
+                if n.is_none() {
+                    return Err(PyValueError::py_err(
+                        "cannot read from a non-blocking file without data",
+                    ));
+                }
+                let n: usize = n.extract()?;
+                let data = unsafe {
+                    std::slice::from_raw_parts(
+                        pyo3::ffi::PyByteArray_AsString(chunk.as_ptr()) as *const u8,
+                        n.min(chunk.len()),
+                    )
+                };
+                out.extend_from_slice(data);
+                n
+            }
+            None => {
+                let data = match fp.call_method1("read", (self.chunk_size,)) {
+                    Ok(data) => data,
+                    // Like `json.load`, fall back to reading everything
+                    Err(ref err) if !self.started && err.is_instance::<PyTypeError>(py) => {
+                        self.sized = false;
+                        fp.call_method0("read")?
+                    }
+                    Err(err) => return Err(err),
+                };
+                if let Ok(s) = <PyString as PyTryFrom>::try_from(data) {
+                    let bytes = s.as_bytes()?;
+                    out.extend_from_slice(bytes);
+                    bytes.len()
+                } else if let Ok(b) = <PyBytes as PyTryFrom>::try_from(data) {
+                    out.extend_from_slice(b.as_bytes());
+                    b.as_bytes().len()
+                } else if data.is_none() {
+                    return Err(PyValueError::py_err(
+                        "cannot read from a non-blocking file without data",
+                    ));
+                } else {
+                    return Err(PyTypeError::py_err(format!(
+                        "read() must return str or bytes, got: {}",
+                        data.get_type().name()
+                    )));
+                }
+            }
+        };
+        self.started = true;
+        self.eof = n == 0 || !self.sized;
+        Ok(n > 0)
+    }
+}
+
+/// Reads from a Python file object in chunks, so that documents can be
+/// parsed without reading the whole file into memory first.
+///
+/// Errors raised by Python while reading are kept, so that they can be
+/// reported instead of the `io::Error` serde_json sees.
+pub struct PyReader<'p> {
+    py: Python<'p>,
+    source: ChunkSource,
+    pending: Vec<u8>,
+    pos: usize,
+    error: Option<PyErr>,
+}
+
+impl<'p> PyReader<'p> {
+    pub fn new(py: Python<'p>, fp: &'p PyAny, chunk_size: usize) -> PyResult<PyReader<'p>> {
+        Ok(PyReader {
+            py,
+            source: ChunkSource::new(py, fp, chunk_size)?,
+            pending: Vec::new(),
+            pos: 0,
+            error: None,
+        })
+    }
+
+    /// Takes the error Python raised while reading, if any.
+    pub fn take_error(&mut self) -> Option<PyErr> {
+        self.error.take()
+    }
+}
+
+impl<'p> io::Read for PyReader<'p> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            self.pending.clear();
+            self.pos = 0;
+            match self.source.read_chunk(self.py, &mut self.pending) {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(err) => {
+                    self.error = Some(err);
+                    return Err(io::Error::other("could not read from file"));
+                }
+            }
+        }
+        let available = &self.pending[self.pos..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Remembers the start of a stream, so that a short document which could
+/// not be parsed can be looked at again.
+pub struct Recorder<R> {
+    inner: R,
+    head: Vec<u8>,
+    total: usize,
+}
+
+impl<R: io::Read> Recorder<R> {
+    pub fn new(inner: R) -> Recorder<R> {
+        Recorder {
+            inner,
+            head: Vec::with_capacity(HEAD_SIZE),
+            total: 0,
+        }
+    }
+
+    /// Returns the whole document if it is short enough to have been kept.
+    pub fn short_document(&mut self) -> io::Result<Option<&[u8]>> {
+        let mut buf = [0; HEAD_SIZE + 1];
+        while self.total <= HEAD_SIZE {
+            if io::Read::read(self, &mut buf)? == 0 {
+                return Ok(Some(&self.head));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: io::Read> io::Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.head.len() < HEAD_SIZE {
+            let keep = n.min(HEAD_SIZE - self.head.len());
+            self.head.extend_from_slice(&buf[..keep]);
+        }
+        self.total += n;
+        Ok(n)
+    }
+}
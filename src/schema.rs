@@ -0,0 +1,1973 @@
+// The code generated by `#[pyclass]` trips this lint
+#![allow(clippy::manual_div_ceil)]
+
+//! JSON Schema (Draft 2020-12) validation.
+//!
+//! A schema is compiled once into a list of nodes, one per subschema, with
+//! `$ref`s resolved to node indexes. Instances are validated as Python
+//! objects, following the same mapping as `dumps`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+
+use pyo3::class::basic::CompareOp;
+use pyo3::exceptions::RecursionError as PyRecursionError;
+use pyo3::exceptions::TypeError as PyTypeError;
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyFloat, PyList, PyString};
+use pyo3::{ffi, AsPyPointer, PyNativeType};
+use regex::Regex;
+
+use crate::error::ValidationError;
+use crate::pointer;
+use crate::value::{elements, is_string, json_equal, parse_text, Kind};
+
+/// The base URI of schemas without an `$id`.
+const DEFAULT_BASE: &str = "hyperjson:///schema.json";
+
+/// How many subschemas may be applied inside one another, which bounds the
+/// stack used by `$dynamicRef`s and deeply nested instances.
+const MAX_DEPTH: usize = 500;
+
+/// Validates instances against a JSON Schema (Draft 2020-12).
+///
+/// The schema is compiled when the validator is created. `$ref`s may point
+/// anywhere in the schema, by JSON Pointer, `$id`, `$anchor` or
+/// `$dynamicAnchor`; other documents are not fetched. A `$ref` that leads
+/// back to where it started without moving into the instance makes the
+/// schema invalid. `format` is only an annotation, as the draft has it by
+/// default. `pattern` and `patternProperties` use the syntax of the `regex`
+/// crate rather than ECMA-262, so lookaround and backreferences make the
+/// schema invalid.
+#[pyclass]
+pub struct Validator {
+    schema: Schema,
+}
+
+#[pymethods]
+impl Validator {
+    #[new]
+    #[allow(clippy::new_ret_no_self)]
+    fn new(obj: &PyRawObject, schema: PyObject) -> PyResult<()> {
+        let py = obj.py();
+        let schema = Schema::compile(py, schema.extract(py)?)?;
+        obj.init(Validator { schema });
+        Ok(())
+    }
+
+    /// Raises `ValidationError` for the first error, with all of them in its
+    /// `errors` attribute. A bytes-like instance is parsed as JSON, while a
+    /// `str` is validated as a string.
+    fn validate(&self, py: Python, instance: PyObject) -> PyResult<()> {
+        let instance = parse_instance(py, instance)?;
+        self.schema.check(py, instance.extract(py)?)
+    }
+
+    fn is_valid(&self, py: Python, instance: PyObject) -> PyResult<bool> {
+        let instance = parse_instance(py, instance)?;
+        self.schema.is_valid(py, instance.extract(py)?)
+    }
+
+    /// Returns all errors as a list of `ValidationError`s, which is empty
+    /// if the instance is valid.
+    fn errors(&self, py: Python, instance: PyObject) -> PyResult<PyObject> {
+        let instance = parse_instance(py, instance)?;
+        let errors = self.schema.errors(py, instance.extract(py)?)?;
+        let list = PyList::empty(py);
+        for error in errors {
+            list.append(error.to_py(py)?)?;
+        }
+        Ok(list.to_object(py))
+    }
+}
+
+/// Parses a bytes-like instance as a JSON document. Anything else is an
+/// instance as it is, strings included.
+fn parse_instance(py: Python, instance: PyObject) -> PyResult<PyObject> {
+    let obj: &PyAny = instance.extract(py)?;
+    if is_string(obj) {
+        return Ok(instance);
+    }
+    Ok(parse_text(py, obj)?.unwrap_or(instance))
+}
+
+/// The `Validator` given as `schema`, which has to be one so that the
+/// schema is not compiled again on every call.
+pub fn validator(schema: &PyAny) -> PyResult<&Validator> {
+    schema.extract::<&Validator>().map_err(|_| {
+        PyTypeError::py_err(format!(
+            "schema must be a Validator, not {}",
+            schema.get_type().name()
+        ))
+    })
+}
+
+/// Validation while `loads` decodes a document. Each member and element is
+/// checked against the subschemas that have to hold for it as soon as it is
+/// complete, so that an invalid document fails early. What is found valid
+/// is remembered, so checking the whole value at the end does not evaluate
+/// it again.
+pub struct Decoding<'s> {
+    schema: &'s Schema,
+    // Whether members and elements are checked on their own, which
+    // `$dynamicRef`s rule out as they depend on the way there
+    early: bool,
+    // Subschemas and values, by address, found valid so far
+    known: RefCell<HashSet<(usize, usize)>>,
+    // Keeps those values alive, so that their addresses are not reused
+    alive: RefCell<Vec<PyObject>>,
+    // The error that stopped decoding, which serde only carries as text
+    raised: Cell<Option<PyErr>>,
+}
+
+/// Where a value being decoded is: its instance path, and the subschemas
+/// that have to hold for it with their schema paths.
+pub struct Cursor<'s> {
+    instance_path: Path<Token>,
+    nodes: Vec<(usize, Path<Step<'s>>)>,
+}
+
+/// The subschemas of a `Cursor` and those that apply to the same value
+/// with them through `$ref` and `allOf`, for finding the ones of its
+/// members or elements.
+pub struct Container<'c, 's> {
+    cursor: &'c Cursor<'s>,
+    keywords: Vec<(&'s Keywords, Path<Step<'s>>)>,
+}
+
+/// A path built from its end, which is only spelled out for errors.
+type Path<T> = Option<Rc<Link<T>>>;
+
+struct Link<T> {
+    parent: Path<T>,
+    token: T,
+}
+
+fn push<T>(parent: &Path<T>, token: T) -> Path<T> {
+    Some(Rc::new(Link {
+        parent: parent.clone(),
+        token,
+    }))
+}
+
+/// Spells out a path as a JSON Pointer.
+fn pointer<T: fmt::Display>(path: &Path<T>) -> String {
+    let mut tokens = Vec::new();
+    let mut link = path;
+    while let Some(next) = link {
+        tokens.push(next.token.to_string());
+        link = &next.parent;
+    }
+    tokens
+        .iter()
+        .rev()
+        .map(|token| format!("/{}", token))
+        .collect()
+}
+
+/// A member or element of the instance.
+enum Token {
+    Key(String),
+    Index(usize),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Key(key) => f.write_str(&pointer::escape(key)),
+            Token::Index(index) => write!(f, "{}", index),
+        }
+    }
+}
+
+/// A keyword that leads to a subschema.
+enum Step<'s> {
+    Properties(&'s str),
+    PatternProperties(&'s str),
+    AdditionalProperties,
+    PrefixItems(usize),
+    Items,
+    Ref,
+    AllOf(usize),
+}
+
+impl fmt::Display for Step<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Properties(name) => write!(f, "properties/{}", pointer::escape(name)),
+            Step::PatternProperties(pattern) => {
+                write!(f, "patternProperties/{}", pointer::escape(pattern))
+            }
+            Step::AdditionalProperties => f.write_str("additionalProperties"),
+            Step::PrefixItems(index) => write!(f, "prefixItems/{}", index),
+            Step::Items => f.write_str("items"),
+            Step::Ref => f.write_str("$ref"),
+            Step::AllOf(index) => write!(f, "allOf/{}", index),
+        }
+    }
+}
+
+impl<'s> Decoding<'s> {
+    pub fn new(validator: &'s Validator) -> Decoding<'s> {
+        let schema = &validator.schema;
+        let early = schema.nodes.iter().all(|node| match node {
+            Node::Object(keywords) => keywords.dynamic_reference.is_none(),
+            Node::Bool(_) => true,
+        });
+        Decoding {
+            schema,
+            early,
+            known: RefCell::new(HashSet::new()),
+            alive: RefCell::new(Vec::new()),
+            raised: Cell::new(None),
+        }
+    }
+
+    /// The cursor of the whole document, if its parts are checked early.
+    pub fn root(&self) -> Option<Cursor<'s>> {
+        if !self.early {
+            return None;
+        }
+        Some(Cursor {
+            instance_path: None,
+            nodes: vec![(0, None)],
+        })
+    }
+
+    /// The object or array at `cursor`.
+    pub fn container<'c>(&self, cursor: &'c Cursor<'s>) -> Container<'c, 's> {
+        let mut pending: Vec<_> = cursor.nodes.iter().rev().cloned().collect();
+        let mut keywords = Vec::new();
+        // `Compiler::check_cycles` makes sure that this ends
+        while let Some((node, path)) = pending.pop() {
+            let found = match &self.schema.nodes[node] {
+                Node::Object(found) => found,
+                Node::Bool(_) => continue,
+            };
+            for (i, &node) in found.all_of.iter().enumerate().rev() {
+                pending.push((node, push(&path, Step::AllOf(i))));
+            }
+            if let Some(reference) = &found.reference {
+                pending.push((reference.node, push(&path, Step::Ref)));
+            }
+            keywords.push((found.as_ref(), path));
+        }
+        Container { cursor, keywords }
+    }
+
+    /// The cursor of the member `key` of an object.
+    pub fn member(&self, container: &Container<'_, 's>, key: &str) -> Option<Cursor<'s>> {
+        let mut nodes = Vec::new();
+        for (keywords, path) in &container.keywords {
+            let mut matched = false;
+            for (name, node) in &keywords.properties {
+                if name == key {
+                    matched = true;
+                    nodes.push((*node, push(path, Step::Properties(name))));
+                }
+            }
+            for (pattern, regex, node) in &keywords.pattern_properties {
+                if regex.is_match(key) {
+                    matched = true;
+                    nodes.push((*node, push(path, Step::PatternProperties(pattern))));
+                }
+            }
+            match keywords.additional_properties {
+                // Reported for the object as a whole
+                Some(node) if !matched && !self.is_false(node) => {
+                    nodes.push((node, push(path, Step::AdditionalProperties)));
+                }
+                _ => {}
+            }
+        }
+        self.cursor(container, Token::Key(key.to_string()), nodes)
+    }
+
+    /// The cursor of the element at `index` of an array.
+    pub fn element(&self, container: &Container<'_, 's>, index: usize) -> Option<Cursor<'s>> {
+        let mut nodes = Vec::new();
+        for (keywords, path) in &container.keywords {
+            if let Some(&node) = keywords.prefix_items.get(index) {
+                nodes.push((node, push(path, Step::PrefixItems(index))));
+            } else {
+                match keywords.items {
+                    // Reported for the array as a whole
+                    Some(node) if !self.is_false(node) => {
+                        nodes.push((node, push(path, Step::Items)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.cursor(container, Token::Index(index), nodes)
+    }
+
+    /// Checks a complete member or element. If it is not valid, the error
+    /// is kept for `raised` and false returned.
+    pub fn check(&self, py: Python, cursor: &Cursor, value: &PyAny) -> bool {
+        for (node, path) in &cursor.nodes {
+            let mut evaluator = self.schema.evaluator(py, true);
+            evaluator.known = Some(&self.known);
+            let result = evaluator.apply(*node, value, String::new(), &mut Evaluated::default());
+            let err = match result {
+                Ok(true) => {
+                    // Scalars are cheaper to check again than to remember
+                    if value.downcast_ref::<PyDict>().is_ok()
+                        || value.downcast_ref::<PyList>().is_ok()
+                    {
+                        self.known
+                            .borrow_mut()
+                            .insert((*node, value.as_ptr() as usize));
+                        self.alive.borrow_mut().push(value.to_object(py));
+                    }
+                    continue;
+                }
+                Ok(false) => {
+                    // The paths are relative to the value and the subschema
+                    let instance_path = pointer(&cursor.instance_path);
+                    let schema_path = pointer(path);
+                    for error in &mut evaluator.errors {
+                        error.instance_path.insert_str(0, &instance_path);
+                        error.schema_path.insert_str(0, &schema_path);
+                    }
+                    raise(py, &evaluator.errors)
+                }
+                Err(err) => err,
+            };
+            self.raised.set(Some(err));
+            return false;
+        }
+        true
+    }
+
+    /// Checks the whole value once it is decoded.
+    pub fn finish(&self, py: Python, value: &PyAny) -> PyResult<()> {
+        let mut evaluator = self.schema.evaluator(py, true);
+        evaluator.known = Some(&self.known);
+        if evaluator.apply(0, value, String::new(), &mut Evaluated::default())? {
+            return Ok(());
+        }
+        Err(raise(py, &evaluator.errors))
+    }
+
+    /// The error that stopped decoding, if it was raised by `check`.
+    pub fn raised(&self) -> Option<PyErr> {
+        self.raised.take()
+    }
+
+    fn is_false(&self, node: usize) -> bool {
+        matches!(self.schema.nodes[node], Node::Bool(false))
+    }
+
+    fn cursor(
+        &self,
+        container: &Container<'_, 's>,
+        token: Token,
+        mut nodes: Vec<(usize, Path<Step<'s>>)>,
+    ) -> Option<Cursor<'s>> {
+        nodes.retain(|(node, _)| !matches!(self.schema.nodes[*node], Node::Bool(true)));
+        if nodes.is_empty() {
+            return None;
+        }
+        Some(Cursor {
+            instance_path: push(&container.cursor.instance_path, token),
+            nodes,
+        })
+    }
+}
+
+/// The `ValidationError` for the first of `errors`, with all of them in its
+/// `errors` attribute.
+fn raise(py: Python, errors: &[Error]) -> PyErr {
+    let all = match errors
+        .iter()
+        .map(|error| error.to_py(py))
+        .collect::<PyResult<Vec<_>>>()
+    {
+        Ok(all) => all,
+        Err(err) => return err,
+    };
+    let first = all[0];
+    if let Err(err) = first.setattr("errors", all.to_object(py)) {
+        return err;
+    }
+    PyErr::from_instance(first)
+}
+
+pub struct Schema {
+    nodes: Vec<Node>,
+    resources: Vec<Resource>,
+    // Whether `unevaluatedItems` or `unevaluatedProperties` are used, which
+    // need to know everything applied to an instance
+    annotations: bool,
+}
+
+impl Schema {
+    fn compile(py: Python, schema: &PyAny) -> PyResult<Schema> {
+        let parsed = parse_text(py, schema)?;
+        let schema = match &parsed {
+            Some(parsed) => parsed.extract(py)?,
+            None => schema,
+        };
+        let mut compiler = Compiler {
+            py,
+            nodes: Vec::new(),
+            resources: Vec::new(),
+            uris: HashMap::new(),
+            documents: HashMap::new(),
+            refs: Vec::new(),
+            annotations: false,
+        };
+        let root = Scope {
+            base: DEFAULT_BASE.to_string(),
+            pointer: String::new(),
+            resource: 0,
+        };
+        compiler.resources.push(Resource::default());
+        compiler
+            .documents
+            .insert(DEFAULT_BASE.to_string(), schema.to_object(py));
+        compiler.compile(schema, vec![root])?;
+        compiler.resolve()?;
+        Ok(Schema {
+            nodes: compiler.nodes,
+            resources: compiler.resources,
+            annotations: compiler.annotations,
+        })
+    }
+
+    fn evaluator<'s>(&'s self, py: Python<'s>, collect: bool) -> Evaluator<'s> {
+        Evaluator {
+            py,
+            schema: self,
+            collect,
+            scope: Vec::new(),
+            instance_path: Vec::new(),
+            keyword_path: Vec::new(),
+            errors: Vec::new(),
+            known: None,
+        }
+    }
+
+    fn is_valid(&self, py: Python, instance: &PyAny) -> PyResult<bool> {
+        self.evaluator(py, false)
+            .apply(0, instance, String::new(), &mut Evaluated::default())
+    }
+
+    fn errors(&self, py: Python, instance: &PyAny) -> PyResult<Vec<Error>> {
+        let mut evaluator = self.evaluator(py, true);
+        evaluator.apply(0, instance, String::new(), &mut Evaluated::default())?;
+        Ok(evaluator.errors)
+    }
+
+    fn check(&self, py: Python, instance: &PyAny) -> PyResult<()> {
+        let mut evaluator = self.evaluator(py, true);
+        if evaluator.apply(0, instance, String::new(), &mut Evaluated::default())? {
+            return Ok(());
+        }
+        Err(raise(py, &evaluator.errors))
+    }
+}
+
+/// A failed keyword.
+struct Error {
+    message: String,
+    instance: PyObject,
+    // JSON Pointers to the instance and to the keyword, through any `$ref`s
+    instance_path: String,
+    schema_path: String,
+}
+
+impl Error {
+    fn to_py<'p>(&self, py: Python<'p>) -> PyResult<&'p PyAny> {
+        let error = py
+            .get_type::<ValidationError>()
+            .call1((self.message.as_str(),))?;
+        error.setattr("message", &self.message)?;
+        error.setattr("instance", &self.instance)?;
+        error.setattr("instance_path", &self.instance_path)?;
+        error.setattr("schema_path", &self.schema_path)?;
+        Ok(error)
+    }
+}
+
+/// A schema resource: the root or a subschema with an `$id`.
+#[derive(Default)]
+struct Resource {
+    dynamic_anchors: HashMap<String, usize>,
+}
+
+enum Node {
+    Bool(bool),
+    Object(Box<Keywords>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Type {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Type> {
+        Some(match name {
+            "null" => Type::Null,
+            "boolean" => Type::Boolean,
+            "integer" => Type::Integer,
+            "number" => Type::Number,
+            "string" => Type::String,
+            "array" => Type::Array,
+            "object" => Type::Object,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Integer => "integer",
+            Type::Number => "number",
+            Type::String => "string",
+            Type::Array => "array",
+            Type::Object => "object",
+        }
+    }
+}
+
+/// A reference, resolved to a node after compiling.
+struct Reference {
+    uri: String,
+    node: usize,
+}
+
+#[derive(Default)]
+struct Keywords {
+    // Set if this is the root of a resource
+    resource: Option<usize>,
+    dynamic_anchor: Option<String>,
+    reference: Option<Reference>,
+    dynamic_reference: Option<Reference>,
+
+    types: Option<Vec<Type>>,
+    enumeration: Option<PyObject>,
+    constant: Option<PyObject>,
+    multiple_of: Option<PyObject>,
+    maximum: Option<PyObject>,
+    exclusive_maximum: Option<PyObject>,
+    minimum: Option<PyObject>,
+    exclusive_minimum: Option<PyObject>,
+    max_length: Option<usize>,
+    min_length: Option<usize>,
+    pattern: Option<(String, Regex)>,
+    max_items: Option<usize>,
+    min_items: Option<usize>,
+    unique_items: bool,
+    max_contains: Option<usize>,
+    min_contains: Option<usize>,
+    max_properties: Option<usize>,
+    min_properties: Option<usize>,
+    required: Vec<String>,
+    dependent_required: Vec<(String, Vec<String>)>,
+
+    all_of: Vec<usize>,
+    any_of: Option<Vec<usize>>,
+    one_of: Option<Vec<usize>>,
+    not: Option<usize>,
+    condition: Option<usize>,
+    then: Option<usize>,
+    otherwise: Option<usize>,
+    dependent_schemas: Vec<(String, usize)>,
+    prefix_items: Vec<usize>,
+    items: Option<usize>,
+    contains: Option<usize>,
+    properties: Vec<(String, usize)>,
+    pattern_properties: Vec<(String, Regex, usize)>,
+    additional_properties: Option<usize>,
+    property_names: Option<usize>,
+    unevaluated_items: Option<usize>,
+    unevaluated_properties: Option<usize>,
+}
+
+impl Keywords {
+    /// The subschemas applied to the instance itself rather than to a part
+    /// of it.
+    fn in_place(&self) -> Vec<usize> {
+        let references = self.reference.iter().chain(&self.dynamic_reference);
+        references
+            .map(|reference| reference.node)
+            .chain(self.all_of.iter().copied())
+            .chain(self.any_of.iter().flatten().copied())
+            .chain(self.one_of.iter().flatten().copied())
+            .chain(self.not)
+            .chain(self.condition)
+            .chain(self.then)
+            .chain(self.otherwise)
+            .chain(self.dependent_schemas.iter().map(|(_, node)| *node))
+            .collect()
+    }
+}
+
+/// Where a subschema is: its pointer from the root of each resource that
+/// contains it, innermost last.
+#[derive(Clone)]
+struct Scope {
+    base: String,
+    pointer: String,
+    resource: usize,
+}
+
+struct Compiler<'p> {
+    py: Python<'p>,
+    nodes: Vec<Node>,
+    resources: Vec<Resource>,
+    uris: HashMap<String, usize>,
+    // The schema of each resource, to look up pointers to places that are
+    // not subschemas themselves
+    documents: HashMap<String, PyObject>,
+    // Nodes with a `$ref` (or a `$dynamicRef` if set) to resolve
+    refs: Vec<(usize, bool)>,
+    annotations: bool,
+}
+
+impl<'p> Compiler<'p> {
+    fn invalid(&self, scopes: &[Scope], message: &str) -> PyErr {
+        let scope = &scopes[0];
+        PyValueError::py_err(format!(
+            "invalid schema at {:?}: {}",
+            scope.pointer, message
+        ))
+    }
+
+    fn compile(&mut self, schema: &PyAny, mut scopes: Vec<Scope>) -> PyResult<usize> {
+        let index = self.nodes.len();
+        self.nodes.push(Node::Bool(true));
+        if let Ok(value) = schema.extract::<bool>() {
+            if Kind::of(schema) == Kind::Bool {
+                self.register(&scopes, index);
+                self.nodes[index] = Node::Bool(value);
+                return Ok(index);
+            }
+        }
+        let schema: &PyDict = schema
+            .downcast_ref()
+            .map_err(|_| self.invalid(&scopes, "a schema must be an object or a boolean"))?;
+        let mut keywords = Keywords::default();
+        if let Some(id) = schema.get_item("$id") {
+            let id: String = id
+                .extract()
+                .map_err(|_| self.invalid(&scopes, "$id must be a string"))?;
+            let base = without_fragment(&resolve(&scopes[scopes.len() - 1].base, &id));
+            keywords.resource = Some(self.resources.len());
+            scopes.push(Scope {
+                base: base.clone(),
+                pointer: String::new(),
+                resource: self.resources.len(),
+            });
+            self.resources.push(Resource::default());
+            self.documents.insert(base, schema.to_object(self.py));
+        } else if index == 0 {
+            keywords.resource = Some(0);
+        }
+        self.register(&scopes, index);
+        let scope = scopes[scopes.len() - 1].clone();
+        if let Some(anchor) = schema.get_item("$anchor") {
+            let anchor: String = anchor.extract()?;
+            self.uris
+                .insert(format!("{}#{}", scope.base, anchor), index);
+        }
+        if let Some(anchor) = schema.get_item("$dynamicAnchor") {
+            let anchor: String = anchor.extract()?;
+            self.uris
+                .insert(format!("{}#{}", scope.base, anchor), index);
+            self.resources[scope.resource]
+                .dynamic_anchors
+                .insert(anchor.clone(), index);
+            keywords.dynamic_anchor = Some(anchor);
+        }
+        for (name, dynamic) in &[("$ref", false), ("$dynamicRef", true)] {
+            if let Some(reference) = schema.get_item(*name) {
+                let reference: String = reference
+                    .extract()
+                    .map_err(|_| self.invalid(&scopes, &format!("{} must be a string", name)))?;
+                let reference = Some(Reference {
+                    uri: resolve(&scope.base, &reference),
+                    node: 0,
+                });
+                if *dynamic {
+                    keywords.dynamic_reference = reference;
+                } else {
+                    keywords.reference = reference;
+                }
+                self.refs.push((index, *dynamic));
+            }
+        }
+
+        for (key, value) in schema {
+            let key: String = match key.extract() {
+                Ok(key) => key,
+                Err(_) => continue,
+            };
+            let (sub, subs) = (
+                |compiler: &mut Compiler, path: &str| -> PyResult<usize> {
+                    compiler.compile(value, descend(&scopes, path))
+                },
+                |compiler: &mut Compiler, path: &str| -> PyResult<Vec<usize>> {
+                    let list: &PyList = value.downcast_ref().map_err(|_| {
+                        compiler.invalid(&scopes, &format!("{} must be an array", path))
+                    })?;
+                    list.iter()
+                        .enumerate()
+                        .map(|(i, value)| {
+                            compiler.compile(value, descend(&scopes, &format!("{}/{}", path, i)))
+                        })
+                        .collect()
+                },
+            );
+            let count = |compiler: &Compiler| -> PyResult<usize> {
+                match value.extract::<f64>() {
+                    Ok(count)
+                        if count >= 0.0
+                            && count.fract() == 0.0
+                            && Kind::of(value) == Kind::Number =>
+                    {
+                        Ok(count as usize)
+                    }
+                    _ => Err(compiler
+                        .invalid(&scopes, &format!("{} must be a non-negative integer", key))),
+                }
+            };
+            let number = |compiler: &Compiler| -> PyResult<PyObject> {
+                if Kind::of(value) != Kind::Number {
+                    return Err(compiler.invalid(&scopes, &format!("{} must be a number", key)));
+                }
+                Ok(value.to_object(compiler.py))
+            };
+            let members = |compiler: &mut Compiler, path: &str| -> PyResult<Vec<(String, usize)>> {
+                let dict: &PyDict = value.downcast_ref().map_err(|_| {
+                    compiler.invalid(&scopes, &format!("{} must be an object", path))
+                })?;
+                let mut members = Vec::with_capacity(dict.len());
+                for (name, value) in dict {
+                    let name: String = name.extract()?;
+                    let path = format!("{}/{}", path, pointer::escape(&name));
+                    members.push((name, compiler.compile(value, descend(&scopes, &path))?));
+                }
+                Ok(members)
+            };
+            match key.as_str() {
+                "type" => {
+                    let names: Vec<String> = if Kind::of(value) == Kind::String {
+                        vec![value.extract()?]
+                    } else {
+                        value.extract()?
+                    };
+                    let types = names
+                        .iter()
+                        .map(|name| {
+                            Type::parse(name).ok_or_else(|| {
+                                self.invalid(&scopes, &format!("unknown type {:?}", name))
+                            })
+                        })
+                        .collect::<PyResult<_>>()?;
+                    keywords.types = Some(types);
+                }
+                "enum" => {
+                    if Kind::of(value) != Kind::Array {
+                        return Err(self.invalid(&scopes, "enum must be an array"));
+                    }
+                    keywords.enumeration = Some(value.to_object(self.py));
+                }
+                "const" => keywords.constant = Some(value.to_object(self.py)),
+                "multipleOf" => {
+                    let divisor = number(self)?;
+                    if !value.rich_compare(0, CompareOp::Gt)?.is_true(self.py)? {
+                        return Err(self.invalid(&scopes, "multipleOf must be positive"));
+                    }
+                    keywords.multiple_of = Some(divisor);
+                }
+                "maximum" => keywords.maximum = Some(number(self)?),
+                "exclusiveMaximum" => keywords.exclusive_maximum = Some(number(self)?),
+                "minimum" => keywords.minimum = Some(number(self)?),
+                "exclusiveMinimum" => keywords.exclusive_minimum = Some(number(self)?),
+                "maxLength" => keywords.max_length = Some(count(self)?),
+                "minLength" => keywords.min_length = Some(count(self)?),
+                "pattern" => {
+                    let pattern: String = value.extract()?;
+                    let regex =
+                        Regex::new(&pattern).map_err(|e| self.invalid(&scopes, &e.to_string()))?;
+                    keywords.pattern = Some((pattern, regex));
+                }
+                "maxItems" => keywords.max_items = Some(count(self)?),
+                "minItems" => keywords.min_items = Some(count(self)?),
+                "uniqueItems" => keywords.unique_items = value.extract()?,
+                "maxContains" => keywords.max_contains = Some(count(self)?),
+                "minContains" => keywords.min_contains = Some(count(self)?),
+                "maxProperties" => keywords.max_properties = Some(count(self)?),
+                "minProperties" => keywords.min_properties = Some(count(self)?),
+                "required" => keywords.required = value.extract()?,
+                "dependentRequired" => {
+                    let dict: &PyDict = value.downcast_ref()?;
+                    for (name, required) in dict {
+                        keywords
+                            .dependent_required
+                            .push((name.extract()?, required.extract()?));
+                    }
+                }
+                "allOf" => keywords.all_of = subs(self, "allOf")?,
+                "anyOf" => keywords.any_of = Some(subs(self, "anyOf")?),
+                "oneOf" => keywords.one_of = Some(subs(self, "oneOf")?),
+                "not" => keywords.not = Some(sub(self, "not")?),
+                "if" => keywords.condition = Some(sub(self, "if")?),
+                "then" => keywords.then = Some(sub(self, "then")?),
+                "else" => keywords.otherwise = Some(sub(self, "else")?),
+                "dependentSchemas" => {
+                    keywords.dependent_schemas = members(self, "dependentSchemas")?
+                }
+                "prefixItems" => keywords.prefix_items = subs(self, "prefixItems")?,
+                "items" => keywords.items = Some(sub(self, "items")?),
+                "contains" => keywords.contains = Some(sub(self, "contains")?),
+                "properties" => keywords.properties = members(self, "properties")?,
+                "patternProperties" => {
+                    for (pattern, node) in members(self, "patternProperties")? {
+                        let regex = Regex::new(&pattern)
+                            .map_err(|e| self.invalid(&scopes, &e.to_string()))?;
+                        keywords.pattern_properties.push((pattern, regex, node));
+                    }
+                }
+                "additionalProperties" => {
+                    keywords.additional_properties = Some(sub(self, "additionalProperties")?)
+                }
+                "propertyNames" => keywords.property_names = Some(sub(self, "propertyNames")?),
+                "unevaluatedItems" => {
+                    self.annotations = true;
+                    keywords.unevaluated_items = Some(sub(self, "unevaluatedItems")?);
+                }
+                "unevaluatedProperties" => {
+                    self.annotations = true;
+                    keywords.unevaluated_properties = Some(sub(self, "unevaluatedProperties")?);
+                }
+                "$defs" | "definitions" => {
+                    members(self, &key)?;
+                }
+                // Annotations and unknown keywords
+                _ => {}
+            }
+        }
+        self.nodes[index] = Node::Object(Box::new(keywords));
+        Ok(index)
+    }
+
+    fn register(&mut self, scopes: &[Scope], index: usize) {
+        for scope in scopes {
+            let uri = if scope.pointer.is_empty() {
+                scope.base.clone()
+            } else {
+                format!("{}#{}", scope.base, scope.pointer)
+            };
+            self.uris.entry(uri).or_insert(index);
+        }
+    }
+
+    /// Resolves the references collected while compiling, compiling the
+    /// places they point to if need be.
+    fn resolve(&mut self) -> PyResult<()> {
+        while let Some((index, dynamic)) = self.refs.pop() {
+            let uri = match &self.nodes[index] {
+                Node::Object(keywords) if dynamic => &keywords.dynamic_reference,
+                Node::Object(keywords) => &keywords.reference,
+                Node::Bool(_) => unreachable!(),
+            };
+            let uri = normalize(&uri.as_ref().unwrap().uri);
+            let node = match self.uris.get(&uri) {
+                Some(&node) => node,
+                None => self.compile_pointer(&uri)?,
+            };
+            if let Node::Object(keywords) = &mut self.nodes[index] {
+                let reference = if dynamic {
+                    &mut keywords.dynamic_reference
+                } else {
+                    &mut keywords.reference
+                };
+                reference.as_mut().unwrap().node = node;
+            }
+        }
+        self.check_cycles()
+    }
+
+    /// Rejects references that lead back to a subschema without moving into
+    /// the instance, as evaluating them would never end.
+    fn check_cycles(&self) -> PyResult<()> {
+        let children: Vec<Vec<usize>> = self
+            .nodes
+            .iter()
+            .map(|node| match node {
+                Node::Object(keywords) => keywords.in_place(),
+                Node::Bool(_) => Vec::new(),
+            })
+            .collect();
+        // Whether each node is on the current path, or done with
+        let mut on_path = vec![false; self.nodes.len()];
+        let mut done = vec![false; self.nodes.len()];
+        for root in 0..self.nodes.len() {
+            if done[root] {
+                continue;
+            }
+            on_path[root] = true;
+            let mut path = vec![(root, 0)];
+            while let Some((node, next)) = path.last_mut() {
+                let node = *node;
+                match children[node].get(*next) {
+                    Some(&child) => {
+                        *next += 1;
+                        if on_path[child] {
+                            return Err(PyValueError::py_err(format!(
+                                "invalid schema: reference cycle through {:?} that never \
+                                 moves into the instance",
+                                self.uri_of(child)
+                            )));
+                        }
+                        if !done[child] {
+                            on_path[child] = true;
+                            path.push((child, 0));
+                        }
+                    }
+                    None => {
+                        on_path[node] = false;
+                        done[node] = true;
+                        path.pop();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A URI of a node, for error messages.
+    fn uri_of(&self, node: usize) -> String {
+        self.uris
+            .iter()
+            .filter(|(_, &index)| index == node)
+            .map(|(uri, _)| uri.clone())
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// Compiles the place a JSON Pointer fragment refers to.
+    fn compile_pointer(&mut self, uri: &str) -> PyResult<usize> {
+        let unresolvable = || PyValueError::py_err(format!("unresolvable reference {:?}", uri));
+        let (base, fragment) = match uri.find('#') {
+            Some(hash) => (&uri[..hash], &uri[hash + 1..]),
+            None => return Err(unresolvable()),
+        };
+        let document = self.documents.get(base).ok_or_else(unresolvable)?;
+        let document = document.clone_ref(self.py);
+        let mut target: &PyAny = document.extract(self.py)?;
+        let tokens = pointer::parse(fragment).map_err(|_| unresolvable())?;
+        for token in &tokens {
+            let next = if let Ok(dict) = target.downcast_ref::<PyDict>() {
+                dict.get_item(token.as_str())
+            } else {
+                match (elements(target), pointer::index(token)) {
+                    (Some(elements), Some(index)) => elements.get(index).cloned(),
+                    _ => None,
+                }
+            };
+            target = next.ok_or_else(unresolvable)?;
+        }
+        let resource = self.resource_of(base);
+        let scope = Scope {
+            base: base.to_string(),
+            pointer: fragment.to_string(),
+            resource,
+        };
+        self.compile(target, vec![scope])
+    }
+
+    fn resource_of(&self, base: &str) -> usize {
+        self.uris
+            .get(base)
+            .and_then(|&node| match &self.nodes[node] {
+                Node::Object(keywords) => keywords.resource,
+                Node::Bool(_) => None,
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// The scopes of a subschema at `path` relative to the current one.
+fn descend(scopes: &[Scope], path: &str) -> Vec<Scope> {
+    scopes
+        .iter()
+        .map(|scope| Scope {
+            base: scope.base.clone(),
+            pointer: format!("{}/{}", scope.pointer, path),
+            resource: scope.resource,
+        })
+        .collect()
+}
+
+/// Resolves a URI reference against `base` (RFC 3986, section 5.2).
+fn resolve(base: &str, reference: &str) -> String {
+    let has_scheme = |uri: &str| {
+        uri.find(':').is_some_and(|colon| {
+            let scheme = &uri[..colon];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+    };
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let base = without_fragment(base);
+    if reference.is_empty() || reference.starts_with('#') {
+        return format!("{}{}", base, reference);
+    }
+    let scheme_end = base.find(':').map_or(0, |colon| colon + 1);
+    if reference.starts_with("//") {
+        return format!("{}{}", &base[..scheme_end], reference);
+    }
+    // The end of the authority, where the path starts
+    let path_start = if base[scheme_end..].starts_with("//") {
+        base[scheme_end + 2..]
+            .find('/')
+            .map_or(base.len(), |slash| scheme_end + 2 + slash)
+    } else {
+        scheme_end
+    };
+    let path = if reference.starts_with('/') {
+        reference.to_string()
+    } else {
+        let base_path = &base[path_start..];
+        let base_path = base_path.split(&['?'][..]).next().unwrap_or("");
+        match base_path.rfind('/') {
+            Some(slash) => format!("{}{}", &base_path[..=slash], reference),
+            None if path_start > scheme_end => format!("/{}", reference),
+            None => reference.to_string(),
+        }
+    };
+    format!("{}{}", &base[..path_start], remove_dot_segments(&path))
+}
+
+fn remove_dot_segments(path: &str) -> String {
+    let (path, rest) = match path.find(['?', '#']) {
+        Some(end) => (&path[..end], &path[end..]),
+        None => (path, ""),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    let parts: Vec<&str> = path.split('/').collect();
+    for (i, segment) in parts.iter().enumerate() {
+        match *segment {
+            "." => {
+                if i == parts.len() - 1 {
+                    segments.push("");
+                }
+            }
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+                if i == parts.len() - 1 {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("{}{}", segments.join("/"), rest)
+}
+
+fn without_fragment(uri: &str) -> String {
+    uri.split('#').next().unwrap_or("").to_string()
+}
+
+/// Drops an empty fragment and percent-decodes the fragment, so that URIs
+/// can be compared.
+fn normalize(uri: &str) -> String {
+    let (base, fragment) = match uri.find('#') {
+        Some(hash) => (&uri[..hash], &uri[hash + 1..]),
+        None => return uri.to_string(),
+    };
+    if fragment.is_empty() {
+        return base.to_string();
+    }
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    format!("{}#{}", base, String::from_utf8_lossy(&decoded))
+}
+
+/// The annotations that `unevaluatedItems` and `unevaluatedProperties`
+/// depend on.
+#[derive(Default)]
+struct Evaluated {
+    properties: HashSet<String>,
+    // The number of leading items evaluated, unless all are
+    items: usize,
+    all_items: bool,
+    contains: HashSet<usize>,
+}
+
+impl Evaluated {
+    fn merge(&mut self, other: Evaluated) {
+        self.properties.extend(other.properties);
+        self.items = self.items.max(other.items);
+        self.all_items |= other.all_items;
+        self.contains.extend(other.contains);
+    }
+}
+
+struct Evaluator<'s> {
+    py: Python<'s>,
+    schema: &'s Schema,
+    // Whether to go on after an error, to find all of them
+    collect: bool,
+    // The resources entered so far, for `$dynamicRef`
+    scope: Vec<usize>,
+    instance_path: Vec<String>,
+    keyword_path: Vec<String>,
+    errors: Vec<Error>,
+    // Subschemas and values known to be valid, see `Decoding`
+    known: Option<&'s RefCell<HashSet<(usize, usize)>>>,
+}
+
+impl<'s> Evaluator<'s> {
+    fn error(&mut self, keyword: &str, instance: &PyAny, message: String) {
+        let mut schema_path = String::new();
+        for token in self
+            .keyword_path
+            .iter()
+            .map(String::as_str)
+            .chain(Some(keyword))
+        {
+            if !token.is_empty() {
+                schema_path.push('/');
+                schema_path.push_str(token);
+            }
+        }
+        let instance_path = self
+            .instance_path
+            .iter()
+            .map(|token| format!("/{}", token))
+            .collect();
+        self.errors.push(Error {
+            message,
+            instance: instance.to_object(self.py),
+            instance_path,
+            schema_path,
+        });
+    }
+
+    /// Applies `node` to `instance` at the `keyword` (a relative schema
+    /// path), adding to `evaluated` if it is valid.
+    fn apply(
+        &mut self,
+        node: usize,
+        instance: &PyAny,
+        keyword: String,
+        evaluated: &mut Evaluated,
+    ) -> PyResult<bool> {
+        if self.keyword_path.len() >= MAX_DEPTH {
+            return Err(PyRecursionError::py_err(
+                "maximum depth exceeded while validating",
+            ));
+        }
+        self.keyword_path.push(keyword);
+        let mut local = Evaluated::default();
+        let valid = self.evaluate(node, instance, &mut local);
+        self.keyword_path.pop();
+        if *valid.as_ref().unwrap_or(&false) {
+            evaluated.merge(local);
+        }
+        valid
+    }
+
+    /// Applies `node` to a member or element of the instance.
+    fn apply_child(
+        &mut self,
+        node: usize,
+        child: &PyAny,
+        token: String,
+        keyword: String,
+    ) -> PyResult<bool> {
+        if let Some(known) = self.known {
+            if known.borrow().contains(&(node, child.as_ptr() as usize)) {
+                return Ok(true);
+            }
+        }
+        self.instance_path.push(token);
+        let valid = self.apply(node, child, keyword, &mut Evaluated::default());
+        self.instance_path.pop();
+        valid
+    }
+
+    /// Like `apply`, but without reporting errors.
+    fn test(
+        &mut self,
+        node: usize,
+        instance: &PyAny,
+        keyword: String,
+        evaluated: &mut Evaluated,
+    ) -> PyResult<bool> {
+        let (errors, collect) = (self.errors.len(), self.collect);
+        self.collect = false;
+        let valid = self.apply(node, instance, keyword, evaluated);
+        self.collect = collect;
+        self.errors.truncate(errors);
+        valid
+    }
+
+    /// Notes that a keyword failed, and returns whether to stop.
+    fn fail(&self, valid: &mut bool) -> bool {
+        *valid = false;
+        !self.collect
+    }
+
+    fn evaluate(
+        &mut self,
+        node: usize,
+        instance: &PyAny,
+        evaluated: &mut Evaluated,
+    ) -> PyResult<bool> {
+        let schema = self.schema;
+        let keywords = match &schema.nodes[node] {
+            Node::Bool(true) => return Ok(true),
+            Node::Bool(false) => {
+                let message = format!("False schema does not allow {}", repr(instance));
+                self.error("", instance, message);
+                return Ok(false);
+            }
+            Node::Object(keywords) => keywords,
+        };
+        if let Some(resource) = keywords.resource {
+            self.scope.push(resource);
+        }
+        let valid = self.evaluate_keywords(keywords, instance, evaluated);
+        if keywords.resource.is_some() {
+            self.scope.pop();
+        }
+        valid
+    }
+
+    fn evaluate_keywords(
+        &mut self,
+        keywords: &'s Keywords,
+        instance: &PyAny,
+        evaluated: &mut Evaluated,
+    ) -> PyResult<bool> {
+        let py = self.py;
+        let kind = Kind::of(instance);
+        let mut valid = true;
+
+        if let Some(reference) = &keywords.reference {
+            if !self.apply(reference.node, instance, "$ref".to_string(), evaluated)?
+                && self.fail(&mut valid)
+            {
+                return Ok(false);
+            }
+        }
+        if let Some(reference) = &keywords.dynamic_reference {
+            let node = self.dynamic_target(reference);
+            if !self.apply(node, instance, "$dynamicRef".to_string(), evaluated)?
+                && self.fail(&mut valid)
+            {
+                return Ok(false);
+            }
+        }
+
+        if let Some(types) = &keywords.types {
+            let mut matched = false;
+            for &ty in types {
+                if matches_type(ty, instance, &kind)? {
+                    matched = true;
+                    break;
+                }
+            }
+            if !matched {
+                let names: Vec<_> = types.iter().map(|ty| format!("'{}'", ty.name())).collect();
+                let message = format!("{} is not of type {}", repr(instance), names.join(", "));
+                self.error("type", instance, message);
+                if self.fail(&mut valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(values) = &keywords.enumeration {
+            let values: &PyAny = values.extract(py)?;
+            let mut found = false;
+            for value in elements(values).unwrap() {
+                if json_equal(py, instance, value)? {
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                let message = format!("{} is not one of {}", repr(instance), repr(values));
+                self.error("enum", instance, message);
+                if self.fail(&mut valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(constant) = &keywords.constant {
+            let constant: &PyAny = constant.extract(py)?;
+            if !json_equal(py, instance, constant)? {
+                self.error(
+                    "const",
+                    instance,
+                    format!("{} was expected", repr(constant)),
+                );
+                if self.fail(&mut valid) {
+                    return Ok(false);
+                }
+            }
+        }
+
+        if kind == Kind::Number && !self.evaluate_number(keywords, instance, &mut valid)? {
+            return Ok(false);
+        }
+        if kind == Kind::String {
+            let string: &PyString = instance.downcast_ref()?;
+            // Python counts code points, like JSON Schema
+            let length = instance.len()?;
+            if let Some(max) = keywords.max_length {
+                if length > max {
+                    self.error(
+                        "maxLength",
+                        instance,
+                        format!("{} is too long", repr(instance)),
+                    );
+                    if self.fail(&mut valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+            if let Some(min) = keywords.min_length {
+                if length < min {
+                    self.error(
+                        "minLength",
+                        instance,
+                        format!("{} is too short", repr(instance)),
+                    );
+                    if self.fail(&mut valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+            if let Some((pattern, regex)) = &keywords.pattern {
+                if !regex.is_match(&string.to_string_lossy()) {
+                    let message =
+                        format!("{} does not match {}", repr(instance), quote(py, pattern));
+                    self.error("pattern", instance, message);
+                    if self.fail(&mut valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        if kind == Kind::Array && !self.evaluate_array(keywords, instance, evaluated, &mut valid)? {
+            return Ok(false);
+        }
+        if kind == Kind::Object
+            && !self.evaluate_object(keywords, instance, evaluated, &mut valid)?
+        {
+            return Ok(false);
+        }
+
+        for (i, &node) in keywords.all_of.iter().enumerate() {
+            if !self.apply(node, instance, format!("allOf/{}", i), evaluated)?
+                && self.fail(&mut valid)
+            {
+                return Ok(false);
+            }
+        }
+        if let Some(any_of) = &keywords.any_of {
+            let mut matched = false;
+            for (i, &node) in any_of.iter().enumerate() {
+                if self.test(node, instance, format!("anyOf/{}", i), evaluated)? {
+                    matched = true;
+                    // The other branches only matter for annotations
+                    if !self.schema.annotations {
+                        break;
+                    }
+                }
+            }
+            if !matched {
+                let message = format!(
+                    "{} is not valid under any of the given schemas",
+                    repr(instance)
+                );
+                self.error("anyOf", instance, message);
+                if self.fail(&mut valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(one_of) = &keywords.one_of {
+            let mut matched = Vec::new();
+            let mut local = Evaluated::default();
+            for (i, &node) in one_of.iter().enumerate() {
+                if self.test(node, instance, format!("oneOf/{}", i), &mut local)? {
+                    matched.push(i);
+                    if matched.len() > 1 {
+                        break;
+                    }
+                }
+            }
+            let message = match matched.len() {
+                1 => None,
+                0 => Some(format!(
+                    "{} is not valid under any of the given schemas",
+                    repr(instance)
+                )),
+                _ => Some(format!(
+                    "{} is valid under more than one of the given schemas",
+                    repr(instance)
+                )),
+            };
+            match message {
+                None => evaluated.merge(local),
+                Some(message) => {
+                    self.error("oneOf", instance, message);
+                    if self.fail(&mut valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        if let Some(node) = keywords.not {
+            if self.test(node, instance, "not".to_string(), &mut Evaluated::default())? {
+                let message = format!(
+                    "{} should not be valid under the given schema",
+                    repr(instance)
+                );
+                self.error("not", instance, message);
+                if self.fail(&mut valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(condition) = keywords.condition {
+            let branch = if self.test(condition, instance, "if".to_string(), evaluated)? {
+                keywords.then.map(|node| (node, "then"))
+            } else {
+                keywords.otherwise.map(|node| (node, "else"))
+            };
+            if let Some((node, keyword)) = branch {
+                if !self.apply(node, instance, keyword.to_string(), evaluated)?
+                    && self.fail(&mut valid)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+
+        // These depend on the annotations of all the other keywords
+        if kind == Kind::Array {
+            if let Some(node) = keywords.unevaluated_items {
+                if !evaluated.all_items {
+                    let items = elements(instance).unwrap();
+                    for (i, item) in items.into_iter().enumerate().skip(evaluated.items) {
+                        if evaluated.contains.contains(&i) {
+                            continue;
+                        }
+                        if !self.apply_child(
+                            node,
+                            item,
+                            i.to_string(),
+                            "unevaluatedItems".to_string(),
+                        )? && self.fail(&mut valid)
+                        {
+                            return Ok(false);
+                        }
+                    }
+                    evaluated.all_items = true;
+                }
+            }
+        }
+        if kind == Kind::Object {
+            if let Some(node) = keywords.unevaluated_properties {
+                let dict: &PyDict = instance.downcast_ref()?;
+                let mut unexpected = Vec::new();
+                for (key, value) in dict {
+                    let name = key_string(key)?;
+                    if evaluated.properties.contains(&name) {
+                        continue;
+                    }
+                    if let Node::Bool(false) = &self.schema.nodes[node] {
+                        unexpected.push(repr(key));
+                    } else if !self.apply_child(
+                        node,
+                        value,
+                        pointer::escape(&name),
+                        "unevaluatedProperties".to_string(),
+                    )? && self.fail(&mut valid)
+                    {
+                        return Ok(false);
+                    }
+                    evaluated.properties.insert(name);
+                }
+                if !unexpected.is_empty() {
+                    let message = format!(
+                        "Unevaluated properties are not allowed ({} {} unexpected)",
+                        unexpected.join(", "),
+                        if unexpected.len() == 1 { "was" } else { "were" }
+                    );
+                    self.error("unevaluatedProperties", instance, message);
+                    if self.fail(&mut valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        Ok(valid)
+    }
+
+    fn evaluate_number(
+        &mut self,
+        keywords: &Keywords,
+        instance: &PyAny,
+        valid: &mut bool,
+    ) -> PyResult<bool> {
+        let limits = [
+            ("maximum", &keywords.maximum, "greater than the maximum"),
+            (
+                "exclusiveMaximum",
+                &keywords.exclusive_maximum,
+                "greater than or equal to the maximum",
+            ),
+            ("minimum", &keywords.minimum, "less than the minimum"),
+            (
+                "exclusiveMinimum",
+                &keywords.exclusive_minimum,
+                "less than or equal to the minimum",
+            ),
+        ];
+        for &(keyword, limit, description) in &limits {
+            let limit: &PyAny = match limit {
+                Some(limit) => limit.extract(self.py)?,
+                None => continue,
+            };
+            let op = match keyword {
+                "maximum" => CompareOp::Gt,
+                "exclusiveMaximum" => CompareOp::Ge,
+                "minimum" => CompareOp::Lt,
+                _ => CompareOp::Le,
+            };
+            if instance.rich_compare(limit, op)?.is_true(self.py)? {
+                let message = format!("{} is {} of {}", repr(instance), description, repr(limit));
+                self.error(keyword, instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(divisor) = &keywords.multiple_of {
+            if !is_multiple(self.py, instance, divisor.extract(self.py)?)? {
+                let message = format!(
+                    "{} is not a multiple of {}",
+                    repr(instance),
+                    repr(divisor.extract(self.py)?)
+                );
+                self.error("multipleOf", instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn evaluate_array(
+        &mut self,
+        keywords: &Keywords,
+        instance: &PyAny,
+        evaluated: &mut Evaluated,
+        valid: &mut bool,
+    ) -> PyResult<bool> {
+        let py = self.py;
+        let items = elements(instance).unwrap();
+        if let Some(max) = keywords.max_items {
+            if items.len() > max {
+                self.error(
+                    "maxItems",
+                    instance,
+                    format!("{} is too long", repr(instance)),
+                );
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(min) = keywords.min_items {
+            if items.len() < min {
+                self.error(
+                    "minItems",
+                    instance,
+                    format!("{} is too short", repr(instance)),
+                );
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if keywords.unique_items {
+            let mut unique = true;
+            'outer: for (i, item) in items.iter().enumerate() {
+                for other in &items[i + 1..] {
+                    if json_equal(py, item, other)? {
+                        unique = false;
+                        break 'outer;
+                    }
+                }
+            }
+            if !unique {
+                let message = format!("{} has non-unique elements", repr(instance));
+                self.error("uniqueItems", instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        for (i, (&node, item)) in keywords.prefix_items.iter().zip(&items).enumerate() {
+            if !self.apply_child(node, item, i.to_string(), format!("prefixItems/{}", i))?
+                && self.fail(valid)
+            {
+                return Ok(false);
+            }
+        }
+        evaluated.items = evaluated
+            .items
+            .max(keywords.prefix_items.len().min(items.len()));
+        if let Some(node) = keywords.items {
+            let start = keywords.prefix_items.len();
+            if let (Node::Bool(false), true) = (&self.schema.nodes[node], items.len() > start) {
+                let message = format!(
+                    "Expected at most {} items but found {} extra",
+                    start,
+                    items.len() - start
+                );
+                self.error("items", instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            } else {
+                for (i, item) in items.iter().enumerate().skip(start) {
+                    if !self.apply_child(node, item, i.to_string(), "items".to_string())?
+                        && self.fail(valid)
+                    {
+                        return Ok(false);
+                    }
+                }
+            }
+            evaluated.all_items = true;
+        }
+        if let Some(node) = keywords.contains {
+            let mut matched = 0;
+            for (i, item) in items.iter().enumerate() {
+                self.instance_path.push(i.to_string());
+                let found = self.test(
+                    node,
+                    item,
+                    "contains".to_string(),
+                    &mut Evaluated::default(),
+                )?;
+                self.instance_path.pop();
+                if found {
+                    matched += 1;
+                    evaluated.contains.insert(i);
+                }
+            }
+            let min = keywords.min_contains.unwrap_or(1);
+            let message = if matched < min {
+                Some(if keywords.min_contains.is_none() {
+                    (
+                        "contains",
+                        format!(
+                            "{} does not contain items matching the given schema",
+                            repr(instance)
+                        ),
+                    )
+                } else {
+                    (
+                        "minContains",
+                        format!(
+                            "Too few items match the given schema (expected at least {} but only {} matched)",
+                            min, matched
+                        ),
+                    )
+                })
+            } else {
+                match keywords.max_contains {
+                    Some(max) if matched > max => Some((
+                        "maxContains",
+                        format!(
+                            "Too many items match the given schema (expected at most {})",
+                            max
+                        ),
+                    )),
+                    _ => None,
+                }
+            };
+            if let Some((keyword, message)) = message {
+                self.error(keyword, instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn evaluate_object(
+        &mut self,
+        keywords: &Keywords,
+        instance: &PyAny,
+        evaluated: &mut Evaluated,
+        valid: &mut bool,
+    ) -> PyResult<bool> {
+        let dict: &PyDict = instance.downcast_ref()?;
+        if let Some(max) = keywords.max_properties {
+            if dict.len() > max {
+                self.error(
+                    "maxProperties",
+                    instance,
+                    format!("{} has too many properties", repr(instance)),
+                );
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(min) = keywords.min_properties {
+            if dict.len() < min {
+                let message = format!("{} does not have enough properties", repr(instance));
+                self.error("minProperties", instance, message);
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        for name in &keywords.required {
+            if dict.get_item(name.as_str()).is_none() {
+                self.error(
+                    "required",
+                    instance,
+                    format!("{} is a required property", quote(self.py, name)),
+                );
+                if self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        for (name, required) in &keywords.dependent_required {
+            if dict.get_item(name.as_str()).is_none() {
+                continue;
+            }
+            for dependency in required {
+                if dict.get_item(dependency.as_str()).is_none() {
+                    let message = format!(
+                        "{} is a dependency of {}",
+                        quote(self.py, dependency),
+                        quote(self.py, name)
+                    );
+                    let keyword = format!("dependentRequired/{}", pointer::escape(name));
+                    self.error(&keyword, instance, message);
+                    if self.fail(valid) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        for (name, node) in &keywords.dependent_schemas {
+            if dict.get_item(name.as_str()).is_some() {
+                let keyword = format!("dependentSchemas/{}", pointer::escape(name));
+                if !self.apply(*node, instance, keyword, evaluated)? && self.fail(valid) {
+                    return Ok(false);
+                }
+            }
+        }
+        if let Some(node) = keywords.property_names {
+            for key in dict.keys() {
+                if !self.apply(
+                    node,
+                    key,
+                    "propertyNames".to_string(),
+                    &mut Evaluated::default(),
+                )? && self.fail(valid)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let annotations = self.schema.annotations;
+        for (name, node) in &keywords.properties {
+            if let Some(value) = dict.get_item(name.as_str()) {
+                let keyword = format!("properties/{}", pointer::escape(name));
+                if !self.apply_child(*node, value, pointer::escape(name), keyword)?
+                    && self.fail(valid)
+                {
+                    return Ok(false);
+                }
+                if annotations {
+                    evaluated.properties.insert(name.clone());
+                }
+            }
+        }
+        if keywords.pattern_properties.is_empty() && keywords.additional_properties.is_none() {
+            return Ok(true);
+        }
+        let mut unexpected = Vec::new();
+        for (key, value) in dict {
+            let name = key_string(key)?;
+            let mut matched = keywords
+                .properties
+                .iter()
+                .any(|(property, _)| *property == name);
+            for (pattern, regex, node) in &keywords.pattern_properties {
+                if regex.is_match(&name) {
+                    matched = true;
+                    let keyword = format!("patternProperties/{}", pointer::escape(pattern));
+                    if !self.apply_child(*node, value, pointer::escape(&name), keyword)?
+                        && self.fail(valid)
+                    {
+                        return Ok(false);
+                    }
+                }
+            }
+            if !matched {
+                if let Some(node) = keywords.additional_properties {
+                    if let Node::Bool(false) = &self.schema.nodes[node] {
+                        unexpected.push(repr(key));
+                    } else if !self.apply_child(
+                        node,
+                        value,
+                        pointer::escape(&name),
+                        "additionalProperties".to_string(),
+                    )? && self.fail(valid)
+                    {
+                        return Ok(false);
+                    }
+                } else {
+                    continue;
+                }
+            }
+            if annotations {
+                evaluated.properties.insert(name);
+            }
+        }
+        if !unexpected.is_empty() {
+            let message = format!(
+                "Additional properties are not allowed ({} {} unexpected)",
+                unexpected.join(", "),
+                if unexpected.len() == 1 { "was" } else { "were" }
+            );
+            self.error("additionalProperties", instance, message);
+            if self.fail(valid) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Finds where a `$dynamicRef` goes: if it first lands on a
+    /// `$dynamicAnchor` of the same name, the outermost resource in scope
+    /// with such an anchor wins.
+    fn dynamic_target(&self, reference: &Reference) -> usize {
+        let anchor = match reference.uri.rfind('#') {
+            Some(hash) if !reference.uri[hash + 1..].starts_with('/') => &reference.uri[hash + 1..],
+            _ => return reference.node,
+        };
+        match &self.schema.nodes[reference.node] {
+            Node::Object(keywords) if keywords.dynamic_anchor.as_deref() == Some(anchor) => {}
+            _ => return reference.node,
+        }
+        for &resource in &self.scope {
+            if let Some(&node) = self.schema.resources[resource].dynamic_anchors.get(anchor) {
+                return node;
+            }
+        }
+        reference.node
+    }
+}
+
+fn matches_type(ty: Type, instance: &PyAny, kind: &Kind) -> PyResult<bool> {
+    Ok(match ty {
+        Type::Null => *kind == Kind::Null,
+        Type::Boolean => *kind == Kind::Bool,
+        Type::Number => *kind == Kind::Number,
+        // Floats with a zero fractional part count
+        Type::Integer => {
+            *kind == Kind::Number
+                && (unsafe { ffi::PyLong_Check(instance.as_ptr()) } != 0
+                    || instance.downcast_ref::<PyFloat>()?.value().fract() == 0.0)
+        }
+        Type::String => *kind == Kind::String,
+        Type::Array => *kind == Kind::Array,
+        Type::Object => *kind == Kind::Object,
+    })
+}
+
+fn is_multiple(py: Python, instance: &PyAny, divisor: &PyAny) -> PyResult<bool> {
+    let is_int = |obj: &PyAny| unsafe { ffi::PyLong_Check(obj.as_ptr()) } != 0;
+    if is_int(instance) && is_int(divisor) {
+        let remainder = instance.call_method1("__mod__", (divisor,))?;
+        return remainder.rich_compare(0, CompareOp::Eq)?.is_true(py);
+    }
+    let (value, divisor): (f64, f64) = match (instance.extract(), divisor.extract()) {
+        (Ok(value), Ok(divisor)) => (value, divisor),
+        // Too large for a float
+        _ => return Ok(false),
+    };
+    let quotient = value / divisor;
+    if !quotient.is_finite() {
+        return Ok(false);
+    }
+    Ok((quotient - quotient.round()).abs() <= 1e-9 * quotient.abs().max(1.0))
+}
+
+fn key_string(key: &PyAny) -> PyResult<String> {
+    match key.downcast_ref::<PyString>() {
+        Ok(key) => Ok(key.to_string()?.into_owned()),
+        Err(_) => Ok(key.str()?.to_string()?.into_owned()),
+    }
+}
+
+/// Quotes a string like Python does.
+fn quote(py: Python, string: &str) -> String {
+    repr(PyString::new(py, string).as_ref())
+}
+
+fn repr(obj: &PyAny) -> String {
+    match obj.repr() {
+        Ok(repr) => repr.to_string_lossy().into_owned(),
+        Err(_) => String::from("<unprintable>"),
+    }
+}
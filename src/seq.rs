@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+
+use pyo3::exceptions::{OSError, ValueError as PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyAny;
+use serde::de::DeserializeSeed;
+
+use crate::{DumpsOptions, LoadsOptions};
+
+/// The record separator that starts each JSON text in a sequence.
+pub const RS: u8 = 0x1E;
+
+/// Parses an RFC 7464 JSON text sequence.
+///
+/// Following the spec's recovery rules, a record that cannot be parsed is
+/// skipped with a `RuntimeWarning`, and parsing resumes at the next `RS`.
+/// This also skips anything before the first `RS`. A number, `true`, `false`
+/// or `null` that is not followed by whitespace may have been cut off, which
+/// is warned about as well, but the value is kept.
+pub fn parse(py: Python, options: &LoadsOptions, data: &[u8]) -> PyResult<Vec<PyObject>> {
+    let mut values = Vec::new();
+    let mut offset: usize = 0;
+    for (i, record) in data.split(|&b| b == RS).enumerate() {
+        // Where the record's `RS` is
+        let start = offset.saturating_sub(1);
+        offset += record.len() + 1;
+        let text = trim_start(record);
+        if text.is_empty() {
+            continue;
+        }
+        if i == 0 {
+            warn(py, "skipping data before the first record separator")?;
+            continue;
+        }
+        let mut deserializer = serde_json::Deserializer::from_slice(record);
+        let result = options
+            .seed(py, None)
+            .deserialize(&mut deserializer)
+            .and_then(|value| deserializer.end().map(|()| value));
+        match result {
+            Ok(value) => {
+                if may_be_truncated(text) {
+                    warn(
+                        py,
+                        &format!("record at byte offset {} may have been truncated", start),
+                    )?;
+                }
+                values.push(value);
+            }
+            Err(e) if e.is_syntax() || e.is_eof() => {
+                warn(
+                    py,
+                    &format!("skipping invalid record at byte offset {}: {}", start, e),
+                )?;
+            }
+            Err(e) => return Err(PyValueError::py_err(e.to_string())),
+        }
+    }
+    Ok(values)
+}
+
+/// Writes each object of `iterable` as a record of a JSON text sequence:
+/// `RS`, the JSON text and a line feed.
+pub fn write<W: Write>(
+    py: Python,
+    options: &DumpsOptions,
+    iterable: &PyAny,
+    mut writer: W,
+) -> PyResult<W> {
+    let io_error = |err: io::Error| OSError::py_err(err.to_string());
+    for obj in iterable.iter()? {
+        writer.write_all(&[RS]).map_err(io_error)?;
+        writer = options.write(py, obj?, writer)?;
+        writer.write_all(b"\n").map_err(io_error)?;
+    }
+    Ok(writer)
+}
+
+fn trim_start(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b" \t\r\n".contains(b))
+        .unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// Tells whether `text` is a top-level number or literal without the
+/// whitespace that should follow it.
+fn may_be_truncated(text: &[u8]) -> bool {
+    let unterminated = match text.last() {
+        Some(b) => !b" \t\r\n".contains(b),
+        None => false,
+    };
+    unterminated && matches!(text[0], b'-' | b'0'..=b'9' | b't' | b'f' | b'n')
+}
+
+fn warn(py: Python, message: &str) -> PyResult<()> {
+    let category = py.import("builtins")?.get("RuntimeWarning")?;
+    PyErr::warn(py, category, message, 1)
+}
@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyDict, PyList, PyString};
+
+use crate::surrogates::{self, Lone};
+
+/// A member of an object: its name in the output with the lone surrogates
+/// taken out of it, its key in the dict and its value.
+pub type Member<'a> = (Cow<'a, str>, Lone, &'a PyAny, &'a PyAny);
+
+/// The order `dumps` writes object members in.
+pub enum SortKeys {
+    /// The order of the dict
+    Unsorted,
+    /// By code point, like `sorted` on the keys
+    Lexical,
+    /// With runs of digits compared by their value, so `'a2'` comes before
+    /// `'a10'`
+    Natural,
+    /// By the result of calling a function on each name
+    Key(PyObject),
+}
+
+impl SortKeys {
+    pub fn from_py(py: Python, sort_keys: Option<PyObject>) -> PyResult<SortKeys> {
+        let sort_keys = match sort_keys {
+            Some(sort_keys) if !sort_keys.is_none() => sort_keys,
+            _ => return Ok(SortKeys::Unsorted),
+        };
+        let obj: &PyAny = sort_keys.extract(py)?;
+        if let Ok(name) = obj.downcast_ref::<PyString>() {
+            return match name.to_string()?.as_ref() {
+                "natural" => Ok(SortKeys::Natural),
+                name => Err(PyValueError::py_err(format!(
+                    "sort_keys must be a bool, 'natural' or a callable, got: {:?}",
+                    name
+                ))),
+            };
+        }
+        if obj.is_callable() {
+            Ok(SortKeys::Key(sort_keys))
+        } else if obj.is_true()? {
+            Ok(SortKeys::Lexical)
+        } else {
+            Ok(SortKeys::Unsorted)
+        }
+    }
+
+    /// Puts the members of one object in order. Lone surrogates sort by
+    /// their code points, like in Python.
+    pub fn sort(&self, py: Python, members: &mut Vec<Member>) -> PyResult<()> {
+        match self {
+            SortKeys::Unsorted => {}
+            SortKeys::Lexical => members
+                .sort_by(|(a, a_lone, _, _), (b, b_lone, _, _)| lexical(a, a_lone, b, b_lone)),
+            SortKeys::Natural => members
+                .sort_by(|(a, a_lone, _, _), (b, b_lone, _, _)| natural(a, a_lone, b, b_lone)),
+            SortKeys::Key(function) => {
+                let mut ranks = Vec::with_capacity(members.len());
+                for (name, _, key, _) in members.iter() {
+                    // Passes `str` keys as they are, lone surrogates included
+                    let name = match key.downcast_ref::<PyString>() {
+                        Ok(key) => key,
+                        Err(_) => PyString::new(py, name),
+                    };
+                    ranks.push(function.call1(py, (name,))?);
+                }
+                // Python's sort copes with keys that are not totally ordered,
+                // and compares them with `<` just like `sorted` would
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("key", PyList::new(py, &ranks).getattr("__getitem__")?)?;
+                let order: Vec<usize> = py
+                    .import("builtins")?
+                    .call(
+                        "sorted",
+                        ((0..members.len()).collect::<Vec<_>>(),),
+                        Some(kwargs),
+                    )?
+                    .extract()?;
+                let mut unsorted: Vec<_> = members.drain(..).map(Some).collect();
+                members.extend(order.into_iter().map(|i| unsorted[i].take().unwrap()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lexical(a: &str, a_lone: &[(usize, u16)], b: &str, b_lone: &[(usize, u16)]) -> Ordering {
+    if a_lone.is_empty() && b_lone.is_empty() {
+        a.cmp(b)
+    } else {
+        surrogates::code_points(a, a_lone).cmp(surrogates::code_points(b, b_lone))
+    }
+}
+
+fn is_digit(c: u32) -> bool {
+    (u32::from(b'0')..=u32::from(b'9')).contains(&c)
+}
+
+/// Takes a run of ASCII digits, without leading zeros.
+fn digits(chars: &mut Peekable<impl Iterator<Item = u32>>) -> Vec<u32> {
+    let mut digits = Vec::new();
+    while let Some(&c) = chars.peek() {
+        if !is_digit(c) {
+            break;
+        }
+        if !(digits.is_empty() && c == u32::from(b'0')) {
+            digits.push(c);
+        }
+        chars.next();
+    }
+    digits
+}
+
+/// Compares runs of ASCII digits as numbers and everything else by code
+/// point. Names that only differ in leading zeros fall back to `lexical`.
+fn natural(a: &str, a_lone: &[(usize, u16)], b: &str, b_lone: &[(usize, u16)]) -> Ordering {
+    let mut a_chars = surrogates::code_points(a, a_lone).peekable();
+    let mut b_chars = surrogates::code_points(b, b_lone).peekable();
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return lexical(a, a_lone, b, b_lone),
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(&x), Some(&y)) if is_digit(x) && is_digit(y) => {
+                let (x, y) = (digits(&mut a_chars), digits(&mut b_chars));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(&y))
+            }
+            (Some(&x), Some(&y)) => {
+                a_chars.next();
+                b_chars.next();
+                x.cmp(&y)
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::char;
+use std::iter;
+use std::mem;
+
+use pyo3::exceptions::ValueError as PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyString};
+
+/// What to do with lone UTF-16 surrogates such as `'\ud800'`.
+///
+/// Python strings can hold them and the `json` module round-trips them,
+/// but they are not valid in UTF-8 and therefore not in a Rust `str`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SurrogatePolicy {
+    /// Fail on lone surrogates
+    Strict,
+    /// Keep lone surrogates, writing them as `\udxxx` escapes
+    Escape,
+    /// Replace lone surrogates with U+FFFD REPLACEMENT CHARACTER
+    Replace,
+}
+
+impl SurrogatePolicy {
+    pub fn from_py(py: Python, policy: Option<PyObject>) -> PyResult<SurrogatePolicy> {
+        let policy = match policy {
+            Some(policy) if !policy.is_none() => policy,
+            _ => return Ok(SurrogatePolicy::Strict),
+        };
+        let name: String = policy.extract(py)?;
+        match name.as_str() {
+            "strict" => Ok(SurrogatePolicy::Strict),
+            "escape" => Ok(SurrogatePolicy::Escape),
+            "replace" => Ok(SurrogatePolicy::Replace),
+            _ => Err(PyValueError::py_err(format!(
+                "surrogates must be one of 'strict', 'escape' or 'replace', got: {:?}",
+                name
+            ))),
+        }
+    }
+}
+
+/// Lone surrogates taken out of a string, each with the byte offset in the
+/// rest of the string where it belongs.
+///
+/// This keeps them apart from the text, as no code point of a Rust `str` can
+/// stand for them without being mistaken for itself.
+pub type Lone = Vec<(usize, u16)>;
+
+/// Converts a Python str to a Rust string, handling lone surrogates
+/// according to `policy`. With the `Escape` policy they are taken out of
+/// the string and returned with it.
+pub fn to_string(s: &PyString, policy: SurrogatePolicy) -> PyResult<(Cow<'_, str>, Lone)> {
+    let err = match s.to_string() {
+        Ok(string) => return Ok((string, Lone::new())),
+        Err(err) => err,
+    };
+    if policy == SurrogatePolicy::Strict {
+        return Err(err);
+    }
+
+    let encoded = s.call_method1("encode", ("utf-16-le", "surrogatepass"))?;
+    let encoded: &PyBytes = encoded.downcast_ref()?;
+    let units = encoded
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+
+    let mut string = String::with_capacity(encoded.as_bytes().len() / 2);
+    let mut lone = Lone::new();
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => string.push(c),
+            Err(e) if policy == SurrogatePolicy::Escape => {
+                lone.push((string.len(), e.unpaired_surrogate()))
+            }
+            Err(_) => string.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
+    Ok((Cow::Owned(string), lone))
+}
+
+/// The code points of a string with its lone surrogates put back, for
+/// comparing strings the way Python does.
+pub fn code_points<'s>(s: &'s str, lone: &'s [(usize, u16)]) -> impl Iterator<Item = u32> + 's {
+    let mut lone = lone.iter().peekable();
+    let mut chars = s.char_indices().peekable();
+    iter::from_fn(move || match (lone.peek(), chars.peek()) {
+        (Some(&&(pos, surrogate)), Some(&(i, _))) if pos <= i => {
+            lone.next();
+            Some(u32::from(surrogate))
+        }
+        (Some(&&(_, surrogate)), None) => {
+            lone.next();
+            Some(u32::from(surrogate))
+        }
+        (_, Some(_)) => chars.next().map(|(_, c)| c as u32),
+        (None, None) => None,
+    })
+}
+
+/// Returns the bytes of a Python str. With a policy other than `Strict`, lone
+/// surrogates are encoded like any other code point (as Python's
+/// `surrogatepass` error handler does) and left for `prepare` to handle.
+pub fn as_bytes(s: &PyString, policy: SurrogatePolicy) -> PyResult<Cow<'_, [u8]>> {
+    match s.as_bytes() {
+        Ok(bytes) => Ok(Cow::Borrowed(bytes)),
+        Err(err) => {
+            if policy == SurrogatePolicy::Strict {
+                return Err(err);
+            }
+            let encoded = s.call_method1("encode", ("utf-8", "surrogatepass"))?;
+            let encoded: &PyBytes = encoded.downcast_ref()?;
+            Ok(Cow::Owned(encoded.as_bytes().to_vec()))
+        }
+    }
+}
+
+/// Puts lone surrogates back into a string, encoding them the way UTF-8
+/// would encode any other BMP code point.
+pub fn wtf8(s: &str, lone: &[(usize, u16)]) -> Vec<u8> {
+    let mut wtf8 = Vec::with_capacity(s.len() + 3 * lone.len());
+    let mut copied = 0;
+    for &(pos, surrogate) in lone {
+        wtf8.extend_from_slice(&s.as_bytes()[copied..pos]);
+        wtf8.extend_from_slice(&encode_surrogate(surrogate));
+        copied = pos;
+    }
+    wtf8.extend_from_slice(&s.as_bytes()[copied..]);
+    wtf8
+}
+
+/// Creates a Python str from the output of `wtf8`.
+pub fn from_wtf8(py: Python, wtf8: &[u8]) -> PyObject {
+    unsafe {
+        PyObject::from_owned_ptr_or_panic(
+            py,
+            pyo3::ffi::PyUnicode_DecodeUTF8(
+                wtf8.as_ptr() as *const _,
+                wtf8.len() as pyo3::ffi::Py_ssize_t,
+                "surrogatepass\0".as_ptr() as *const _,
+            ),
+        )
+    }
+}
+
+/// Creates a Python str, putting lone surrogates back into it.
+pub fn to_py(py: Python, s: &str, lone: &[(usize, u16)]) -> PyObject {
+    if lone.is_empty() {
+        return s.to_object(py);
+    }
+    from_wtf8(py, &wtf8(s, lone))
+}
+
+/// Encodes a surrogate the way UTF-8 would encode any other BMP code point.
+pub fn encode_surrogate(surrogate: u16) -> [u8; 3] {
+    [
+        0xE0 | (surrogate >> 12) as u8,
+        0x80 | ((surrogate >> 6) & 0x3F) as u8,
+        0x80 | (surrogate & 0x3F) as u8,
+    ]
+}
+
+fn hex4(bytes: &[u8]) -> Option<u16> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let s = std::str::from_utf8(&bytes[..4]).ok()?;
+    u16::from_str_radix(s, 16).ok()
+}
+
+fn is_high(n: u16) -> bool {
+    (0xD800..0xDC00).contains(&n)
+}
+
+fn is_low(n: u16) -> bool {
+    (0xDC00..0xE000).contains(&n)
+}
+
+/// Tells whether `json` holds a surrogate encoded like any other code point
+/// at `i`, and returns it.
+fn raw_surrogate(json: &[u8], i: usize) -> Option<u16> {
+    match json.get(i..i + 3) {
+        Some(&[0xED, b1, b2]) if (0xA0..=0xBF).contains(&b1) && (0x80..=0xBF).contains(&b2) => {
+            Some(0xD000 | (u16::from(b1 & 0x3F) << 6) | u16::from(b2 & 0x3F))
+        }
+        _ => None,
+    }
+}
+
+/// JSON text rewritten by `prepare`.
+pub struct Prepared {
+    pub text: Vec<u8>,
+    /// The lone surrogates taken out of its strings, if any were kept
+    pub strings: Option<Strings>,
+}
+
+/// The lone surrogates `prepare` took out of the strings of a JSON text,
+/// for putting them back while it is decoded.
+///
+/// Strings are told apart by their place in the text, so every string of
+/// the text has to be passed through `next` in order, member names and
+/// strings in skipped values included.
+pub struct Strings {
+    // The lone surrogates of each string that has any, by its index
+    lone: Vec<(usize, Lone)>,
+    // The index of the next string and of its entry in `lone`
+    next: Cell<usize>,
+    entry: Cell<usize>,
+}
+
+impl Strings {
+    /// Moves on to the next string and returns its lone surrogates, if it
+    /// has any.
+    pub fn next(&self) -> Option<&Lone> {
+        let index = self.next.get();
+        self.next.set(index + 1);
+        match self.lone.get(self.entry.get()) {
+            Some((found, lone)) if *found == index => {
+                self.entry.set(self.entry.get() + 1);
+                Some(lone)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rewrites lone surrogates in JSON text, which serde_json rejects.
+///
+/// Lone surrogates written as `\uXXXX` escapes are handled according to
+/// `policy`, and those encoded like any other code point (as returned by
+/// `as_bytes`) according to `raw`. With the `Escape` policy they are taken
+/// out of their strings and returned as `Strings`, and with `Replace` they
+/// become U+FFFD. With `Strict` they are left for serde_json to report, as
+/// are any outside of strings.
+///
+/// Returns `None` if the text can be parsed as is.
+pub fn prepare(json: &[u8], policy: SurrogatePolicy, raw: SurrogatePolicy) -> Option<Prepared> {
+    if policy == SurrogatePolicy::Strict && raw == SurrogatePolicy::Strict {
+        return None;
+    }
+
+    let mut out: Option<Vec<u8>> = None;
+    let mut copied = 0;
+    let mut strings = Vec::new();
+    // The number of strings so far, and while in a string, the length it
+    // has decoded to and its lone surrogates
+    let mut index = 0;
+    let mut in_string = false;
+    let mut decoded = 0;
+    let mut lone = Lone::new();
+    let mut i = 0;
+    while i < json.len() {
+        if !in_string {
+            in_string = json[i] == b'"';
+            decoded = 0;
+            i += 1;
+            continue;
+        }
+        let (surrogate, len, policy) = match json[i] {
+            b'"' => {
+                if !lone.is_empty() {
+                    strings.push((index, mem::take(&mut lone)));
+                }
+                index += 1;
+                in_string = false;
+                i += 1;
+                continue;
+            }
+            b'\\' if json.get(i + 1) == Some(&b'u') => match hex4(&json[i + 2..]) {
+                Some(n) if is_high(n) => {
+                    let low = match json.get(i + 6..) {
+                        Some(rest) if rest.starts_with(b"\\u") => hex4(&rest[2..]),
+                        _ => None,
+                    };
+                    match low {
+                        Some(low) if is_low(low) => {
+                            decoded += 4;
+                            i += 12;
+                            continue;
+                        }
+                        _ => (n, 6, policy),
+                    }
+                }
+                Some(n) if is_low(n) => (n, 6, policy),
+                Some(n) => {
+                    decoded += char::from_u32(u32::from(n)).map_or(0, char::len_utf8);
+                    i += 6;
+                    continue;
+                }
+                // Left for serde_json to report
+                None => {
+                    i += 2;
+                    continue;
+                }
+            },
+            // Skips the escaped character so that `\\` is not mistaken for
+            // the start of another escape
+            b'\\' => {
+                decoded += 1;
+                i += 2;
+                continue;
+            }
+            _ => match raw_surrogate(json, i) {
+                Some(surrogate) => (surrogate, 3, raw),
+                None => {
+                    // The bytes of other characters decode to themselves
+                    decoded += 1;
+                    i += 1;
+                    continue;
+                }
+            },
+        };
+
+        if policy == SurrogatePolicy::Strict {
+            i += len;
+            continue;
+        }
+        let out = out.get_or_insert_with(|| Vec::with_capacity(json.len()));
+        out.extend_from_slice(&json[copied..i]);
+        if policy == SurrogatePolicy::Escape {
+            lone.push((decoded, surrogate));
+        } else {
+            let mut buf = [0; 4];
+            out.extend_from_slice(char::REPLACEMENT_CHARACTER.encode_utf8(&mut buf).as_bytes());
+            decoded += char::REPLACEMENT_CHARACTER.len_utf8();
+        }
+        i += len;
+        copied = i;
+    }
+
+    out.map(|mut text| {
+        text.extend_from_slice(&json[copied..]);
+        Prepared {
+            text,
+            strings: if strings.is_empty() {
+                None
+            } else {
+                Some(Strings {
+                    lone: strings,
+                    next: Cell::new(0),
+                    entry: Cell::new(0),
+                })
+            },
+        }
+    })
+}
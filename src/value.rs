@@ -0,0 +1,130 @@
+//! How Python objects map to JSON values: dicts are objects, lists and
+//! tuples are arrays, like in `dumps`.
+
+use pyo3::buffer::PyBuffer;
+use pyo3::class::basic::CompareOp;
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyBool, PyDict, PyFloat, PyList, PyString, PyTuple};
+use pyo3::{ffi, AsPyPointer};
+
+use crate::LoadsOptions;
+
+/// Compares like JSON values: unlike in Python, `true` is not `1`.
+pub fn json_equal(py: Python, left: &PyAny, right: &PyAny) -> PyResult<bool> {
+    let (left_kind, right_kind) = (Kind::of(left), Kind::of(right));
+    if left_kind != right_kind {
+        return Ok(false);
+    }
+    match left_kind {
+        Kind::Array => {
+            let (left, right) = (elements(left).unwrap(), elements(right).unwrap());
+            if left.len() != right.len() {
+                return Ok(false);
+            }
+            for (left, right) in left.into_iter().zip(right) {
+                if !json_equal(py, left, right)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        Kind::Object => {
+            let (left, right): (&PyDict, &PyDict) = (left.downcast_ref()?, right.downcast_ref()?);
+            if left.len() != right.len() {
+                return Ok(false);
+            }
+            for (key, value) in left {
+                match right.get_item(key) {
+                    Some(other) if json_equal(py, value, other)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        _ => left.rich_compare(right, CompareOp::Eq)?.is_true(py),
+    }
+}
+
+#[derive(PartialEq)]
+pub enum Kind {
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+    Other,
+}
+
+impl Kind {
+    pub fn of(obj: &PyAny) -> Kind {
+        if obj.is_none() {
+            Kind::Null
+        } else if obj.downcast_ref::<PyBool>().is_ok() {
+            Kind::Bool
+        } else if obj.downcast_ref::<PyFloat>().is_ok()
+            || unsafe { ffi::PyLong_Check(obj.as_ptr()) } != 0
+        {
+            Kind::Number
+        } else if is_string(obj) {
+            Kind::String
+        } else if is_array(obj) {
+            Kind::Array
+        } else if is_object(obj) {
+            Kind::Object
+        } else {
+            Kind::Other
+        }
+    }
+}
+
+pub fn is_string(obj: &PyAny) -> bool {
+    obj.downcast_ref::<PyString>().is_ok()
+}
+
+pub fn is_array(obj: &PyAny) -> bool {
+    obj.downcast_ref::<PyList>().is_ok() || obj.downcast_ref::<PyTuple>().is_ok()
+}
+
+pub fn is_object(obj: &PyAny) -> bool {
+    obj.downcast_ref::<PyDict>().is_ok()
+}
+
+pub fn elements(obj: &PyAny) -> Option<Vec<&PyAny>> {
+    if let Ok(list) = obj.downcast_ref::<PyList>() {
+        Some(list.iter().collect())
+    } else if let Ok(tuple) = obj.downcast_ref::<PyTuple>() {
+        Some(tuple.iter().collect())
+    } else {
+        None
+    }
+}
+
+/// Copies the objects and arrays in `obj`, turning tuples into lists. Other
+/// values are shared.
+pub fn deep_copy(py: Python, obj: &PyAny) -> PyResult<PyObject> {
+    if let Ok(dict) = obj.downcast_ref::<PyDict>() {
+        let copy = PyDict::new(py);
+        for (key, value) in dict {
+            copy.set_item(key, deep_copy(py, value)?)?;
+        }
+        return Ok(copy.to_object(py));
+    }
+    if let Some(elements) = elements(obj) {
+        let copy = elements
+            .into_iter()
+            .map(|element| deep_copy(py, element))
+            .collect::<PyResult<Vec<_>>>()?;
+        return Ok(copy.to_object(py));
+    }
+    Ok(obj.to_object(py))
+}
+
+/// Parses `obj` if it is JSON text, a `str` or bytes-like object.
+pub fn parse_text(py: Python, obj: &PyAny) -> PyResult<Option<PyObject>> {
+    if !is_string(obj) && PyBuffer::get(py, obj).is_err() {
+        return Ok(None);
+    }
+    let options = LoadsOptions::from_py(py, None, None, None, None, None, None)?;
+    options.loads(py, obj).map(Some)
+}
@@ -0,0 +1,222 @@
+use std::io;
+use std::ptr;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{TypeError as PyTypeError, ValueError as PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyAny, PyByteArray, PyBytes, PyString};
+use pyo3::AsPyPointer;
+
+use crate::error::HyperJsonError;
+
+/// Writes into a Python object supplied by the caller, either appending to
+/// a `bytearray` (which grows as needed) or filling a writable buffer from
+/// its start.
+///
+/// Errors raised by Python while writing are kept, so that they can be
+/// reported instead of the `io::Error` serde_json sees.
+pub struct BufferWriter<'p> {
+    target: Target<'p>,
+    start: usize,
+    len: usize,
+    error: Option<PyErr>,
+}
+
+enum Target<'p> {
+    ByteArray(&'p PyByteArray),
+    Buffer(PyBuffer),
+}
+
+impl<'p> BufferWriter<'p> {
+    pub fn new(py: Python<'p>, obj: &'p PyAny) -> PyResult<BufferWriter<'p>> {
+        if let Ok(array) = <PyByteArray as PyTryFrom>::try_from(obj) {
+            let len = array.len();
+            return Ok(BufferWriter {
+                target: Target::ByteArray(array),
+                start: len,
+                len,
+                error: None,
+            });
+        }
+        let buffer = PyBuffer::get(py, obj)?;
+        if buffer.readonly() || !buffer.is_c_contiguous() {
+            return Err(PyTypeError::py_err(
+                "buffer must be a bytearray or a writable, contiguous buffer",
+            ));
+        }
+        Ok(BufferWriter {
+            target: Target::Buffer(buffer),
+            start: 0,
+            len: 0,
+            error: None,
+        })
+    }
+
+    /// Returns the number of bytes written, or the error that stopped the
+    /// serializer. A `bytearray` is truncated back to its original length
+    /// on errors; other buffers may be partially overwritten.
+    pub fn finish<T>(self, result: Result<T, HyperJsonError>) -> PyResult<usize> {
+        match result {
+            Ok(_) => Ok(self.len - self.start),
+            Err(err) => {
+                if let Target::ByteArray(array) = self.target {
+                    // The original error is more interesting than this one
+                    let _ = array.resize(self.start);
+                }
+                Err(self.error.unwrap_or_else(|| err.into()))
+            }
+        }
+    }
+
+    fn fail(&mut self, err: PyErr) -> io::Error {
+        self.error = Some(err);
+        io::Error::other("could not write to buffer")
+    }
+}
+
+impl<'p> io::Write for BufferWriter<'p> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.len + buf.len();
+        let dest = match &self.target {
+            Target::ByteArray(array) => {
+                // CPython over-allocates when growing a bytearray, so this
+                // is amortized O(1)
+                if let Err(err) = array.resize(len) {
+                    return Err(self.fail(err));
+                }
+                unsafe { pyo3::ffi::PyByteArray_AsString(array.as_ptr()) as *mut u8 }
+            }
+            Target::Buffer(buffer) => {
+                if len > buffer.len_bytes() {
+                    let err = PyValueError::py_err(format!(
+                        "buffer too small: {} bytes available",
+                        buffer.len_bytes()
+                    ));
+                    return Err(self.fail(err));
+                }
+                buffer.buf_ptr() as *mut u8
+            }
+        };
+        unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), dest.add(self.len), buf.len()) };
+        self.len = len;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How many bytes are collected before they are passed to `fp.write`.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Writes to a Python file object in chunks of about `chunk_size` bytes, so
+/// that large documents never have to be held in memory as a whole.
+///
+/// Binary files are written `bytes`, everything else is written `str`. Text
+/// chunks are cut at character boundaries.
+pub struct FileWriter<'p> {
+    py: Python<'p>,
+    write: &'p PyAny,
+    binary: bool,
+    chunk_size: usize,
+    buf: Vec<u8>,
+    error: Option<PyErr>,
+}
+
+impl<'p> FileWriter<'p> {
+    pub fn new(py: Python<'p>, fp: &'p PyAny, chunk_size: usize) -> PyResult<FileWriter<'p>> {
+        if chunk_size == 0 {
+            return Err(PyValueError::py_err("chunk_size must be positive"));
+        }
+        let write = fp.getattr("write")?;
+        Ok(FileWriter {
+            py,
+            write,
+            binary: is_binary(py, fp)?,
+            chunk_size,
+            buf: Vec::with_capacity(chunk_size),
+            error: None,
+        })
+    }
+
+    /// Writes out what is left in the buffer, or returns the error that
+    /// stopped the serializer. Chunks written before an error stay written.
+    pub fn finish<T>(mut self, result: PyResult<T>) -> PyResult<()> {
+        match result {
+            Ok(_) => self.write_chunk(self.buf.len()),
+            Err(err) => Err(self.error.unwrap_or(err)),
+        }
+    }
+
+    /// Passes the first `len` bytes of the buffer to `fp.write`.
+    fn write_chunk(&mut self, len: usize) -> PyResult<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        send(self.py, self.write, self.binary, &self.buf[..len])?;
+        self.buf.drain(..len);
+        Ok(())
+    }
+}
+
+/// Passes `data` to the `write` method of a file object, as `bytes` or as
+/// `str`. Text must be cut at character boundaries.
+pub fn send(py: Python, write: &PyAny, binary: bool, data: &[u8]) -> PyResult<()> {
+    let chunk = if binary {
+        PyBytes::new(py, data).to_object(py)
+    } else {
+        // serde_json only writes valid UTF-8
+        let s = std::str::from_utf8(data).map_err(|e| PyValueError::py_err(e.to_string()))?;
+        PyString::new(py, s).to_object(py)
+    };
+    write.call1((chunk,))?;
+    Ok(())
+}
+
+impl<'p> io::Write for FileWriter<'p> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        if self.buf.len() >= self.chunk_size {
+            let mut len = self.buf.len();
+            if !self.binary {
+                // Back up to the start of a character
+                while len > 0 && (self.buf[len - 1] & 0xC0) == 0x80 {
+                    len -= 1;
+                }
+                if len > 0 && self.buf[len - 1] >= 0xC0 {
+                    len -= 1;
+                }
+            }
+            if let Err(err) = self.write_chunk(len) {
+                self.error = Some(err);
+                return Err(io::Error::other("could not write to file"));
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Tells binary file objects from text ones, the way `json.dump` users
+/// would expect: by their type, or by their `mode` if they have one.
+pub fn is_binary(py: Python, fp: &PyAny) -> PyResult<bool> {
+    let io = py.import("io")?;
+    let binary_types = (io.get("RawIOBase")?, io.get("BufferedIOBase")?);
+    let builtins = py.import("builtins")?;
+    if builtins
+        .call1("isinstance", (fp, binary_types))?
+        .is_true()?
+    {
+        return Ok(true);
+    }
+    if let Ok(mode) = fp.getattr("mode") {
+        if let Ok(mode) = mode.extract::<String>() {
+            return Ok(mode.contains('b'));
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,57 @@
+import array
+import mmap
+
+import pytest
+import hyperjson
+
+doc = b'{"key": ["value", 1, 2.5, null, true]}'
+expected = {"key": ["value", 1, 2.5, None, True]}
+
+
+@pytest.mark.parametrize("make", [
+    bytes,
+    bytearray,
+    memoryview,
+    lambda b: memoryview(bytearray(b)),
+    lambda b: array.array("B", b),
+])
+def test_loads_buffer(make):
+    assert hyperjson.loads(make(doc)) == expected
+
+
+def test_loads_memoryview_slice():
+    data = b"xxx" + doc + b"yyy"
+    assert hyperjson.loads(memoryview(data)[3:-3]) == expected
+
+
+def test_loads_non_contiguous():
+    data = bytes(b for c in doc for b in (c, 0x20))
+    assert hyperjson.loads(memoryview(data)[::2]) == expected
+
+
+def test_loads_mmap():
+    with mmap.mmap(-1, len(doc)) as m:
+        m.write(doc)
+        assert hyperjson.loads(m) == expected
+
+
+def test_loads_buffer_utf16():
+    assert hyperjson.loads(memoryview('["ü"]'.encode("utf-16"))) == ["ü"]
+
+
+def test_buffer_locked_while_parsing():
+    data = bytearray(b"[1.5, 2.5]")
+
+    def parse_float(s):
+        with pytest.raises(BufferError):
+            data.extend(b"   ")
+        return float(s)
+
+    assert hyperjson.loads(data, parse_float=parse_float) == [1.5, 2.5]
+    data.extend(b"   ")
+
+
+@pytest.mark.parametrize("obj", [None, 1, [b"[]"], object()])
+def test_loads_not_a_buffer(obj):
+    with pytest.raises(TypeError):
+        hyperjson.loads(obj)
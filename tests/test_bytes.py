@@ -0,0 +1,55 @@
+import pytest
+import hyperjson
+
+
+def test_bytes_raise_by_default():
+    with pytest.raises(TypeError):
+        hyperjson.dumps(b"abc")
+    with pytest.raises(TypeError):
+        hyperjson.dumps(b"abc", bytes_mode="raise")
+
+
+def test_bytes_mode_invalid():
+    with pytest.raises(ValueError):
+        hyperjson.dumps(b"abc", bytes_mode="hex")
+
+
+@pytest.mark.parametrize("payload", [b"abc", bytearray(b"abc"), memoryview(b"abc")])
+def test_bytes_like_types(payload):
+    assert hyperjson.dumps(payload, bytes_mode="utf8") == '"abc"'
+    assert hyperjson.dumps([payload], bytes_mode="base64") == '["YWJj"]'
+
+
+def test_bytes_utf8_invalid():
+    with pytest.raises(TypeError):
+        hyperjson.dumps(b"\xff", bytes_mode="utf8")
+
+
+def test_bytes_base64_alphabets():
+    payload = {"key": b"\xfb\xff\xfe"}
+    assert hyperjson.dumps(payload, bytes_mode="base64") == '{"key":"+//+"}'
+    assert hyperjson.dumps(payload, bytes_mode="base64url") == '{"key":"-__-"}'
+
+
+@pytest.mark.parametrize("mode", ["utf8", "base64", "base64url"])
+def test_bytes_roundtrip(mode):
+    payload = {"payload": b"\x00\x01 binary \xfb\xff", "name": "plain"}
+    if mode == "utf8":
+        payload["payload"] = "café".encode("utf-8")
+    s = hyperjson.dumps(payload, bytes_mode=mode)
+    assert hyperjson.loads(s, bytes_mode=mode, bytes_fields={"payload"}) == payload
+
+
+def test_bytes_fields_arrays_and_nesting():
+    s = '{"parts": ["YQ==", "Yg=="], "inner": {"parts": "Yw==", "other": "Yw=="}}'
+    assert hyperjson.loads(s, bytes_mode="base64", bytes_fields=["parts"]) == {
+        "parts": [b"a", b"b"],
+        "inner": {"parts": b"c", "other": "Yw=="},
+    }
+
+
+def test_bytes_fields_invalid():
+    with pytest.raises(ValueError):
+        hyperjson.loads('{"a": "!!"}', bytes_mode="base64", bytes_fields=["a"])
+    with pytest.raises(ValueError):
+        hyperjson.loads('{"a": "YQ=="}', bytes_fields=["a"])
@@ -0,0 +1,150 @@
+import json
+import struct
+
+import pytest
+import hyperjson
+
+"""
+The input and output test vectors are copied from
+https://github.com/cyberphone/json-canonicalization/tree/master/testdata
+and the numbers from RFC 8785, Appendix B.
+"""
+
+VECTORS = {
+    "arrays": (
+        r"""[
+  56,
+  {
+    "d": true,
+    "10": null,
+    "1": [ ]
+  }
+]""",
+        '[56,{"1":[],"10":null,"d":true}]',
+    ),
+    "french": (
+        r"""{
+  "peach": "This sorting order",
+  "péché": "is wrong according to French",
+  "pêche": "but canonicalization MUST",
+  "sin":   "ignore locale"
+}""",
+        '{"peach":"This sorting order","péché":"is wrong according to French",'
+        '"pêche":"but canonicalization MUST","sin":"ignore locale"}',
+    ),
+    "structures": (
+        r"""{
+  "1": {"f": {"f": "hi","F": 5} ,"\n": 56.0},
+  "10": { },
+  "": "empty",
+  "a": { },
+  "111": [ {"e": "yes","E": "no" } ],
+  "A": { }
+}""",
+        r'{"":"empty","1":{"\n":56,"f":{"F":5,"f":"hi"}},"10":{},'
+        r'"111":[{"E":"no","e":"yes"}],"A":{},"a":{}}',
+    ),
+    "unicode": (
+        r"""{
+  "Unnormalized Unicode":"A\u030a"
+}""",
+        '{"Unnormalized Unicode":"A\u030a"}',
+    ),
+    "values": (
+        r"""{
+  "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+  "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+  "literals": [null, true, false]
+}""",
+        r'{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],'
+        r'"string":"€$\u000f\nA'
+        "'"
+        r'B\"\\\\\"/"}',
+    ),
+    "weird": (
+        r"""{
+  "\u20ac": "Euro Sign",
+  "\r": "Carriage Return",
+  "\ufb33": "Hebrew Letter Dalet With Dagesh",
+  "1": "One",
+  "\ud83d\ude00": "Emoji: Grinning Face",
+  "\u0080": "Control",
+  "\u00f6": "Latin Small Letter O With Diaeresis"
+}""",
+        '{"\\r":"Carriage Return","1":"One","\u0080":"Control",'
+        '"\u00f6":"Latin Small Letter O With Diaeresis","\u20ac":"Euro Sign",'
+        '"\U0001f600":"Emoji: Grinning Face","\ufb33":"Hebrew Letter Dalet With Dagesh"}',
+    ),
+}
+
+
+@pytest.mark.parametrize("name", sorted(VECTORS))
+def test_vectors(name):
+    text, expected = VECTORS[name]
+    # `loads` does not always round 333333333.33333329 correctly
+    assert hyperjson.dumps_canonical(json.loads(text)) == expected
+
+
+@pytest.mark.parametrize(
+    "bits, expected",
+    [
+        ("0000000000000000", "0"),
+        ("8000000000000000", "0"),
+        ("0000000000000001", "5e-324"),
+        ("8000000000000001", "-5e-324"),
+        ("7fefffffffffffff", "1.7976931348623157e+308"),
+        ("ffefffffffffffff", "-1.7976931348623157e+308"),
+        ("4340000000000000", "9007199254740992"),
+        ("c340000000000000", "-9007199254740992"),
+        ("4430000000000000", "295147905179352830000"),
+        ("44b52d02c7e14af5", "9.999999999999997e+22"),
+        ("44b52d02c7e14af6", "1e+23"),
+        ("44b52d02c7e14af7", "1.0000000000000001e+23"),
+        ("444b1ae4d6e2ef4e", "999999999999999700000"),
+        ("444b1ae4d6e2ef4f", "999999999999999900000"),
+        ("444b1ae4d6e2ef50", "1e+21"),
+        ("3eb0c6f7a0b5ed8c", "9.999999999999997e-7"),
+        ("3eb0c6f7a0b5ed8d", "0.000001"),
+        ("41b3de4355555553", "333333333.3333332"),
+        ("41b3de4355555554", "333333333.33333325"),
+        ("41b3de4355555555", "333333333.3333333"),
+        ("41b3de4355555556", "333333333.3333334"),
+        ("41b3de4355555557", "333333333.33333343"),
+        ("becbf647612f3696", "-0.0000033333333333333333"),
+        ("43143ff3c1cb0959", "1424953923781206.2"),
+    ],
+)
+def test_numbers(bits, expected):
+    (value,) = struct.unpack(">d", bytes.fromhex(bits))
+    assert hyperjson.dumps_canonical(value) == expected
+
+
+@pytest.mark.parametrize(
+    "bits", ["7fffffffffffffff", "7ff0000000000000", "fff0000000000000"]
+)
+def test_non_finite(bits):
+    (value,) = struct.unpack(">d", bytes.fromhex(bits))
+    with pytest.raises(ValueError):
+        hyperjson.dumps_canonical(value)
+
+
+def test_integers():
+    assert hyperjson.dumps_canonical([0, -1, 2 ** 53, 2 ** 60]) == (
+        "[0,-1,9007199254740992,1152921504606847000]"
+    )
+    with pytest.raises(ValueError):
+        hyperjson.dumps_canonical(2 ** 53 + 1)
+    with pytest.raises(OverflowError):
+        hyperjson.dumps_canonical(10 ** 400)
+
+
+def test_types():
+    assert hyperjson.dumps_canonical((True, False, None, ("\x1f\b",))) == (
+        '[true,false,null,["\\u001f\\b"]]'
+    )
+    with pytest.raises(TypeError):
+        hyperjson.dumps_canonical({1: 2})
+    with pytest.raises(TypeError):
+        hyperjson.dumps_canonical(object())
+    with pytest.raises(UnicodeEncodeError):
+        hyperjson.dumps_canonical("\ud800")
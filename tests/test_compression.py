@@ -0,0 +1,103 @@
+import gzip
+import io
+import pathlib
+import tempfile
+
+import pytest
+import hyperjson
+
+doc = {"items": [{"id": i, "name": "ü%d" % i} for i in range(3000)]}
+data = hyperjson.dumps_bytes(doc)
+# The zstd magic number, as written by the zstd command line tool
+ZSTD_MAGIC = b"\x28\xb5\x2f\xfd"
+
+
+@pytest.fixture
+def tmp():
+    with tempfile.TemporaryDirectory() as d:
+        yield pathlib.Path(d)
+
+
+def test_load_gzip_autodetect():
+    assert hyperjson.load(io.BytesIO(gzip.compress(data))) == doc
+
+
+def test_load_concatenated_gzip():
+    half = len(data) // 2
+    fp = io.BytesIO(gzip.compress(data[:half]) + gzip.compress(data[half:]))
+    assert hyperjson.load(fp) == doc
+
+
+def test_load_compression_none():
+    with pytest.raises(ValueError):
+        hyperjson.load(io.BytesIO(gzip.compress(data)), compression="none")
+
+
+def test_load_wrong_compression():
+    with pytest.raises(OSError):
+        hyperjson.load(io.BytesIO(data), compression="gzip")
+
+
+def test_load_corrupt_gzip():
+    compressed = gzip.compress(data)
+    with pytest.raises(OSError):
+        hyperjson.load(io.BytesIO(compressed[:len(compressed) // 2]))
+
+
+@pytest.mark.parametrize("compression", ["gzip", "zstd"])
+def test_dump_roundtrip(compression):
+    fp = io.BytesIO()
+    hyperjson.dump(doc, fp, compression=compression, chunk_size=1024)
+    compressed = fp.getvalue()
+    assert len(compressed) < len(data)
+    assert hyperjson.load(io.BytesIO(compressed)) == doc
+    assert hyperjson.load(io.BytesIO(compressed), compression=compression) == doc
+
+
+def test_dump_gzip_is_readable_by_python():
+    fp = io.BytesIO()
+    hyperjson.dump(doc, fp, compression="gzip")
+    assert gzip.decompress(fp.getvalue()) == data
+
+
+def test_dump_zstd_magic():
+    fp = io.BytesIO()
+    hyperjson.dump(doc, fp, compression="zstd")
+    assert fp.getvalue().startswith(ZSTD_MAGIC)
+
+
+@pytest.mark.parametrize("name,magic", [
+    ("doc.json.gz", b"\x1f\x8b"), ("doc.json.zst", ZSTD_MAGIC), ("doc.json", b"{"),
+])
+def test_path_compression_from_extension(tmp, name, magic):
+    path = tmp / name
+    hyperjson.dump_path(doc, path)
+    assert path.read_bytes().startswith(magic)
+    assert hyperjson.load_path(path) == doc
+
+
+def test_path_explicit_compression(tmp):
+    path = tmp / "doc.json"
+    hyperjson.dump_path(doc, path, compression="gzip")
+    assert gzip.decompress(path.read_bytes()) == data
+    assert hyperjson.load_path(path) == doc
+    assert hyperjson.load_path(path, compression="gzip") == doc
+
+
+def test_path_gzip_written_by_python(tmp):
+    path = tmp / "doc.json.gz"
+    path.write_bytes(gzip.compress(data))
+    assert hyperjson.load_path(path) == doc
+
+
+def test_compressed_utf16(tmp):
+    path = tmp / "doc.json.gz"
+    path.write_bytes(gzip.compress('{"a": "ü"}'.encode("utf-16")))
+    assert hyperjson.load_path(path) == {"a": "ü"}
+
+
+def test_invalid_compression():
+    with pytest.raises(ValueError):
+        hyperjson.dump([], io.BytesIO(), compression="brotli")
+    with pytest.raises(ValueError):
+        hyperjson.load(io.BytesIO(b"[]"), compression="brotli")
@@ -0,0 +1,88 @@
+import io
+import os
+import tempfile
+
+import pytest
+import hyperjson
+
+big = {"items": [{"id": i, "name": "ü%d\U0001f600" % i} for i in range(5000)]}
+
+
+class Recorder:
+    def __init__(self):
+        self.chunks = []
+
+    def write(self, chunk):
+        self.chunks.append(chunk)
+
+
+def test_dump_text_chunks():
+    fp = Recorder()
+    hyperjson.dump(big, fp, ensure_ascii=False, chunk_size=100)
+    assert len(fp.chunks) > 1
+    assert all(isinstance(chunk, str) for chunk in fp.chunks)
+    assert "".join(fp.chunks) == hyperjson.dumps(big, ensure_ascii=False)
+
+
+@pytest.mark.parametrize("chunk_size", [1, 2, 3, 5, 4096])
+def test_dump_text_chunk_boundaries(chunk_size):
+    obj = ["ü€\U0001f600" * 10]
+    fp = Recorder()
+    hyperjson.dump(obj, fp, ensure_ascii=False, chunk_size=chunk_size)
+    assert "".join(fp.chunks) == hyperjson.dumps(obj, ensure_ascii=False)
+
+
+def test_dump_binary():
+    fp = io.BytesIO()
+    hyperjson.dump(big, fp, chunk_size=1000)
+    assert fp.getvalue() == hyperjson.dumps_bytes(big)
+
+
+def test_dump_files():
+    with tempfile.TemporaryDirectory() as tmp:
+        path = os.path.join(tmp, "out.json")
+        with open(path, "w", encoding="utf-8") as f:
+            hyperjson.dump(big, f, indent=2)
+        with open(path, "rb") as f:
+            assert f.read() == hyperjson.dumps_bytes(big, indent=2)
+        with open(path, "wb") as f:
+            hyperjson.dump(big, f)
+        with open(path, "rb") as f:
+            assert hyperjson.load(f) == big
+
+
+def test_dump_mode_attribute():
+    class BinaryFile(Recorder):
+        mode = "wb"
+
+    fp = BinaryFile()
+    hyperjson.dump([1, "a"], fp)
+    assert fp.chunks == [b'[1,"a"]']
+
+
+def test_dump_write_error_propagates():
+    class Full:
+        def __init__(self):
+            self.calls = 0
+
+        def write(self, chunk):
+            self.calls += 1
+            if self.calls == 3:
+                raise OSError("disk full")
+
+    fp = Full()
+    with pytest.raises(OSError):
+        hyperjson.dump(big, fp, chunk_size=64)
+    assert fp.calls == 3
+
+
+def test_dump_serialization_error():
+    fp = Recorder()
+    with pytest.raises(TypeError):
+        hyperjson.dump([1, object()], fp)
+    assert fp.chunks == []
+
+
+def test_dump_invalid_chunk_size():
+    with pytest.raises(ValueError):
+        hyperjson.dump([], io.StringIO(), chunk_size=0)
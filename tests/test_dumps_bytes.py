@@ -0,0 +1,35 @@
+import json
+
+import pytest
+import hyperjson
+
+
+@pytest.mark.parametrize("obj", [
+    None, True, 1, 1.5, "über", [1, "a", None], {"a": {"b": [1, 2]}}, "\U0001f600",
+])
+def test_dumps_bytes(obj):
+    result = hyperjson.dumps_bytes(obj)
+    assert isinstance(result, bytes)
+    assert result == hyperjson.dumps(obj).encode()
+
+
+def test_dumps_bytes_options():
+    obj = {"b": ["ü", "</a>"], "a": 1}
+    for kwargs in [
+        {"indent": 2},
+        {"sort_keys": True},
+        {"ensure_ascii": False},
+        {"encode_html_chars": True},
+    ]:
+        assert hyperjson.dumps_bytes(obj, **kwargs) == hyperjson.dumps(obj, **kwargs).encode()
+
+
+def test_dumps_bytes_roundtrip():
+    obj = {"key": ["value", 1, 2.5, None, True]}
+    assert hyperjson.loads(hyperjson.dumps_bytes(obj)) == obj
+    assert json.loads(hyperjson.dumps_bytes(obj)) == obj
+
+
+def test_dumps_bytes_error():
+    with pytest.raises(TypeError):
+        hyperjson.dumps_bytes(object())
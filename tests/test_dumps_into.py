@@ -0,0 +1,78 @@
+import array
+
+import pytest
+import hyperjson
+
+
+def test_dumps_into_bytearray():
+    buf = bytearray()
+    n = hyperjson.dumps_into({"a": [1, 2]}, buf)
+    assert buf == b'{"a":[1,2]}'
+    assert n == len(buf)
+
+
+def test_dumps_into_appends():
+    buf = bytearray(b"prefix:")
+    n1 = hyperjson.dumps_into([1, "ü"], buf)
+    n2 = hyperjson.dumps_into(None, buf, indent=2)
+    assert buf == 'prefix:[1,"ü"]null'.encode()
+    assert (n1, n2) == (len('[1,"ü"]'.encode()), 4)
+
+
+def test_dumps_into_reuse():
+    buf = bytearray()
+    for i in range(1000):
+        del buf[:]
+        hyperjson.dumps_into({"i": i}, buf)
+        assert hyperjson.loads(bytes(buf)) == {"i": i}
+
+
+def test_dumps_into_options():
+    obj = {"b": "</ü>", "a": [True, None]}
+    for kwargs in [{}, {"indent": 4}, {"sort_keys": True}, {"ensure_ascii": False},
+                   {"encode_html_chars": True}]:
+        buf = bytearray()
+        hyperjson.dumps_into(obj, buf, **kwargs)
+        assert buf == hyperjson.dumps_bytes(obj, **kwargs)
+
+
+def test_dumps_into_error_restores_bytearray():
+    buf = bytearray(b"keep")
+    with pytest.raises(TypeError):
+        hyperjson.dumps_into([1, 2, object()], buf)
+    assert buf == b"keep"
+
+
+def test_dumps_into_memoryview():
+    buf = bytearray(16)
+    view = memoryview(buf)
+    n = hyperjson.dumps_into([1, 2, 3], view[4:])
+    assert n == 7
+    assert buf[4:4 + n] == b"[1,2,3]"
+    assert buf[:4] == bytes(4)
+
+
+def test_dumps_into_array():
+    buf = array.array("B", bytes(8))
+    assert hyperjson.dumps_into("abc", buf) == 5
+    assert buf.tobytes()[:5] == b'"abc"'
+
+
+def test_dumps_into_too_small():
+    buf = bytearray(4)
+    with pytest.raises(ValueError):
+        hyperjson.dumps_into([1, 2, 3], memoryview(buf))
+
+
+def test_dumps_into_exported_bytearray():
+    buf = bytearray()
+    view = memoryview(buf)
+    with pytest.raises(BufferError):
+        hyperjson.dumps_into([1], buf)
+    view.release()
+
+
+@pytest.mark.parametrize("buf", [b"immutable", "str", None])
+def test_dumps_into_invalid_buffer(buf):
+    with pytest.raises((TypeError, BufferError)):
+        hyperjson.dumps_into([1], buf)
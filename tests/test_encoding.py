@@ -0,0 +1,64 @@
+import json
+
+import pytest
+import hyperjson
+
+payload = {"key": ["value", 1, "über", "\U0001f600"]}
+doc = json.dumps(payload, ensure_ascii=False)
+
+
+@pytest.mark.parametrize("encoding", [
+    "utf-8", "utf-8-sig", "utf-16", "utf-16-le", "utf-16-be", "utf-32", "utf-32-le", "utf-32-be",
+])
+def test_detect_encoding(encoding):
+    data = doc.encode(encoding)
+    assert json.loads(data) == payload
+    assert hyperjson.loads(data) == payload
+
+
+@pytest.mark.parametrize("encoding", ["utf-16-le", "utf-16-be", "utf-32-le", "utf-32-be"])
+def test_short_documents(encoding):
+    assert hyperjson.loads("1".encode(encoding)) == 1
+    assert hyperjson.loads("[]".encode(encoding)) == []
+
+
+def test_invalid_utf8_strict():
+    with pytest.raises(UnicodeDecodeError) as excinfo:
+        hyperjson.loads(b'["ok", "\xff"]')
+    assert excinfo.value.start == 8
+    with pytest.raises(UnicodeDecodeError):
+        hyperjson.loads(b'["ok", "\xff"]', errors="strict")
+
+
+def test_invalid_utf8_replace():
+    data = b'["a\xffb", "\xe2\x82", "\xf0\x9f\x98\x80"]'
+    assert hyperjson.loads(data, errors="replace") == json.loads(data.decode("utf-8", "replace"))
+
+
+def test_invalid_utf8_surrogateescape():
+    data = b'{"a\xff": "b\xfe\xfd"}'
+    result = hyperjson.loads(data, errors="surrogateescape")
+    assert result == json.loads(data.decode("utf-8", "surrogateescape"))
+    assert hyperjson.dumps(result, surrogates="escape") == '{"a\\udcff":"b\\udcfe\\udcfd"}'
+
+
+def test_invalid_utf16():
+    data = '["a"]'.encode("utf-16-le") + b"\x00"
+    with pytest.raises(UnicodeDecodeError):
+        hyperjson.loads(data)
+    # The replacement character ends up after the closing bracket
+    with pytest.raises(ValueError):
+        hyperjson.loads(data, errors="replace")
+
+
+def test_lone_surrogates_in_bytes():
+    data = '"\ud800"'.encode("utf-16-le", "surrogatepass")
+    assert hyperjson.loads(data, surrogates="escape") == json.loads(data)
+    assert hyperjson.loads(data, surrogates="replace") == "�"
+    with pytest.raises(UnicodeDecodeError):
+        hyperjson.loads(data)
+
+
+def test_errors_invalid_policy():
+    with pytest.raises(ValueError):
+        hyperjson.loads(b"1", errors="ignore")
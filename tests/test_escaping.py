@@ -0,0 +1,56 @@
+import json
+
+import pytest
+import hyperjson
+
+
+@pytest.mark.parametrize("s", ["plain", "über", "€", "\U0001f600", "a b", "\x00\n\""])
+def test_ensure_ascii(s):
+    assert hyperjson.dumps(s) == json.dumps(s, ensure_ascii=False)
+    assert hyperjson.dumps(s, ensure_ascii=True) == json.dumps(s, ensure_ascii=True)
+    assert hyperjson.dumps(s, ensure_ascii=False) == json.dumps(s, ensure_ascii=False)
+
+
+def test_ensure_ascii_keys():
+    obj = {"ключ": ["значение"]}
+    assert hyperjson.dumps(obj) == json.dumps(obj, ensure_ascii=False, separators=(",", ":"))
+    assert hyperjson.dumps(obj, ensure_ascii=True) == json.dumps(obj, separators=(",", ":"))
+
+
+def test_encode_html_chars():
+    s = "</script><script>alert('x' && 1)</script>"
+    encoded = hyperjson.dumps(s, encode_html_chars=True)
+    for c in "<>&'":
+        assert c not in encoded
+    assert encoded == (
+        '"\\u003c\\/script\\u003e\\u003cscript\\u003ealert(\\u0027x\\u0027 '
+        '\\u0026\\u0026 1)\\u003c\\/script\\u003e"'
+    )
+    assert hyperjson.loads(encoded) == s
+
+
+def test_line_separators():
+    s = "a b c"
+    for ensure_ascii in (True, False):
+        encoded = hyperjson.dumps(s, ensure_ascii=ensure_ascii, encode_html_chars=True)
+        assert encoded == '"a\\u2028b\\u2029c"'
+    assert hyperjson.dumps(s, ensure_ascii=False) == '"a b c"'
+
+
+def test_html_without_ensure_ascii():
+    s = "<ü>"
+    assert hyperjson.dumps(s, ensure_ascii=False, encode_html_chars=True) == '"\\u003cü\\u003e"'
+    assert hyperjson.dumps(s, encode_html_chars=True) == '"\\u003cü\\u003e"'
+    assert hyperjson.dumps(s, ensure_ascii=True, encode_html_chars=True) == '"\\u003c\\u00fc\\u003e"'
+
+
+def test_escape_forward_slashes():
+    assert hyperjson.dumps("a/b") == '"a/b"'
+    assert hyperjson.dumps("a/b", escape_forward_slashes=True) == '"a\\/b"'
+    assert hyperjson.dumps("a/b", encode_html_chars=True) == '"a\\/b"'
+    assert hyperjson.dumps("a/b", encode_html_chars=True, escape_forward_slashes=False) == '"a/b"'
+
+
+def test_escaping_with_indent():
+    obj = {"<": ["/"]}
+    assert hyperjson.dumps(obj, indent=2, encode_html_chars=True) == '{\n  "\\u003c": [\n    "\\/"\n  ]\n}'
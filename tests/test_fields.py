@@ -0,0 +1,75 @@
+import json
+
+import pytest
+import hyperjson
+
+doc = {
+    "id": 7,
+    "user": {"name": "ann", "email": "a@example.com", "address": {"city": "Oslo", "zip": "0150"}},
+    "items": [{"id": 1, "price": 2.5}, {"id": 2, "price": 3}],
+    "blob": {"big": list(range(100))},
+}
+s = json.dumps(doc)
+
+
+def test_dotted_paths():
+    assert hyperjson.loads(s, fields={"id", "user.name", "user.address.city"}) == {
+        "id": 7,
+        "user": {"name": "ann", "address": {"city": "Oslo"}},
+    }
+
+
+def test_whole_value():
+    assert hyperjson.loads(s, fields=["user"]) == {"user": doc["user"]}
+
+
+def test_shorter_path_wins():
+    assert hyperjson.loads(s, fields=["user.name", "user"]) == {"user": doc["user"]}
+    assert hyperjson.loads(s, fields=["user", "user.name"]) == {"user": doc["user"]}
+
+
+def test_nested_spec():
+    spec = {"id": True, "user": {"address": {"zip": True}}, "blob": False}
+    assert hyperjson.loads(s, fields=spec) == {"id": 7, "user": {"address": {"zip": "0150"}}}
+
+
+def test_arrays_are_transparent():
+    assert hyperjson.loads(s, fields={"items.id"}) == {"items": [{"id": 1}, {"id": 2}]}
+    assert hyperjson.loads("[%s, %s]" % (s, s), fields={"id"}) == [{"id": 7}, {"id": 7}]
+
+
+def test_missing_and_scalar_paths():
+    assert hyperjson.loads(s, fields={"nope", "id.x"}) == {"id": 7}
+    assert hyperjson.loads("3", fields={"a"}) == 3
+
+
+def test_empty():
+    assert hyperjson.loads(s, fields=set()) == {}
+    assert hyperjson.loads(s, fields=None) == doc
+
+
+def test_skipped_values_are_still_validated():
+    with pytest.raises(json.JSONDecodeError):
+        hyperjson.loads('{"a": 1, "b": [1, }', fields={"a"})
+
+
+def test_skipped_values_are_not_parsed():
+    calls = []
+    hyperjson.loads('{"a": 1.5, "b": [2.5, 3.5]}', fields={"a"}, parse_float=calls.append)
+    assert calls == ["1.5"]
+
+
+def test_with_bytes_fields():
+    s = hyperjson.dumps({"sig": b"\xff", "other": b"\x00"}, bytes_mode="base64")
+    assert hyperjson.loads(s, fields={"sig"}, bytes_mode="base64", bytes_fields={"sig"}) == {"sig": b"\xff"}
+
+
+@pytest.mark.parametrize("fields", [{"a..b"}, {""}, {".a"}])
+def test_invalid_paths(fields):
+    with pytest.raises(ValueError):
+        hyperjson.loads(s, fields=fields)
+
+
+def test_single_string_is_rejected():
+    with pytest.raises(TypeError):
+        hyperjson.loads(s, fields="user.name")
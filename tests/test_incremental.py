@@ -0,0 +1,114 @@
+import json
+import time
+
+import pytest
+import hyperjson
+
+doc = {"items": [{"id": i, "name": "item %d" % i, "tags": ["ü", "\\\"{[", "\U0001f600"]} for i in range(2000)]}
+text = json.dumps(doc, ensure_ascii=False)
+
+
+def feed_all(decoder, chunks):
+    values = []
+    for chunk in chunks:
+        values.extend(decoder.feed(chunk))
+    return values
+
+
+def test_feed_complete_values():
+    decoder = hyperjson.IncrementalDecoder()
+    assert decoder.feed('{"a": 1} [1, 2] "x" ') == [{"a": 1}, [1, 2], "x"]
+    assert decoder.buffered == 0
+
+
+def test_feed_partial_values():
+    decoder = hyperjson.IncrementalDecoder()
+    assert decoder.feed(b'{"a": [1, ') == []
+    assert decoder.buffered == 10
+    assert decoder.feed(b'2]}{"b"') == [{"a": [1, 2]}]
+    assert decoder.feed(b": 3}") == [{"b": 3}]
+
+
+@pytest.mark.parametrize("size", [1, 7, 4096])
+def test_chunks(size):
+    data = (text + "\n").encode() * 3
+    chunks = [data[i:i + size] for i in range(0, len(data), size)]
+    assert feed_all(hyperjson.IncrementalDecoder(), chunks) == [doc] * 3
+
+
+def test_strings_with_brackets_and_escapes():
+    decoder = hyperjson.IncrementalDecoder()
+    s = '"a\\"}]" {"k\\\\": "]\\"{"} ["\\\\"]'
+    assert feed_all(decoder, s) == ["a\"}]", {"k\\": "]\"{"}, ["\\"]]
+
+
+def test_scalars_need_a_delimiter():
+    decoder = hyperjson.IncrementalDecoder()
+    assert decoder.feed("12") == []
+    assert decoder.feed("3") == []
+    assert decoder.feed(" true") == [123]
+    assert decoder.feed("[") == [True]
+    assert decoder.feed("]null") == [[]]
+    assert decoder.close() == [None]
+
+
+def test_close():
+    decoder = hyperjson.IncrementalDecoder()
+    assert decoder.close() == []
+    decoder.feed("[1, 2")
+    with pytest.raises(json.JSONDecodeError):
+        decoder.close()
+    assert decoder.buffered == 0
+    assert decoder.feed("[3]") == [[3]]
+
+
+def test_invalid_values():
+    decoder = hyperjson.IncrementalDecoder()
+    with pytest.raises(json.JSONDecodeError):
+        decoder.feed('[1] {"a" 1} [2]')
+    # Values around the error are not lost
+    assert decoder.feed("") == [[1], [2]]
+    with pytest.raises(json.JSONDecodeError) as excinfo:
+        decoder.feed("] [3]")
+    assert "byte offset" in str(excinfo.value)
+    assert decoder.feed(" ") == [[3]]
+
+
+def test_max_buffer_size():
+    decoder = hyperjson.IncrementalDecoder(max_buffer_size=10)
+    assert decoder.feed('"short" ') == ["short"]
+    assert decoder.feed('["this') == []
+    with pytest.raises(ValueError):
+        decoder.feed(' is too long')
+    assert decoder.buffered == 0
+    assert decoder.feed("[1]") == [[1]]
+
+
+def test_complete_values_do_not_count_against_max_buffer_size():
+    decoder = hyperjson.IncrementalDecoder(max_buffer_size=10)
+    assert decoder.feed("[1]" * 100) == [[1]] * 100
+
+
+def test_options():
+    decoder = hyperjson.IncrementalDecoder(parse_float=str, bytes_mode="base64", bytes_fields={"b"})
+    assert decoder.feed('[1.5] {"b": "/w=="}') == [["1.5"], {"b": b"\xff"}]
+
+
+def test_unknown_option():
+    with pytest.raises(TypeError):
+        hyperjson.IncrementalDecoder(max_size=10)
+
+
+def test_feed_type_error():
+    with pytest.raises(TypeError):
+        hyperjson.IncrementalDecoder().feed(1)
+
+
+def test_long_value_is_scanned_once():
+    # Feeding a large value in small chunks must not be quadratic
+    big = json.dumps(list(range(200000))).encode()
+    chunks = [big[i:i + 64] for i in range(0, len(big), 64)]
+    decoder = hyperjson.IncrementalDecoder()
+    start = time.perf_counter()
+    assert feed_all(decoder, chunks) == [list(range(200000))]
+    assert time.perf_counter() - start < 5
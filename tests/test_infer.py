@@ -0,0 +1,119 @@
+import io
+
+import pytest
+import hyperjson
+
+DRAFT = "https://json-schema.org/draft/2020-12/schema"
+
+
+def test_infer_schema():
+    docs = [
+        {"id": 1, "name": "a", "tags": ["x"], "score": 1.5},
+        {"id": 2, "name": "b", "tags": [], "score": 2},
+        {"id": 3, "name": None, "extra": True},
+    ]
+    assert hyperjson.infer_schema(docs) == {
+        "$schema": DRAFT,
+        "type": "object",
+        "properties": {
+            "id": {"type": "integer"},
+            "name": {"type": ["null", "string"]},
+            "tags": {"type": "array", "items": {"type": "string"}},
+            "score": {"type": "number"},
+            "extra": {"type": "boolean"},
+        },
+        "required": ["id", "name"],
+    }
+
+
+def test_infer_schema_ndjson():
+    lines = io.StringIO('{"a": 1}\n\n{"a": "x", "b": [1, [2]]}\n')
+    assert hyperjson.infer_schema(lines) == {
+        "$schema": DRAFT,
+        "type": "object",
+        "properties": {
+            "a": {"type": ["integer", "string"]},
+            "b": {
+                "type": "array",
+                "items": {"type": ["integer", "array"], "items": {"type": "integer"}},
+            },
+        },
+        "required": ["a"],
+    }
+    lines = io.BytesIO(b'[1]\n{"a": 2}\n')
+    assert hyperjson.infer_schema(lines)["type"] == ["array", "object"]
+
+
+def test_infer_schema_empty():
+    assert hyperjson.infer_schema([]) == {"$schema": DRAFT}
+    assert hyperjson.infer_schema([[]]) == {"$schema": DRAFT, "type": "array"}
+
+
+def test_infer_schema_enum():
+    docs = [{"status": status} for status in ["on", "off", "on", None]]
+    schema = hyperjson.infer_schema(docs)["properties"]["status"]
+    assert schema == {"type": ["null", "string"], "enum": ["on", "off", None]}
+    # Without repeated values there is no telling it is an enum
+    docs = [{"name": name} for name in ["a", "b", "c"]]
+    schema = hyperjson.infer_schema(docs)["properties"]["name"]
+    assert schema == {"type": "string"}
+    # Nor when more values than the limit are seen
+    docs = [{"status": status} for status in "abcabc"]
+    schema = hyperjson.infer_schema(docs, enum_limit=2)["properties"]["status"]
+    assert schema == {"type": "string"}
+    schema = hyperjson.infer_schema(docs, enum_limit=3)["properties"]["status"]
+    assert schema == {"type": "string", "enum": ["a", "b", "c"]}
+    schema = hyperjson.infer_schema(docs, enum_limit=0)["properties"]["status"]
+    assert schema == {"type": "string"}
+    # `enum` would reject the numbers
+    docs = [{"value": value} for value in ["a", "a", 1]]
+    schema = hyperjson.infer_schema(docs)["properties"]["value"]
+    assert schema == {"type": ["integer", "string"]}
+
+
+@pytest.mark.parametrize(
+    "values, format",
+    [
+        (["2019-12-01T12:30:00Z", "2020-02-29t00:00:00.123+01:00"], "date-time"),
+        (["2019-12-01", "1999-01-31"], "date"),
+        (["123e4567-e89b-12d3-a456-426614174000"], "uuid"),
+        (["someone@example.com", "a.b+c@mail.example.org"], "email"),
+        (["2019-12-01T12:30:00Z", "2019-12-01"], None),
+        (["2019-13-01"], None),
+        (["2019-12-01T25:00:00Z"], None),
+        (["2019-12-01T12:30:00"], None),
+        (["123e4567-e89b-12d3-a456-42661417400g"], None),
+        (["someone@localhost", "@example.com"], None),
+        (["someone@example.com", "someone else"], None),
+    ],
+)
+def test_infer_schema_format(values, format):
+    schema = hyperjson.infer_schema([{"value": value} for value in values])
+    expected = {"type": "string"}
+    if format is not None:
+        expected["format"] = format
+    assert schema["properties"]["value"] == expected
+
+
+def test_infer_schema_validates():
+    docs = [
+        {"id": "123e4567-e89b-12d3-a456-426614174000", "kind": "a", "n": [1, 2.5]},
+        {"id": "123e4567-e89b-12d3-a456-426614174001", "kind": "a"},
+        {"id": "123e4567-e89b-12d3-a456-426614174002", "kind": "b", "n": None},
+    ]
+    validator = hyperjson.Validator(hyperjson.infer_schema(docs))
+    for doc in docs:
+        validator.validate(doc)
+    assert not validator.is_valid({"kind": "a"})
+    assert not validator.is_valid({"id": "x", "kind": "c"})
+
+
+def test_infer_schema_errors():
+    with pytest.raises(TypeError):
+        hyperjson.infer_schema([{"a": object()}])
+    with pytest.raises(TypeError):
+        hyperjson.infer_schema([{1: 2}])
+    with pytest.raises(ValueError):
+        hyperjson.infer_schema(["{"])
+    with pytest.raises(TypeError):
+        hyperjson.infer_schema(1)
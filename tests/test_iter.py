@@ -0,0 +1,166 @@
+import io
+import json
+
+import pytest
+import hyperjson
+
+records = [{"id": i, "name": "record %d" % i, "tags": ["ü", "\U0001f600"]} for i in range(1000)]
+jsonl = "".join(json.dumps(r, ensure_ascii=False) + "\n" for r in records)
+
+
+def test_loads_iter_jsonl():
+    assert list(hyperjson.loads_iter(jsonl)) == records
+    assert list(hyperjson.loads_iter(jsonl.encode())) == records
+    assert list(hyperjson.loads_iter(bytearray(jsonl.encode()))) == records
+    assert list(hyperjson.loads_iter(memoryview(jsonl.encode()))) == records
+
+
+def test_loads_iter_concatenated():
+    assert list(hyperjson.loads_iter('{"a": 1}[1, 2]"x" 3 4\n\ntrue null')) == [
+        {"a": 1},
+        [1, 2],
+        "x",
+        3,
+        4,
+        True,
+        None,
+    ]
+
+
+@pytest.mark.parametrize("s", ["", "   ", "\n\n", b"", b"\xef\xbb\xbf"])
+def test_loads_iter_empty(s):
+    assert list(hyperjson.loads_iter(s)) == []
+
+
+def test_loads_iter_is_lazy():
+    it = hyperjson.loads_iter('1\n2\n{"a": oops}\n')
+    assert iter(it) is it
+    assert next(it) == 1
+    assert next(it) == 2
+    with pytest.raises(json.JSONDecodeError) as excinfo:
+        next(it)
+    assert "line 3, byte offset 4" in str(excinfo.value)
+    with pytest.raises(StopIteration):
+        next(it)
+
+
+def test_loads_iter_options():
+    it = hyperjson.loads_iter('1.5\n{"b": "/w=="}\n', parse_float=str, bytes_mode="base64", bytes_fields={"b"})
+    assert list(it) == ["1.5", {"b": b"\xff"}]
+
+
+def test_loads_iter_type_error():
+    with pytest.raises(TypeError):
+        hyperjson.loads_iter(1)
+
+
+def test_skip_invalid():
+    s = '{"a": 1}\n{"a": oops}\n  {"a": 3}\n{"a": [4,\n{"a": 5}\n'
+    it = hyperjson.loads_iter(s, skip_invalid=True)
+    assert list(it) == [{"a": 1}, {"a": 3}, {"a": 5}]
+    assert it.skipped == [(2, 9, "expected value"), (4, 32, "EOF while parsing a list")]
+
+
+def test_skip_invalid_offsets_count_bytes():
+    s = '"ü"\n{oops}\n'
+    it = hyperjson.loads_iter(s, skip_invalid=True)
+    assert list(it) == ["ü"]
+    assert it.skipped[0][:2] == (2, 5)
+
+
+def test_skip_invalid_truncated_last_record():
+    it = hyperjson.loads_iter('1\n{"a": ', skip_invalid=True)
+    assert list(it) == [1]
+    assert [(lineno, offset) for lineno, offset, _ in it.skipped] == [(2, 2)]
+
+
+@pytest.mark.parametrize("binary", [True, False])
+@pytest.mark.parametrize("chunk_size", [1, 7, 64 * 1024])
+def test_load_iter(binary, chunk_size):
+    fp = io.BytesIO(jsonl.encode()) if binary else io.StringIO(jsonl)
+    assert list(hyperjson.load_iter(fp, chunk_size=chunk_size)) == records
+
+
+def test_load_iter_numbers_across_chunks():
+    fp = io.BytesIO(b"123456789 987654321\n-1.5e10")
+    assert list(hyperjson.load_iter(fp, chunk_size=2)) == [123456789, 987654321, -1.5e10]
+
+
+def test_load_iter_large_record_across_chunks():
+    calls = []
+
+    def parse_float(s):
+        calls.append(s)
+        return float(s)
+
+    values = [0.5] * 20000
+    fp = io.BytesIO(("1.5\n%s\n2.5" % json.dumps(values)).encode())
+    assert list(hyperjson.load_iter(fp, chunk_size=64, parse_float=parse_float)) == [1.5, values, 2.5]
+    # Values are only built once they are complete
+    assert len(calls) == len(values) + 2
+
+
+def test_load_iter_hooks_run_once():
+    calls = []
+
+    def parse_float(s):
+        calls.append(s)
+        return float(s)
+
+    fp = io.BytesIO(b"1.5 [2.5,\n3.5] 4.5")
+    assert list(hyperjson.load_iter(fp, chunk_size=4, parse_float=parse_float)) == [1.5, [2.5, 3.5], 4.5]
+    assert calls == ["1.5", "2.5", "3.5", "4.5"]
+
+
+def test_loads_iter_python_errors():
+    def parse_float(s):
+        raise ArithmeticError(s)
+
+    it = hyperjson.loads_iter("1\n2.5\n3\n", parse_float=parse_float)
+    assert next(it) == 1
+    with pytest.raises(ValueError):
+        next(it)
+    it = hyperjson.loads_iter("1\n2.5\n3\n", parse_float=parse_float, skip_invalid=True)
+    assert list(it) == [1, 3]
+    assert [(lineno, offset) for lineno, offset, _ in it.skipped] == [(2, 2)]
+
+
+def test_load_iter_skip_invalid():
+    data = '{"a": 1}\n{"a": oops, "long": "%s"}\n{"a": 3}\n' % ("x" * 100)
+    it = hyperjson.load_iter(io.StringIO(data), skip_invalid=True, chunk_size=8)
+    assert list(it) == [{"a": 1}, {"a": 3}]
+    assert [(lineno, offset) for lineno, offset, _ in it.skipped] == [(2, 9)]
+
+
+def test_load_iter_reads_lazily():
+    class Lines:
+        def __init__(self):
+            self.reads = 0
+
+        def read(self, n):
+            self.reads += 1
+            return "[%d]\n" % self.reads
+
+    fp = Lines()
+    it = hyperjson.load_iter(fp)
+    assert [next(it) for _ in range(3)] == [[1], [2], [3]]
+    assert fp.reads < 10
+
+
+def test_load_iter_propagates_read_errors():
+    class Broken:
+        def read(self, n):
+            raise OSError("broken")
+
+    with pytest.raises(OSError):
+        list(hyperjson.load_iter(Broken()))
+
+
+def test_load_iter_chunk_size():
+    with pytest.raises(ValueError):
+        hyperjson.load_iter(io.StringIO("1"), chunk_size=0)
+
+
+def test_iterator_type_cannot_be_called():
+    with pytest.raises(TypeError):
+        type(hyperjson.loads_iter("1"))()
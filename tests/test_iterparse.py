@@ -0,0 +1,130 @@
+import io
+import json
+
+import pytest
+import hyperjson
+
+doc = {
+    "meta": {"count": 3, "ok": True, "none": None},
+    "item": {"records": [{"id": 1, "tags": ["a"]}, {"id": 2, "x": 1.5}, 3]},
+    "list": [[1, 2], [], {}],
+}
+
+
+def events(data, **kwargs):
+    return list(hyperjson.iterparse(io.BytesIO(data.encode()), **kwargs))
+
+
+def test_events():
+    assert events('{"a": [1, "x", null, true, 1.5], "b": {}}') == [
+        ("", "start_map", None),
+        ("", "map_key", "a"),
+        ("a", "start_array", None),
+        ("a.item", "number", 1),
+        ("a.item", "string", "x"),
+        ("a.item", "null", None),
+        ("a.item", "boolean", True),
+        ("a.item", "number", 1.5),
+        ("a", "end_array", None),
+        ("", "map_key", "b"),
+        ("b", "start_map", None),
+        ("b", "end_map", None),
+        ("", "end_map", None),
+    ]
+
+
+def test_nested_prefixes():
+    assert events('[{"k": [[0]]}]') == [
+        ("", "start_array", None),
+        ("item", "start_map", None),
+        ("item", "map_key", "k"),
+        ("item.k", "start_array", None),
+        ("item.k.item", "start_array", None),
+        ("item.k.item.item", "number", 0),
+        ("item.k.item", "end_array", None),
+        ("item.k", "end_array", None),
+        ("item", "end_map", None),
+        ("", "end_array", None),
+    ]
+
+
+def test_scalar_document():
+    assert events(' "a\\u00fc" ') == [("", "string", "aü")]
+    assert events("12") == [("", "number", 12)]
+    assert events("") == []
+
+
+def test_small_chunks():
+    data = json.dumps(doc, indent=2)
+    expected = events(data)
+    for chunk_size in (1, 2, 3, 7):
+        assert events(data, chunk_size=chunk_size) == expected
+
+
+def test_text_file():
+    fp = io.StringIO('["ü", {"ü": 1}]')
+    assert [event[2] for event in hyperjson.iterparse(fp, chunk_size=1)] == [None, "ü", None, "ü", 1, None, None]
+
+
+def test_parse_float():
+    assert events("[1.5]", parse_float=str)[1] == ("item", "number", "1.5")
+
+
+@pytest.mark.parametrize(
+    "data",
+    ["[1,]", "[1 2]", '{"a" 1}', "{1: 2}", "[tru]", "[01]", "[1.]", "[-]", '["a', "[1", "1 2", "]", '{"a": 1]'],
+)
+def test_invalid(data):
+    with pytest.raises(json.JSONDecodeError):
+        events(data)
+
+
+def test_error_offset():
+    with pytest.raises(json.JSONDecodeError, match="byte offset 6"):
+        events("[1, 2 3]", chunk_size=2)
+
+
+def test_items():
+    fp = io.BytesIO(json.dumps(doc).encode())
+    assert list(hyperjson.items(fp, "item.records.item")) == doc["item"]["records"]
+
+
+def test_items_of_top_level_array():
+    data = json.dumps([{"id": i, "name": "ü" * i} for i in range(100)])
+    for chunk_size in (1, 5, 65536):
+        fp = io.BytesIO(data.encode())
+        assert list(hyperjson.items(fp, "item", chunk_size=chunk_size)) == json.loads(data)
+
+
+def test_items_matches_several_places():
+    fp = io.StringIO('[{"a": 1}, {"b": {"a": [2]}}, {"a": {"c": 3}}]')
+    assert list(hyperjson.items(fp, "item.a")) == [1, {"c": 3}]
+
+
+def test_items_root():
+    fp = io.StringIO(json.dumps(doc))
+    assert list(hyperjson.items(fp, "")) == [doc]
+
+
+def test_items_no_match():
+    assert list(hyperjson.items(io.StringIO(json.dumps(doc)), "nope")) == []
+
+
+def test_items_invalid():
+    with pytest.raises(json.JSONDecodeError):
+        list(hyperjson.items(io.StringIO('[{"a": 1}, {"a": }]'), "item"))
+    it = hyperjson.items(io.StringIO('[1, {"a": 1}, 2 x'), "item")
+    assert [next(it), next(it), next(it)] == [1, {"a": 1}, 2]
+    with pytest.raises(json.JSONDecodeError):
+        next(it)
+    with pytest.raises(StopIteration):
+        next(it)
+
+
+def test_bom():
+    assert events("﻿[1]") == [("", "start_array", None), ("item", "number", 1), ("", "end_array", None)]
+
+
+def test_chunk_size_zero():
+    with pytest.raises(ValueError):
+        hyperjson.iterparse(io.BytesIO(b"[]"), chunk_size=0)
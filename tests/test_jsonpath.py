@@ -0,0 +1,227 @@
+import json
+
+import pytest
+import hyperjson
+
+# The example document of RFC 9535, section 1.5
+store = {
+    "store": {
+        "book": [
+            {"category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95},
+            {"category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99},
+            {
+                "category": "fiction",
+                "author": "Herman Melville",
+                "title": "Moby Dick",
+                "isbn": "0-553-21311-3",
+                "price": 8.99,
+            },
+            {
+                "category": "fiction",
+                "author": "J. R. R. Tolkien",
+                "title": "The Lord of the Rings",
+                "isbn": "0-395-19395-8",
+                "price": 22.99,
+            },
+        ],
+        "bicycle": {"color": "red", "price": 399},
+    }
+}
+books = store["store"]["book"]
+
+
+def canonical(values):
+    # Object members may come in any order
+    return sorted(json.dumps(value, sort_keys=True) for value in values)
+
+
+def query(doc, path):
+    """Runs the query on the decoded document and on the raw JSON, which
+    must agree."""
+    decoded = hyperjson.query(doc, path)
+    assert canonical(hyperjson.query(json.dumps(doc), path)) == canonical(decoded)
+    assert canonical(hyperjson.query(json.dumps(doc).encode(), path)) == canonical(decoded)
+    return decoded
+
+
+@pytest.mark.parametrize(
+    "path, expected",
+    [
+        ("$.store.book[*].author", [b["author"] for b in books]),
+        ("$..author", [b["author"] for b in books]),
+        ("$.store.*", [books, store["store"]["bicycle"]]),
+        ("$.store..price", [8.95, 12.99, 8.99, 22.99, 399]),
+        ("$..book[2]", [books[2]]),
+        ("$..book[2].author", ["Herman Melville"]),
+        ("$..book[2].publisher", []),
+        ("$..book[-1]", [books[3]]),
+        ("$..book[0,1]", books[:2]),
+        ("$..book[:2]", books[:2]),
+        ("$..book[?@.isbn]", books[2:]),
+        ("$..book[?@.price<10]", [books[0], books[2]]),
+        ("$.store.book[?(@.price < 10)].title", ["Sayings of the Century", "Moby Dick"]),
+    ],
+)
+def test_rfc_examples(path, expected):
+    assert query(store, path) == expected
+
+
+def test_descendants_of_everything():
+    assert len(query(store, "$..*")) == 27
+
+
+def test_root():
+    assert query(store, "$") == [store]
+    assert query(1, "$") == [1]
+
+
+def test_names():
+    doc = {"a b": 1, "ü": 2, "'": 3, "o": {"j": 4}}
+    assert query(doc, "$['a b']") == [1]
+    assert query(doc, '$["a b", "ü"]') == [1, 2]
+    assert query(doc, "$.ü") == [2]
+    assert query(doc, "$['\\'']") == [3]
+    assert query(doc, '$["\\u00fc"]') == [2]
+    assert query(doc, "$.o['j']") == [4]
+    assert query(doc, "$.o[ 'j' ]") == [4]
+    assert query(doc, "$ .o .j") == [4]
+
+
+def test_names_do_not_index_arrays():
+    assert query(["x"], "$['0']") == []
+    assert query({"0": "x"}, "$[0]") == []
+
+
+@pytest.mark.parametrize(
+    "path, expected",
+    [
+        ("$[1:3]", ["b", "c"]),
+        ("$[5:]", ["f", "g"]),
+        ("$[1:5:2]", ["b", "d"]),
+        ("$[5:1:-2]", ["f", "d"]),
+        ("$[::-1]", ["g", "f", "e", "d", "c", "b", "a"]),
+        ("$[-2:]", ["f", "g"]),
+        ("$[::0]", []),
+        ("$[-100:100]", list("abcdefg")),
+        ("$[0, 0, -1]", ["a", "a", "g"]),
+        ("$[7]", []),
+        ("$[-8]", []),
+    ],
+)
+def test_slices_and_indexes(path, expected):
+    assert query(list("abcdefg"), path) == expected
+
+
+@pytest.mark.parametrize(
+    "path, expected",
+    [
+        ("$[?@.a == 'b']", [{"a": "b"}]),
+        ("$[?@.a == 1]", [{"a": 1}, {"a": 1.0}]),
+        ("$[?@.a != 1]", [{"a": "b"}, {"a": True}, {"a": None}, {"a": [1]}, {"b": 1}]),
+        ("$[?@.a == true]", [{"a": True}]),
+        ("$[?@.a == null]", [{"a": None}]),
+        ("$[?@.a == $[5].a]", [{"a": [1]}]),
+        ("$[?@.a > 0]", [{"a": 1}, {"a": 1.0}]),
+        ("$[?@.a >= 'a']", [{"a": "b"}]),
+        ("$[?@.a]", [{"a": "b"}, {"a": 1}, {"a": 1.0}, {"a": True}, {"a": None}, {"a": [1]}]),
+        ("$[?!@.a]", [{"b": 1}]),
+        ("$[?@.a == @.missing]", [{"b": 1}]),
+        ("$[?@.missing == @.other]", [{"a": "b"}, {"a": 1}, {"a": 1.0}, {"a": True}, {"a": None}, {"a": [1]}, {"b": 1}]),
+        ("$[?@.b == 1 || @.a == 'b']", [{"a": "b"}, {"b": 1}]),
+        ("$[?(@.a == 1 || @.b) && !(@.a == 1.0)]", [{"b": 1}]),
+        ("$[?@.a < true]", []),
+    ],
+)
+def test_filters(path, expected):
+    doc = [{"a": "b"}, {"a": 1}, {"a": 1.0}, {"a": True}, {"a": None}, {"a": [1]}, {"b": 1}]
+    assert query(doc, path) == expected
+
+
+def test_filter_on_object_members():
+    assert query({"x": {"k": 1}, "y": {"k": 2}}, "$[?@.k > 1]") == [{"k": 2}]
+
+
+def test_filter_refers_to_root():
+    doc = {"limit": 10, "items": [5, 15, 10]}
+    assert query(doc, "$.items[?@ >= $.limit]") == [15, 10]
+
+
+@pytest.mark.parametrize(
+    "path, expected",
+    [
+        ("$[?length(@.s) == 3]", [{"s": "abc", "l": [1]}]),
+        ("$[?length(@.l) == 2]", [{"s": "x", "l": [1, 2]}]),
+        ("$[?count(@.l[*]) == 1]", [{"s": "abc", "l": [1]}]),
+        ("$[?match(@.s, 'a.c')]", [{"s": "abc", "l": [1]}]),
+        ("$[?match(@.s, 'b')]", []),
+        ("$[?search(@.s, 'b')]", [{"s": "abc", "l": [1]}]),
+        ("$[?search(@.s, '[x]')]", [{"s": "x", "l": [1, 2]}]),
+        ("$[?value(@.l[0]) == 1]", [{"s": "abc", "l": [1]}, {"s": "x", "l": [1, 2]}]),
+        ("$[?value(@.l[*]) == 1]", [{"s": "abc", "l": [1]}]),
+        ("$[?match(@.s, '[')]", []),
+    ],
+)
+def test_functions(path, expected):
+    assert query([{"s": "abc", "l": [1]}, {"s": "x", "l": [1, 2]}], path) == expected
+
+
+def test_match_dot_excludes_line_breaks():
+    assert query(["a\nb", "a\rb", "a-b"], "$[?match(@, 'a.b')]") == ["a-b"]
+
+
+def test_length_counts_code_points():
+    assert query(["ü€😀", "abcd"], "$[?length(@) == 3]") == ["ü€😀"]
+
+
+@pytest.mark.parametrize(
+    "path",
+    [
+        "",
+        "store",
+        "$.",
+        "$..",
+        "$ ",
+        "$[",
+        "$[]",
+        "$[01]",
+        "$[-0]",
+        "$[9007199254740992]",
+        "$['a]",
+        "$['\\\"']",
+        "$['\\ud800']",
+        "$.1a",
+        "$[?@.a = 1]",
+        "$[?1]",
+        "$[?@.* == 1]",
+        "$[?@..a == 1]",
+        "$[?length(@.*) == 1]",
+        "$[?count(1) == 1]",
+        "$[?length(@)]",
+        "$[?match(@.a, 'x') == true]",
+        "$[?foo(@)]",
+        "$[?!@.a == 1]",
+        "$[?@.a == 01]",
+        "$[?@.a == 1.]",
+        "$[?(@.a]",
+    ],
+)
+def test_invalid_queries(path):
+    with pytest.raises(ValueError):
+        hyperjson.query(store, path)
+
+
+def test_raw_document_is_validated():
+    with pytest.raises(json.JSONDecodeError):
+        hyperjson.query('{"a": 1, "b": [}', "$.a")
+
+
+def test_raw_document_missing_prefix():
+    assert hyperjson.query('{"a": 1}', "$.b.c[*]") == []
+
+
+def test_tuples_are_arrays():
+    assert hyperjson.query({"a": (1, 2, 3)}, "$.a[-1]") == [3]
+
+
+def test_booleans_are_not_numbers():
+    assert hyperjson.query([True, 1, False, 0], "$[?@ == 1]") == [1]
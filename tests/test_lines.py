@@ -0,0 +1,159 @@
+import gc
+import io
+import json
+import sys
+
+import pytest
+import hyperjson
+
+records = [{"id": i, "text": "line\nbreak   ü", "tags": ["a", "b"]} for i in range(500)]
+
+
+def test_write():
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp) as writer:
+        for record in records:
+            writer.write(record)
+    lines = fp.getvalue().split("\n")
+    assert lines[-1] == ""
+    assert [json.loads(line) for line in lines[:-1]] == records
+
+
+def test_write_many():
+    fp = io.BytesIO()
+    with hyperjson.JsonLinesWriter(fp) as writer:
+        writer.write_many(iter(records))
+    assert fp.getvalue() == b"".join(hyperjson.dumps_bytes(r) + b"\n" for r in records)
+
+
+def test_write_binary_and_text():
+    text, binary = io.StringIO(), io.BytesIO()
+    for fp in (text, binary):
+        with hyperjson.JsonLinesWriter(fp, ensure_ascii=False) as writer:
+            writer.write({"a": "ü"})
+    assert text.getvalue() == '{"a":"ü"}\n'
+    assert binary.getvalue() == '{"a":"ü"}\n'.encode()
+
+
+def test_every_record_is_one_line():
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp, ensure_ascii=False) as writer:
+        writer.write_many(["\n", "\r\n", {"\n": [1, {"x": "\n"}]}, 1, None])
+    assert fp.getvalue().count("\n") == 5
+    assert fp.getvalue().endswith("\n") and not fp.getvalue().endswith("\n\n")
+
+
+def test_options():
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp, sort_keys=True, bytes_mode="base64", encode_html_chars=True) as writer:
+        writer.write({"b": b"\xff", "a": "</a>"})
+    assert fp.getvalue() == '{"a":"\\u003c\\/a\\u003e","b":"\\/w=="}\n'
+
+
+def test_indent_is_rejected():
+    with pytest.raises(ValueError):
+        hyperjson.JsonLinesWriter(io.StringIO(), indent=2)
+    hyperjson.JsonLinesWriter(io.StringIO(), indent=None)
+
+
+def test_unknown_option():
+    with pytest.raises(TypeError):
+        hyperjson.JsonLinesWriter(io.StringIO(), sort_key=True)
+
+
+def test_not_a_file():
+    with pytest.raises(AttributeError):
+        hyperjson.JsonLinesWriter("")
+
+
+def test_buffering():
+    fp = io.StringIO()
+    writer = hyperjson.JsonLinesWriter(fp, chunk_size=20)
+    writer.write([1])
+    assert fp.getvalue() == ""
+    writer.write("x" * 20)
+    assert fp.getvalue() == '[1]\n"%s"\n' % ("x" * 20)
+    writer.write(2)
+    writer.flush()
+    assert fp.getvalue().endswith("\n2\n")
+
+
+def test_failed_record_is_dropped():
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp) as writer:
+        writer.write(1)
+        with pytest.raises(TypeError):
+            writer.write([2, object()])
+        writer.write(3)
+    assert fp.getvalue() == "1\n3\n"
+
+
+def test_closed():
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp) as writer:
+        writer.write(1)
+    assert not fp.closed
+    with pytest.raises(ValueError):
+        writer.write(2)
+
+
+def test_exit_flushes_on_error():
+    fp = io.StringIO()
+    with pytest.raises(KeyError):
+        with hyperjson.JsonLinesWriter(fp) as writer:
+            writer.write(1)
+            raise KeyError()
+    assert fp.getvalue() == "1\n"
+
+
+def test_close():
+    fp = io.StringIO()
+    writer = hyperjson.JsonLinesWriter(fp)
+    writer.write(1)
+    writer.close()
+    writer.close()
+    assert fp.getvalue() == "1\n"
+    assert not fp.closed
+    with pytest.raises(ValueError):
+        writer.flush()
+
+
+def test_flushes_when_collected():
+    fp = io.StringIO()
+    writer = hyperjson.JsonLinesWriter(fp)
+    writer.write(1)
+    del writer
+    gc.collect()
+    assert fp.getvalue() == "1\n"
+
+
+def test_write_error_when_collected():
+    class Failing(io.StringIO):
+        def write(self, s):
+            raise OSError("disk full")
+
+    unraisable = []
+    hook, sys.unraisablehook = sys.unraisablehook, unraisable.append
+    try:
+        writer = hyperjson.JsonLinesWriter(Failing())
+        writer.write(1)
+        del writer
+        gc.collect()
+    finally:
+        sys.unraisablehook = hook
+    assert [type(u.exc_value) for u in unraisable] == [OSError]
+
+
+def test_roundtrip_with_load_iter():
+    fp = io.BytesIO()
+    with hyperjson.JsonLinesWriter(fp) as writer:
+        writer.write_many(records)
+    fp.seek(0)
+    assert list(hyperjson.load_iter(fp)) == records
+
+
+def test_class():
+    fp = io.StringIO()
+    writer = hyperjson.JsonLinesWriter(fp)
+    assert isinstance(writer, hyperjson.JsonLinesWriter)
+    assert isinstance(hyperjson.JsonLinesWriter, type)
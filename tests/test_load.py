@@ -5,8 +5,8 @@ import io
 
 
 def test_load():
-    obj = io.StringIO(u'["streaming API"]')
-    assert json.load(obj) == hyperjson.load(obj)
+    doc = u'["streaming API"]'
+    assert json.load(io.StringIO(doc)) == hyperjson.load(io.StringIO(doc))
 
 
 def test_load_invalid_reader():
@@ -0,0 +1,117 @@
+import io
+import os
+import socket
+import threading
+
+import pytest
+import hyperjson
+
+big = {"items": [{"id": i, "name": "item %d" % i, "tags": ["ü", "\U0001f600"]} for i in range(20000)]}
+big_doc = hyperjson.dumps(big, ensure_ascii=False)
+
+
+def test_load_does_not_rewind():
+    fp = io.StringIO('garbage["a", 1]')
+    fp.seek(7)
+    assert hyperjson.load(fp) == ["a", 1]
+
+
+def test_load_text_and_binary():
+    assert hyperjson.load(io.StringIO(big_doc)) == big
+    assert hyperjson.load(io.BytesIO(big_doc.encode())) == big
+    assert hyperjson.load(io.BufferedReader(io.BytesIO(big_doc.encode()))) == big
+
+
+def test_load_utf16():
+    assert hyperjson.load(io.BytesIO('{"a": "ü"}'.encode("utf-16"))) == {"a": "ü"}
+
+
+def test_load_utf8_bom():
+    assert hyperjson.load(io.BytesIO('{"a": "ü"}'.encode("utf-8-sig"))) == {"a": "ü"}
+
+
+class ReadOnly:
+    """A file object that only has read(n), handing out tiny chunks."""
+
+    def __init__(self, data):
+        self.data = data
+        self.calls = 0
+
+    def read(self, n=-1):
+        self.calls += 1
+        chunk, self.data = self.data[:3], self.data[3:]
+        return chunk
+
+
+def test_load_short_reads():
+    fp = ReadOnly(b'{"key": [1, 2.5, "value"]}')
+    assert hyperjson.load(fp) == {"key": [1, 2.5, "value"]}
+    assert fp.calls > 1
+
+
+def test_load_pipe():
+    r, w = os.pipe()
+
+    def writer():
+        with os.fdopen(w, "wb") as f:
+            f.write(big_doc.encode())
+
+    thread = threading.Thread(target=writer)
+    thread.start()
+    with os.fdopen(r, "rb", buffering=0) as f:
+        assert hyperjson.load(f) == big
+    thread.join()
+
+
+def test_load_socket():
+    a, b = socket.socketpair()
+    b.sendall(b'[1, 2, 3]')
+    b.close()
+    with a, a.makefile("rb") as f:
+        assert hyperjson.load(f) == [1, 2, 3]
+
+
+@pytest.mark.parametrize("doc", ["NaN", "Infinity", " -Infinity\n"])
+def test_load_special_floats(doc):
+    assert repr(hyperjson.load(io.StringIO(doc))) == repr(float(doc))
+
+
+@pytest.mark.parametrize("doc", ["", "[1,", '{"a": 1} x', "[1, 2]]"])
+def test_load_invalid(doc):
+    with pytest.raises(ValueError):
+        hyperjson.load(io.StringIO(doc))
+
+
+class FailingReader:
+    def __init__(self):
+        self.calls = 0
+
+    def read(self, n=-1):
+        self.calls += 1
+        if self.calls > 1:
+            raise OSError("connection reset")
+        return "[1, 2"
+
+
+def test_load_read_error_propagates():
+    with pytest.raises(OSError):
+        hyperjson.load(FailingReader())
+
+
+class ReadAll:
+    def __init__(self, data):
+        self.data = data
+
+    def read(self):
+        return self.data
+
+
+def test_load_read_without_size():
+    assert hyperjson.load(ReadAll('{"a": [1, 2]}')) == {"a": [1, 2]}
+
+
+def test_load_not_a_file():
+    with pytest.raises(AttributeError):
+        hyperjson.load(object())
+    with pytest.raises(TypeError):
+        hyperjson.load(ReadOnly([1, 2]))
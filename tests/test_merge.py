@@ -0,0 +1,131 @@
+import copy
+
+import pytest
+import hyperjson
+
+
+# The examples of RFC 7396, appendix A
+@pytest.mark.parametrize(
+    "target, patch, expected",
+    [
+        ({"a": "b"}, {"a": "c"}, {"a": "c"}),
+        ({"a": "b"}, {"b": "c"}, {"a": "b", "b": "c"}),
+        ({"a": "b"}, {"a": None}, {}),
+        ({"a": "b", "b": "c"}, {"a": None}, {"b": "c"}),
+        ({"a": ["b"]}, {"a": "c"}, {"a": "c"}),
+        ({"a": "c"}, {"a": ["b"]}, {"a": ["b"]}),
+        ({"a": {"b": "c"}}, {"a": {"b": "d", "c": None}}, {"a": {"b": "d"}}),
+        ({"a": [{"b": "c"}]}, {"a": [1]}, {"a": [1]}),
+        (["a", "b"], ["c", "d"], ["c", "d"]),
+        ({"a": "b"}, ["c"], ["c"]),
+        ({"a": "foo"}, None, None),
+        ({"a": "foo"}, "bar", "bar"),
+        ({"e": None}, {"a": 1}, {"e": None, "a": 1}),
+        ([1, 2], {"a": "b", "c": None}, {"a": "b"}),
+        ({}, {"a": {"bb": {"ccc": None}}}, {"a": {"bb": {}}}),
+    ],
+)
+def test_merge_patch(target, patch, expected):
+    before = copy.deepcopy((target, patch))
+    assert hyperjson.merge_patch(target, patch) == expected
+    assert (target, patch) == before
+
+
+def test_merge_patch_copies():
+    target = {"a": {"b": [1]}, "c": {"d": 1}}
+    patch = {"e": {"f": [2]}}
+    result = hyperjson.merge_patch(target, patch)
+    result["a"]["b"].append(3)
+    result["e"]["f"].append(3)
+    assert target == {"a": {"b": [1]}, "c": {"d": 1}}
+    assert patch == {"e": {"f": [2]}}
+
+
+def test_deep_merge_layers():
+    defaults = {"db": {"host": "localhost", "port": 5432}, "debug": False, "tags": ["a"]}
+    env = {"db": {"host": "db.internal"}, "tags": ["b"]}
+    user = {"debug": True, "db": {"user": None}}
+    before = copy.deepcopy((defaults, env, user))
+    assert hyperjson.deep_merge(defaults, env, user) == {
+        "db": {"host": "db.internal", "port": 5432, "user": None},
+        "debug": True,
+        "tags": ["b"],
+    }
+    assert (defaults, env, user) == before
+
+
+@pytest.mark.parametrize(
+    "arrays, expected",
+    [
+        ("replace", [{"id": 2, "b": 2}, 5]),
+        ("concat", [{"id": 1, "a": 1}, {"id": 2}, 3, {"id": 2, "b": 2}, 5]),
+        ("index", [{"id": 2, "a": 1, "b": 2}, 5, 3]),
+    ],
+)
+def test_deep_merge_arrays(arrays, expected):
+    base = {"x": [{"id": 1, "a": 1}, {"id": 2}, 3]}
+    other = {"x": [{"id": 2, "b": 2}, 5]}
+    assert hyperjson.deep_merge(base, other, arrays=arrays) == {"x": expected}
+
+
+def test_deep_merge_index():
+    assert hyperjson.deep_merge([{"a": 1}, 2], [{"b": 2}], arrays="index") == [{"a": 1, "b": 2}, 2]
+    assert hyperjson.deep_merge([1], [None, 2], arrays="index") == [None, 2]
+
+
+def test_deep_merge_key():
+    base = {"users": [{"id": 1, "name": "a"}, {"id": 2, "name": "b"}, "x"]}
+    other = {"users": [{"id": 2, "admin": True}, {"id": 3}, {"name": "c"}, "x"]}
+    assert hyperjson.deep_merge(base, other, arrays="key", key="id") == {
+        "users": [
+            {"id": 1, "name": "a"},
+            {"id": 2, "name": "b", "admin": True},
+            "x",
+            {"id": 3},
+            {"name": "c"},
+            "x",
+        ]
+    }
+    # key alone implies arrays='key'
+    assert hyperjson.deep_merge([{"id": 1, "a": 1}], [{"id": 1.0, "b": 1}], key="id") == [
+        {"id": 1.0, "a": 1, "b": 1}
+    ]
+    # Like in JSON, true is not 1
+    assert hyperjson.deep_merge([{"id": 1}], [{"id": True}], key="id") == [{"id": 1}, {"id": True}]
+
+
+def test_deep_merge_copies():
+    base = {"a": [{"b": 1}]}
+    other = {"c": {"d": [1]}}
+    result = hyperjson.deep_merge(base, other)
+    result["a"][0]["b"] = 2
+    result["c"]["d"].append(2)
+    assert base == {"a": [{"b": 1}]}
+    assert other == {"c": {"d": [1]}}
+    single = hyperjson.deep_merge(base)
+    assert single == base and single is not base
+
+
+def test_deep_merge_scalars():
+    assert hyperjson.deep_merge({"a": {"b": 1}}, {"a": 1}) == {"a": 1}
+    assert hyperjson.deep_merge({"a": 1}, {"a": {"b": 1}}) == {"a": {"b": 1}}
+    assert hyperjson.deep_merge([1, 2], (3,), arrays="concat") == [1, 2, 3]
+
+
+@pytest.mark.parametrize(
+    "kwargs",
+    [{"arrays": "zip"}, {"arrays": "key"}, {"arrays": "concat", "key": "id"}],
+)
+def test_deep_merge_invalid(kwargs):
+    with pytest.raises(ValueError):
+        hyperjson.deep_merge({}, {}, **kwargs)
+
+
+def test_deep_merge_unexpected_keyword():
+    with pytest.raises(TypeError):
+        hyperjson.deep_merge({}, {}, array="concat")
+
+
+def test_deep_merge_no_layers():
+    with pytest.raises(TypeError):
+        hyperjson.deep_merge()
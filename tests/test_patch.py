@@ -0,0 +1,196 @@
+import json
+
+import pytest
+import hyperjson
+from hyperjson import patch
+from hyperjson.patch import JsonPatchError
+
+
+def test_import():
+    import hyperjson.patch
+
+    assert hyperjson.patch.apply is patch.apply
+    assert issubclass(JsonPatchError, ValueError)
+
+
+# The examples of RFC 6902, appendix A
+@pytest.mark.parametrize(
+    "doc, ops, expected",
+    [
+        ({"foo": "bar"}, [{"op": "add", "path": "/baz", "value": "qux"}],
+         {"baz": "qux", "foo": "bar"}),
+        ({"foo": ["bar", "baz"]}, [{"op": "add", "path": "/foo/1", "value": "qux"}],
+         {"foo": ["bar", "qux", "baz"]}),
+        ({"baz": "qux", "foo": "bar"}, [{"op": "remove", "path": "/baz"}],
+         {"foo": "bar"}),
+        ({"foo": ["bar", "qux", "baz"]}, [{"op": "remove", "path": "/foo/1"}],
+         {"foo": ["bar", "baz"]}),
+        ({"baz": "qux", "foo": "bar"},
+         [{"op": "replace", "path": "/baz", "value": "boo"}],
+         {"baz": "boo", "foo": "bar"}),
+        ({"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}},
+         [{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}],
+         {"foo": {"bar": "baz"}, "qux": {"corge": "grault", "thud": "fred"}}),
+        ({"foo": ["all", "grass", "cows", "eat"]},
+         [{"op": "move", "from": "/foo/1", "path": "/foo/3"}],
+         {"foo": ["all", "cows", "eat", "grass"]}),
+        ({"baz": "qux", "foo": ["a", 2, "c"]},
+         [{"op": "test", "path": "/baz", "value": "qux"},
+          {"op": "test", "path": "/foo/1", "value": 2}],
+         {"baz": "qux", "foo": ["a", 2, "c"]}),
+        ({"foo": "bar"}, [{"op": "add", "path": "/child", "value": {"grandchild": {}}}],
+         {"foo": "bar", "child": {"grandchild": {}}}),
+        ({"foo": ["bar"]}, [{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}],
+         {"foo": ["bar", ["abc", "def"]]}),
+        ({"foo": 1}, [{"op": "copy", "from": "/foo", "path": "/bar"}],
+         {"foo": 1, "bar": 1}),
+        ({"foo": 1}, [{"op": "replace", "path": "", "value": [1]}], [1]),
+        ({"~/": 1}, [{"op": "remove", "path": "/~0~1"}], {}),
+    ],
+)
+def test_apply(doc, ops, expected):
+    before = json.dumps(doc)
+    assert patch.apply(doc, ops) == expected
+    # The document is copied
+    assert json.dumps(doc) == before
+
+
+@pytest.mark.parametrize(
+    "doc, ops",
+    [
+        ({"foo": "bar"}, [{"op": "test", "path": "/baz", "value": "bar"}]),
+        ({"foo": "bar"}, [{"op": "test", "path": "/foo", "value": "baz"}]),
+        ({"foo": [1]}, [{"op": "test", "path": "/foo", "value": [1.5]}]),
+        ({"foo": "bar"}, [{"op": "remove", "path": "/baz"}]),
+        ({"foo": "bar"}, [{"op": "replace", "path": "/baz", "value": 1}]),
+        ({"foo": "bar"}, [{"op": "add", "path": "/baz/bat", "value": "qux"}]),
+        ({"foo": [1]}, [{"op": "add", "path": "/foo/2", "value": 1}]),
+        ({"foo": [1]}, [{"op": "add", "path": "/foo/01", "value": 1}]),
+        ({"foo": [1]}, [{"op": "remove", "path": "/foo/-"}]),
+        ({"foo": {}}, [{"op": "move", "from": "/foo", "path": "/foo/bar"}]),
+        ({"foo": 1}, [{"op": "remove", "path": ""}]),
+        ({"foo": 1}, [{"op": "frobnicate", "path": "/foo"}]),
+        ({"foo": 1}, [{"op": "add", "path": "/bar"}]),
+        ({"foo": 1}, [{"op": "add", "path": "bar", "value": 1}]),
+        ({"foo": 1}, [{"path": "/foo"}]),
+        ({"foo": 1}, ["add"]),
+    ],
+)
+def test_apply_error(doc, ops):
+    with pytest.raises(JsonPatchError):
+        patch.apply(doc, ops)
+
+
+def test_apply_error_message():
+    with pytest.raises(JsonPatchError, match='operation 1: "/a/b" does not exist'):
+        patch.apply({"a": {}}, [{"op": "add", "path": "/x", "value": 1},
+                                {"op": "remove", "path": "/a/b"}])
+
+
+def test_apply_in_place():
+    doc = {"a": [1, 2]}
+    result = patch.apply(doc, [{"op": "add", "path": "/a/-", "value": 3}], in_place=True)
+    assert result is doc
+    assert doc == {"a": [1, 2, 3]}
+
+
+def test_apply_rolls_back():
+    doc = {"a": [1, 2, 3], "b": {"c": 1}, "d": "e"}
+    ops = [
+        {"op": "add", "path": "/a/1", "value": 9},
+        {"op": "remove", "path": "/a/0"},
+        {"op": "replace", "path": "/a/0", "value": 8},
+        {"op": "move", "from": "/b/c", "path": "/x"},
+        {"op": "copy", "from": "/d", "path": "/b/d"},
+        {"op": "remove", "path": "/d"},
+        {"op": "test", "path": "/x", "value": 2},
+    ]
+    with pytest.raises(JsonPatchError, match="operation 6"):
+        patch.apply(doc, ops, in_place=True)
+    assert doc == {"a": [1, 2, 3], "b": {"c": 1}, "d": "e"}
+
+
+def test_apply_copies_values():
+    value = {"b": 1}
+    result = patch.apply({}, [{"op": "add", "path": "/a", "value": value}])
+    result["a"]["b"] = 2
+    assert value == {"b": 1}
+
+
+def test_apply_json():
+    assert patch.apply('{"a": 1}', '[{"op": "add", "path": "/b", "value": 2}]') == '{"a":1,"b":2}'
+    assert patch.apply(b'[1]', [{"op": "add", "path": "/0", "value": 0}]) == b"[0,1]"
+    with pytest.raises(ValueError):
+        patch.apply("{", [])
+
+
+def test_apply_tuple():
+    assert patch.apply({"a": (1, 2)}, [{"op": "add", "path": "/a/-", "value": 3}]) == {"a": [1, 2, 3]}
+    assert patch.apply({"a": (1, 2)}, [{"op": "test", "path": "/a/1", "value": 2}])
+    with pytest.raises(JsonPatchError):
+        patch.apply({"a": (1, 2)}, [{"op": "remove", "path": "/a/0"}], in_place=True)
+
+
+@pytest.mark.parametrize(
+    "a, b",
+    [
+        ({}, {}),
+        ({"a": 1}, {"a": 1.0}),
+        ({"a": 1}, {"a": 2}),
+        ({"a": 1, "b": 2}, {"b": 3, "c": 4}),
+        ({"a": {"b": [1, 2]}}, {"a": {"b": [1, 3]}}),
+        ([1, 2, 3], [1, 2, 3, 4]),
+        ([1, 2, 3], [0, 1, 2, 3]),
+        ([1, 2, 3, 4, 5], [1, 3, 5]),
+        ([1, 2, 3], [3, 2, 1]),
+        ([1, 2, 3], []),
+        ([], [1, 2]),
+        ([{"id": 1, "n": "a"}, {"id": 2}], [{"id": 1, "n": "b"}, {"id": 2}]),
+        ({"a/b": 1, "~": 2}, {"a/b": 2}),
+        ({"a": [1]}, {"a": {"0": 1}}),
+        (1, ["1"]),
+        (True, 1),
+        (None, {}),
+        (list(range(2000)), list(range(1000, 3000))),
+    ],
+)
+def test_diff(a, b):
+    ops = patch.diff(a, b)
+    assert patch.apply(a, ops) == b
+    assert json.loads(json.dumps(ops)) == ops
+
+
+@pytest.mark.parametrize(
+    "a, b, expected",
+    [
+        ({"a": 1}, {"a": 1}, []),
+        ({"a": 1}, {"a": 2}, [{"op": "replace", "path": "/a", "value": 2}]),
+        ({"a": 1}, {}, [{"op": "remove", "path": "/a"}]),
+        ({}, {"a/b": 1}, [{"op": "add", "path": "/a~1b", "value": 1}]),
+        ([1, 2, 3], [1, 3], [{"op": "remove", "path": "/1"}]),
+        ([1, 3], [1, 2, 3], [{"op": "add", "path": "/1", "value": 2}]),
+        ([{"a": 1, "b": 2}], [{"a": 1, "b": 3}], [{"op": "replace", "path": "/0/b", "value": 3}]),
+        (1, 2, [{"op": "replace", "path": "", "value": 2}]),
+    ],
+)
+def test_diff_minimal(a, b, expected):
+    assert patch.diff(a, b) == expected
+
+
+@pytest.mark.parametrize(
+    "a, b, count",
+    [
+        ([1, 2, 3, 4, 5], [5, 4, 3, 2, 1], 4),
+        ([1, 2, 3], [4, 5, 6, 7], 4),
+        ([0, 1, 2, 3], [1, 2, 3, 4], 2),
+        ([1, 2, 3, 4], [2, 1, 4, 3], 3),
+    ],
+)
+def test_diff_op_count(a, b, count):
+    ops = patch.diff(a, b)
+    assert len(ops) == count
+    assert patch.apply(a, ops) == b
+
+
+def test_diff_json():
+    assert patch.diff('{"a": [1, 2]}', b'{"a": [1]}') == [{"op": "remove", "path": "/a/1"}]
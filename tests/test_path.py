@@ -0,0 +1,93 @@
+import os
+import pathlib
+import stat
+import tempfile
+
+import pytest
+import hyperjson
+
+big = {"items": [{"id": i, "name": "item %d" % i} for i in range(50000)]}
+
+
+@pytest.fixture
+def tmp():
+    with tempfile.TemporaryDirectory() as d:
+        yield pathlib.Path(d)
+
+
+def test_roundtrip(tmp):
+    path = tmp / "doc.json"
+    hyperjson.dump_path({"a": [1, "ü"]}, path)
+    assert path.read_bytes() == '{"a":[1,"ü"]}'.encode()
+    assert hyperjson.load_path(path) == {"a": [1, "ü"]}
+
+
+def test_path_types(tmp):
+    path = tmp / "doc.json"
+    hyperjson.dump_path([1], str(path))
+    assert hyperjson.load_path(os.fsencode(path)) == [1]
+    assert hyperjson.load_path(path) == [1]
+
+
+def test_large_file_is_mapped(tmp):
+    path = tmp / "big.json"
+    hyperjson.dump_path(big, path, indent=2)
+    assert path.stat().st_size > 1024 * 1024
+    assert hyperjson.load_path(path) == big
+
+
+def test_load_options(tmp):
+    path = tmp / "doc.json"
+    path.write_bytes('{"a": 1.5, "b": "\\ud800"}'.encode("utf-16"))
+    result = hyperjson.load_path(path, parse_float=str, surrogates="escape")
+    assert result == {"a": "1.5", "b": "\ud800"}
+
+
+def test_dump_replaces_atomically(tmp):
+    path = tmp / "config.json"
+    hyperjson.dump_path({"version": 1}, path)
+    with pytest.raises(TypeError):
+        hyperjson.dump_path({"version": 2, "bad": object()}, path)
+    assert hyperjson.load_path(path) == {"version": 1}
+    assert os.listdir(tmp) == ["config.json"]
+    hyperjson.dump_path({"version": 2}, path)
+    assert hyperjson.load_path(path) == {"version": 2}
+    assert os.listdir(tmp) == ["config.json"]
+
+
+def test_dump_keeps_permissions(tmp):
+    path = tmp / "config.json"
+    path.write_text("{}")
+    os.chmod(path, 0o640)
+    hyperjson.dump_path([], path)
+    assert stat.S_IMODE(path.stat().st_mode) == 0o640
+
+
+def test_dump_relative_path(tmp):
+    cwd = os.getcwd()
+    os.chdir(tmp)
+    try:
+        hyperjson.dump_path([1, 2], "relative.json")
+        assert hyperjson.load_path("relative.json") == [1, 2]
+    finally:
+        os.chdir(cwd)
+
+
+def test_missing_file(tmp):
+    with pytest.raises(FileNotFoundError) as excinfo:
+        hyperjson.load_path(tmp / "missing.json")
+    assert excinfo.value.filename == str(tmp / "missing.json")
+    with pytest.raises(FileNotFoundError):
+        hyperjson.dump_path([], tmp / "missing" / "doc.json")
+
+
+def test_invalid_json(tmp):
+    path = tmp / "doc.json"
+    path.write_text("[1, 2")
+    with pytest.raises(ValueError):
+        hyperjson.load_path(path)
+
+
+def test_invalid_path():
+    with pytest.raises(TypeError):
+        hyperjson.load_path(1)
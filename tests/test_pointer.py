@@ -0,0 +1,89 @@
+import json
+
+import pytest
+import hyperjson
+
+# The example document of RFC 6901, section 5
+doc = {
+    "foo": ["bar", "baz"],
+    "": 0,
+    "a/b": 1,
+    "c%d": 2,
+    "e^f": 3,
+    "g|h": 4,
+    "i\\j": 5,
+    'k"l': 6,
+    " ": 7,
+    "m~n": 8,
+}
+s = json.dumps(doc)
+
+
+@pytest.mark.parametrize(
+    "pointer, expected",
+    [
+        ("", doc),
+        ("/foo", ["bar", "baz"]),
+        ("/foo/0", "bar"),
+        ("/", 0),
+        ("/a~1b", 1),
+        ("/c%d", 2),
+        ("/e^f", 3),
+        ("/g|h", 4),
+        ("/i\\j", 5),
+        ('/k"l', 6),
+        ("/ ", 7),
+        ("/m~0n", 8),
+    ],
+)
+def test_rfc_examples(pointer, expected):
+    assert hyperjson.get_pointer(s, pointer) == expected
+
+
+def test_bytes_and_buffers():
+    data = json.dumps({"a": {"b": [1, 2, {"c": "ü"}]}}).encode()
+    for doc in (data, bytearray(data), memoryview(data)):
+        assert hyperjson.get_pointer(doc, "/a/b/2/c") == "ü"
+
+
+@pytest.mark.parametrize("pointer", ["/nope", "/foo/2", "/foo/-", "/foo/01", "/foo/x", "/foo/0/x", "/ /x", "/a~1b/0"])
+def test_missing(pointer):
+    with pytest.raises(KeyError):
+        hyperjson.get_pointer(s, pointer)
+    assert hyperjson.get_pointer(s, pointer, None) is None
+    assert hyperjson.get_pointer(s, pointer, default=42) == 42
+
+
+def test_numeric_keys_in_objects():
+    assert hyperjson.get_pointer('{"0": "x"}', "/0") == "x"
+
+
+def test_duplicate_keys():
+    assert hyperjson.get_pointer('{"a": 1, "a": 2}', "/a") == 2
+
+
+@pytest.mark.parametrize("pointer", ["a", "/~", "/~2"])
+def test_invalid_pointer(pointer):
+    with pytest.raises(ValueError):
+        hyperjson.get_pointer(s, pointer)
+
+
+def test_rest_of_document_is_checked():
+    with pytest.raises(json.JSONDecodeError):
+        hyperjson.get_pointer('{"a": 1, "b": [}', "/a")
+    with pytest.raises(json.JSONDecodeError):
+        hyperjson.get_pointer('{"a": 1} x', "/a")
+
+
+def test_not_a_document():
+    with pytest.raises(TypeError):
+        hyperjson.get_pointer({"a": 1}, "/a")
+
+
+def test_arguments():
+    with pytest.raises(TypeError):
+        hyperjson.get_pointer(s, "/x", 1, 2)
+    with pytest.raises(TypeError):
+        hyperjson.get_pointer(s, "/x", 1, default=2)
+    with pytest.raises(TypeError):
+        hyperjson.get_pointer(s, "/x", defaults=2)
@@ -0,0 +1,352 @@
+import json
+
+import pytest
+import hyperjson
+from hyperjson import ValidationError, Validator
+
+
+def test_validation_error():
+    assert issubclass(ValidationError, ValueError)
+
+
+# Schemas with instances that are valid, then instances that are not
+cases = [
+    ({"type": "integer"}, [1, 1.0, -(10 ** 30)], [1.5, True, "a", None, [1]]),
+    ({"type": ["string", "null"]}, ["a", None], [0, False, {}]),
+    ({"type": "number"}, [1, 1.5, 10 ** 30], [True, "1"]),
+    ({"minLength": 2, "maxLength": 3}, ["ab", "💩💩", 5], ["a", "abcd"]),
+    ({"pattern": "^a+$"}, ["a", "aaa", 1], ["", "ab"]),
+    ({"pattern": "b"}, ["abc"], ["a"]),
+    ({"minimum": 1, "maximum": 3}, [1, 2.5, 3, "x"], [0, 3.5]),
+    ({"exclusiveMinimum": 1, "exclusiveMaximum": 3}, [2, 1.5], [1, 3]),
+    ({"maximum": 10 ** 30}, [10 ** 30], [10 ** 30 + 1]),
+    ({"multipleOf": 2}, [4, 4.0, 10 ** 30], [5, 4.5]),
+    ({"multipleOf": 0.0001}, [0.0075, 1], [0.00751]),
+    ({"multipleOf": 0.123456789}, [0], [1e308]),
+    ({"enum": [1, "a", [1], {"a": None}]}, [1, 1.0, "a", [1], {"a": None}], [True, [True], {}, 2]),
+    ({"const": {"a": [1, 2]}}, [{"a": [1, 2]}, {"a": (1, 2.0)}], [{"a": [2, 1]}, {"a": [True, 2]}]),
+    ({"const": False}, [False], [0]),
+    ({"required": ["a", "b"]}, [{"a": 1, "b": 2}, [1]], [{"a": 1}, {}]),
+    ({"minProperties": 1, "maxProperties": 2}, [{"a": 1}, "x"], [{}, {"a": 1, "b": 2, "c": 3}]),
+    ({"minItems": 1, "maxItems": 2}, [[1], (1, 2)], [[], [1, 2, 3]]),
+    ({"uniqueItems": True}, [[1, 2], [1, True], [{"a": 1}, {"a": 2}]], [[1, 1.0], [[1], [1]], [{"a": 1}, {"a": 1}]]),
+    ({"prefixItems": [{"type": "integer"}, {"type": "string"}]}, [[], [1], [1, "a", None]], [["a"], [1, 2]]),
+    ({"prefixItems": [True], "items": False}, [[], [1]], [[1, 2]]),
+    ({"items": {"type": "integer"}}, [[], [1, 2], (1, 2)], [[1, "a"], (1, "a")]),
+    ({"contains": {"type": "integer"}}, [[1], ["a", 1], {}], [[], ["a"]]),
+    ({"contains": {"type": "integer"}, "minContains": 2, "maxContains": 3}, [[1, 2], [1, "a", 2, 3]], [[1], [1, 2, 3, 4]]),
+    ({"contains": {"type": "integer"}, "minContains": 0}, [[], ["a"]], []),
+    ({"properties": {"a": {"type": "integer"}}}, [{"a": 1}, {"b": "x"}], [{"a": "x"}]),
+    ({"patternProperties": {"^x": {"type": "string"}}}, [{"xa": "s", "y": 1}], [{"xa": 1}]),
+    (
+        {"properties": {"a": True}, "patternProperties": {"^x": True}, "additionalProperties": {"type": "integer"}},
+        [{"a": "s", "xb": "s", "c": 1}],
+        [{"c": "s"}],
+    ),
+    ({"properties": {"a": True}, "additionalProperties": False}, [{"a": 1}, {}], [{"b": 1}]),
+    ({"propertyNames": {"maxLength": 2}}, [{"ab": 1}], [{"abc": 1}]),
+    ({"dependentRequired": {"a": ["b"]}}, [{}, {"b": 1}, {"a": 1, "b": 1}], [{"a": 1}]),
+    ({"dependentSchemas": {"a": {"required": ["b"]}}}, [{}, {"a": 1, "b": 1}], [{"a": 1}]),
+    ({"allOf": [{"type": "integer"}, {"minimum": 2}]}, [2], [1, "a"]),
+    ({"anyOf": [{"type": "string"}, {"minimum": 3}]}, ["a", 5], [1]),
+    ({"oneOf": [{"type": "integer"}, {"minimum": 3}]}, [1, 3.5], [5, 2.5]),
+    ({"not": {"type": "string"}}, [1], ["a"]),
+    ({"if": {"minimum": 10}, "then": {"multipleOf": 2}, "else": {"maximum": 3}}, [12, 2], [13, 5]),
+    ({"if": {"minimum": 10}}, [1, 12], []),
+    (True, [1, None], []),
+    (False, [], [1, None]),
+    ({"$defs": {"pos": {"type": "integer", "minimum": 0}}, "properties": {"n": {"$ref": "#/$defs/pos"}}}, [{"n": 1}], [{"n": -1}]),
+    ({"definitions": {"s": {"type": "string"}}, "items": {"$ref": "#/definitions/s"}}, [["a"]], [[1]]),
+    ({"$ref": "#/properties/a", "properties": {"a": {"type": "object"}}}, [{}], [1]),
+    ({"properties": {"a": {"$ref": "#"}}, "type": "object"}, [{"a": {"a": {}}}], [{"a": {"a": 1}}]),
+    (
+        {"$defs": {"a~b": {"type": "integer"}, "c%d": {"type": "string"}},
+         "properties": {"x": {"$ref": "#/$defs/a~0b"}, "y": {"$ref": "#/$defs/c%25d"}}},
+        [{"x": 1, "y": "s"}],
+        [{"x": "s"}, {"y": 1}],
+    ),
+    ({"x-custom": {"type": "string"}, "$ref": "#/x-custom"}, ["a"], [1]),
+    ({"$defs": {"s": {"$anchor": "s", "type": "string"}}, "$ref": "#s"}, ["a"], [1]),
+    (
+        {"$id": "https://example.com/root.json",
+         "$defs": {"a": {"$id": "nested/other.json", "$defs": {"b": {"$anchor": "bee", "type": "string"}}}},
+         "$ref": "nested/other.json#bee"},
+        ["a"],
+        [1],
+    ),
+    (
+        {"$id": "https://example.com/a/root.json",
+         "$defs": {"b": {"$id": "../b.json", "type": "string"}},
+         "$ref": "https://example.com/b.json"},
+        ["a"],
+        [1],
+    ),
+    (
+        {"$id": "https://example.com/root",
+         "$ref": "list",
+         "$defs": {
+             "foo": {"$dynamicAnchor": "items", "type": "string"},
+             "list": {"$id": "list", "type": "array", "items": {"$dynamicRef": "#items"},
+                      "$defs": {"items": {"$dynamicAnchor": "items"}}},
+         }},
+        [["a"], []],
+        [[1]],
+    ),
+    (
+        {"properties": {"foo": {"type": "string"}},
+         "allOf": [{"properties": {"bar": {"type": "string"}}}],
+         "unevaluatedProperties": False},
+        [{"foo": "a"}, {"foo": "a", "bar": "b"}],
+        [{"foo": "a", "baz": 1}],
+    ),
+    (
+        {"anyOf": [{"properties": {"a": True}}, {"properties": {"b": True}, "required": ["b"]}],
+         "unevaluatedProperties": False},
+        [{"a": 1}, {"a": 1, "b": 1}],
+        [{"c": 1}],
+    ),
+    (
+        {"allOf": [{"properties": {"foo": True}}, {"unevaluatedProperties": False}]},
+        [{}],
+        [{"foo": 1}],
+    ),
+    (
+        {"if": {"properties": {"a": {"const": 1}}, "required": ["a"]},
+         "then": {"properties": {"b": True}},
+         "unevaluatedProperties": False},
+        [{"a": 1, "b": 1}],
+        [{"a": 2}, {"a": 1, "c": 1}],
+    ),
+    (
+        {"properties": {"a": True}, "unevaluatedProperties": {"type": "integer"}},
+        [{"a": "s", "b": 1}],
+        [{"b": "s"}],
+    ),
+    (
+        {"prefixItems": [True], "allOf": [{"contains": {"type": "string"}}], "unevaluatedItems": {"type": "integer"}},
+        [[1, "a", 2]],
+        [[1, "a", 2.5]],
+    ),
+    ({"items": True, "unevaluatedItems": False}, [[1, 2]], []),
+    ({"unevaluatedItems": False}, [[]], [[1]]),
+    ({"format": "email", "title": "x", "unknown": 1}, ["not an email"], []),
+]
+
+
+@pytest.mark.parametrize("schema, valid, invalid", cases)
+def test_validator(schema, valid, invalid):
+    validator = Validator(schema)
+    for instance in valid:
+        assert validator.is_valid(instance), instance
+        assert validator.errors(instance) == []
+        validator.validate(instance)
+    for instance in invalid:
+        assert not validator.is_valid(instance), instance
+        assert validator.errors(instance)
+        with pytest.raises(ValidationError):
+            validator.validate(instance)
+
+
+@pytest.mark.parametrize("schema, valid, invalid", cases)
+def test_jsonschema(schema, valid, invalid):
+    jsonschema = pytest.importorskip("jsonschema")
+    reference = jsonschema.Draft202012Validator(schema)
+    for instances, expected in [(valid, True), (invalid, False)]:
+        for instance in instances:
+            # Unlike hyperjson, jsonschema does not take tuples for arrays
+            if "(" not in repr(instance):
+                assert reference.is_valid(instance) == expected, instance
+
+
+def test_errors():
+    validator = Validator({
+        "properties": {
+            "users": {
+                "items": {
+                    "required": ["name"],
+                    "properties": {"age": {"$ref": "#/$defs/age"}, "a/b": {"type": "string"}},
+                },
+            },
+        },
+        "$defs": {"age": {"type": "integer", "minimum": 0}},
+    })
+    errors = validator.errors({"users": [{"name": "a", "age": -1}, {"age": "x", "a/b": 1}]})
+    assert [(e.instance_path, e.schema_path, e.message) for e in errors] == [
+        ("/users/0/age", "/properties/users/items/properties/age/$ref/minimum",
+         "-1 is less than the minimum of 0"),
+        ("/users/1", "/properties/users/items/required", "'name' is a required property"),
+        ("/users/1/age", "/properties/users/items/properties/age/$ref/type",
+         "'x' is not of type 'integer'"),
+        ("/users/1/a~1b", "/properties/users/items/properties/a~1b/type",
+         "1 is not of type 'string'"),
+    ]
+    assert errors[0].instance == -1
+    assert str(errors[1]) == "'name' is a required property"
+
+
+@pytest.mark.parametrize(
+    "schema, instance, message",
+    [
+        ({"type": ["string", "null"]}, 1, "1 is not of type 'string', 'null'"),
+        ({"enum": [1, 2]}, 3, "3 is not one of [1, 2]"),
+        ({"const": "a"}, "b", "'a' was expected"),
+        ({"exclusiveMaximum": 3}, 3, "3 is greater than or equal to the maximum of 3"),
+        ({"multipleOf": 2}, 3, "3 is not a multiple of 2"),
+        ({"maxLength": 1}, "ab", "'ab' is too long"),
+        ({"pattern": "^a"}, "b", "'b' does not match '^a'"),
+        ({"uniqueItems": True}, [1, 1], "[1, 1] has non-unique elements"),
+        ({"dependentRequired": {"a": ["b"]}}, {"a": 1}, "'b' is a dependency of 'a'"),
+        ({"additionalProperties": False}, {"a": 1, "b": 2},
+         "Additional properties are not allowed ('a', 'b' were unexpected)"),
+        ({"unevaluatedProperties": False}, {"a": 1}, "Unevaluated properties are not allowed ('a' was unexpected)"),
+        ({"items": False}, [1], "Expected at most 0 items but found 1 extra"),
+        ({"contains": {"const": 1}}, [2], "[2] does not contain items matching the given schema"),
+        ({"anyOf": [{"type": "string"}]}, 1, "1 is not valid under any of the given schemas"),
+        ({"oneOf": [True, True]}, 1, "1 is valid under more than one of the given schemas"),
+        ({"not": True}, 1, "1 should not be valid under the given schema"),
+        (False, 1, "False schema does not allow 1"),
+    ],
+)
+def test_message(schema, instance, message):
+    assert [e.message for e in Validator(schema).errors(instance)] == [message]
+
+
+def test_validate():
+    validator = Validator({"items": {"type": "string"}})
+    with pytest.raises(ValidationError) as info:
+        validator.validate([1, "a", 2])
+    error = info.value
+    assert error.instance_path == "/0"
+    assert error.schema_path == "/items/type"
+    assert [e.instance_path for e in error.errors] == ["/0", "/2"]
+    assert error.errors[0] is error
+
+
+def test_json_text():
+    # Bytes-like instances are JSON documents, but a schema may be a str too
+    validator = Validator('{"type": "object", "required": ["a"]}')
+    assert validator.is_valid(b'{"a": 1}')
+    assert not validator.is_valid(bytearray(b"{}"))
+    assert [e.instance_path for e in validator.errors(memoryview(b"[]"))] == [""]
+    assert Validator({"type": "string"}).is_valid(b'"a"')
+    with pytest.raises(ValueError):
+        validator.is_valid(b"{")
+
+
+def test_str_instances():
+    assert Validator({"type": "string"}).is_valid("abc")
+    assert not Validator({"type": "string", "maxLength": 3}).is_valid('"abc"')
+    assert not Validator({"type": "object"}).is_valid('{"a": 1}')
+    # Keywords for other types ignore strings
+    assert Validator({"minimum": 1}).is_valid("x")
+    assert Validator({"format": "email"}).is_valid("x")
+    with pytest.raises(ValidationError) as info:
+        Validator({"enum": ["a"]}).validate("b")
+    assert info.value.instance == "b"
+
+
+@pytest.mark.parametrize(
+    "schema",
+    [
+        1,
+        [],
+        {"type": "foo"},
+        {"minLength": -1},
+        {"minLength": "1"},
+        {"maximum": "1"},
+        {"multipleOf": 0},
+        {"pattern": "("},
+        # Lookaround is not supported
+        {"pattern": "(?=a)a"},
+        {"enum": 1},
+        {"properties": {"a": 1}},
+        {"$ref": "#/$defs/missing"},
+        {"$ref": "https://example.com/other.json"},
+    ],
+)
+def test_invalid_schema(schema):
+    with pytest.raises(ValueError):
+        Validator(schema)
+
+
+@pytest.mark.parametrize(
+    "schema",
+    [
+        {"$ref": "#"},
+        {"allOf": [{"$ref": "#"}]},
+        {"$defs": {"a": {"$ref": "#/$defs/b"}, "b": {"$ref": "#/$defs/a"}}, "$ref": "#/$defs/a"},
+    ],
+)
+def test_reference_cycle(schema):
+    with pytest.raises(ValueError, match="cycle"):
+        Validator(schema)
+
+
+def test_recursion_through_the_instance():
+    validator = Validator({"items": {"$ref": "#"}, "maxItems": 1})
+    nested = []
+    for _ in range(100):
+        nested = [nested]
+    assert validator.is_valid(nested)
+    for _ in range(10000):
+        nested = [nested]
+    with pytest.raises(RecursionError):
+        validator.is_valid(nested)
+
+
+def test_loads():
+    validator = Validator({"type": "object", "properties": {"id": {"type": "integer"}}})
+    assert hyperjson.loads('{"id": 1}', schema=validator) == {"id": 1}
+    with pytest.raises(ValidationError) as info:
+        hyperjson.loads(b'{"id": "1"}', schema=validator)
+    assert info.value.instance_path == "/id"
+    # Schemas are not compiled on every call
+    with pytest.raises(TypeError):
+        hyperjson.loads("[]", schema={"type": "object"})
+    assert hyperjson.loads("[]", schema=None) == []
+
+
+def test_loads_fails_early():
+    calls = []
+
+    def parse_int(s):
+        calls.append(s)
+        return int(s)
+
+    validator = Validator({"$defs": {"id": {"type": "string"}}, "items": {"properties": {"id": {"$ref": "#/$defs/id"}}}})
+    with pytest.raises(ValidationError) as info:
+        hyperjson.loads('[{"id": "a"}, {"id": 1, "n": 2}, {"id": 3}]', schema=validator, parse_int=parse_int)
+    assert (info.value.instance_path, info.value.schema_path) == ("/1/id", "/items/properties/id/$ref/type")
+    assert calls == ["1"]
+
+
+@pytest.mark.parametrize(
+    "schema, doc",
+    [
+        ({"additionalProperties": False, "properties": {"a": True}}, '{"b": 1, "c": 2}'),
+        ({"prefixItems": [{"type": "integer"}], "items": False}, "[1, 2]"),
+        ({"items": {"type": "integer"}, "minItems": 3}, "[1, 2]"),
+        ({"allOf": [{"items": {"type": "integer"}}], "items": {"maximum": 1}}, '[1, "a", 2]'),
+        ({"patternProperties": {"^x": {"maximum": 1}}, "additionalProperties": {"type": "string"}}, '{"y": 1, "x": 2}'),
+        ({"$dynamicAnchor": "n", "items": {"$dynamicRef": "#n"}, "type": "array"}, "[[1], 2]"),
+    ],
+)
+def test_loads_errors(schema, doc):
+    validator = Validator(schema)
+    with pytest.raises(ValidationError) as info:
+        hyperjson.loads(doc, schema=validator)
+    # The error may come before others that `errors` finds first
+    errors = [(e.message, e.instance_path, e.schema_path) for e in validator.errors(json.loads(doc))]
+    assert (info.value.message, info.value.instance_path, info.value.schema_path) in errors
+
+
+def test_loads_fields():
+    validator = Validator({"required": ["id"], "additionalProperties": False, "properties": {"id": True}})
+    assert hyperjson.loads('{"id": 1, "name": "a"}', fields=["id"], schema=validator) == {"id": 1}
+
+
+def test_many_validators():
+    # Calls to classes are specialized once the code is warm
+    for _ in range(100):
+        validator = Validator({"type": "string"})
+    assert validator.is_valid("a")
@@ -0,0 +1,106 @@
+import io
+import warnings
+
+import pytest
+import hyperjson
+
+RS = "\x1e"
+
+
+def loads_seq(s):
+    with warnings.catch_warnings(record=True) as caught:
+        warnings.simplefilter("always")
+        values = hyperjson.loads_seq(s)
+    return values, [str(w.message) for w in caught if issubclass(w.category, RuntimeWarning)]
+
+
+def test_loads_seq():
+    s = RS + '{"a": 1}\n' + RS + '[1, 2]\n' + RS + '"x"\n' + RS + "3\n"
+    assert loads_seq(s) == ([{"a": 1}, [1, 2], "x", 3], [])
+    assert loads_seq(s.encode()) == ([{"a": 1}, [1, 2], "x", 3], [])
+    assert loads_seq(bytearray(s.encode())) == ([{"a": 1}, [1, 2], "x", 3], [])
+
+
+@pytest.mark.parametrize("s", ["", RS, RS * 3, RS + "\n" + RS + " ", "\n"])
+def test_loads_seq_empty(s):
+    assert loads_seq(s) == ([], [])
+
+
+def test_multiline_records():
+    assert loads_seq(RS + '{\n  "a": [\n    1\n  ]\n}\n')[0] == [{"a": [1]}]
+
+
+def test_truncated_records_are_skipped():
+    s = RS + '{"a": 1}\n' + RS + '{"a": [2,' + RS + '{"a": 3}\n' + RS + "nope\n"
+    values, caught = loads_seq(s)
+    assert values == [{"a": 1}, {"a": 3}]
+    assert len(caught) == 2
+    assert caught[0].startswith("skipping invalid record at byte offset 10")
+    assert caught[1].startswith("skipping invalid record at byte offset 30")
+
+
+def test_resynchronizes_after_garbage():
+    values, caught = loads_seq('garbage"' + RS + "[1]\n")
+    assert values == [[1]]
+    assert caught == ["skipping data before the first record separator"]
+
+
+@pytest.mark.parametrize("text", ["123", "-1.5", "true", "false", "null"])
+def test_possibly_truncated_values_warn(text):
+    values, caught = loads_seq(RS + text + RS + "[1]\n")
+    assert len(values) == 2
+    assert caught == ["record at byte offset 0 may have been truncated"]
+    assert loads_seq(RS + text + "\n")[1] == []
+
+
+def test_strings_and_containers_do_not_warn():
+    assert loads_seq(RS + '"x"' + RS + "[1]" + RS + "{}")[1] == []
+
+
+def test_loads_seq_options():
+    values, _ = loads_seq(RS + '{"b": "/w==", "f": 1.5}\n')
+    assert values == [{"b": "/w==", "f": 1.5}]
+    values = hyperjson.loads_seq(RS + '{"b": "/w==", "f": 1.5}\n', parse_float=str, bytes_mode="base64", bytes_fields={"b"})
+    assert values == [{"b": b"\xff", "f": "1.5"}]
+
+
+def test_loads_seq_hook_errors_propagate():
+    def fail(s):
+        raise KeyError(s)
+
+    with pytest.raises(ValueError):
+        hyperjson.loads_seq(RS + "1.5\n", parse_float=fail)
+
+
+def test_warnings_can_be_errors():
+    with warnings.catch_warnings():
+        warnings.simplefilter("error")
+        with pytest.raises(RuntimeWarning):
+            hyperjson.loads_seq(RS + "[1" + RS + "[2]\n")
+
+
+@pytest.mark.parametrize("fp", [io.StringIO, io.BytesIO])
+def test_dump_seq(fp):
+    fp = fp()
+    hyperjson.dump_seq([{"a": 1}, [1, 2], "x", 3], fp)
+    out = fp.getvalue()
+    if isinstance(out, bytes):
+        out = out.decode()
+    assert out == RS + '{"a":1}\n' + RS + "[1,2]\n" + RS + '"x"\n' + RS + "3\n"
+
+
+def test_dump_seq_roundtrip():
+    records = [{"id": i, "tags": ["a", "ü"]} for i in range(1000)] + [1, 2.5, None, True]
+    fp = io.StringIO()
+    hyperjson.dump_seq(iter(records), fp, indent=2, ensure_ascii=False, chunk_size=100)
+    assert loads_seq(fp.getvalue()) == (records, [])
+
+
+def test_dump_seq_errors():
+    fp = io.StringIO()
+    with pytest.raises(TypeError):
+        hyperjson.dump_seq([1, object()], fp)
+    with pytest.raises(TypeError):
+        hyperjson.dump_seq(1, fp)
+    with pytest.raises(AttributeError):
+        hyperjson.dump_seq([1], "")
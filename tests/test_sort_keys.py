@@ -0,0 +1,100 @@
+import io
+import json
+
+import pytest
+import hyperjson
+
+
+def test_sort_keys_nested():
+    obj = {"b": [{"d": 1, "c": 2.5}], "a": {"z": None, "y": (True,)}, "B": "x"}
+    expected = json.dumps(obj, sort_keys=True, separators=(",", ":"))
+    assert hyperjson.dumps(obj, sort_keys=True) == expected
+    assert hyperjson.dumps_bytes(obj, sort_keys=True) == expected.encode()
+    assert hyperjson.dumps(obj, sort_keys=True, indent=2) == json.dumps(
+        obj, sort_keys=True, indent=2
+    )
+
+
+def test_sort_keys_options_apply_to_nested_values():
+    obj = {"b": {"d": b"\xfb", "c": "<"}, "a": 1}
+    assert hyperjson.dumps(
+        obj, sort_keys=True, bytes_mode="base64", encode_html_chars=True
+    ) == '{"a":1,"b":{"c":"\\u003c","d":"+w=="}}'
+
+
+def test_sort_keys_non_string_keys():
+    obj = {2: "a", None: "b", False: "c", 1.5: "d", "x": "e"}
+    assert hyperjson.dumps(obj, sort_keys=True) == (
+        '{"1.5":"d","2":"a","false":"c","null":"b","x":"e"}'
+    )
+
+
+@pytest.mark.parametrize("sort_keys", [False, None, 0])
+def test_sort_keys_off(sort_keys):
+    obj = {"b": 1, "a": 2}
+    assert hyperjson.dumps(obj, sort_keys=sort_keys) == '{"b":1,"a":2}'
+
+
+def test_sort_keys_surrogates():
+    obj = {"": 1, "\ud800": 2, "퟿": 3, "\U0001f600": 4}
+    assert hyperjson.dumps(obj, sort_keys=True, surrogates="escape", ensure_ascii=True) == json.dumps(
+        obj, sort_keys=True, separators=(",", ":")
+    )
+
+
+def test_sort_keys_natural():
+    obj = {"item10": 1, "item2": 2, "item02": 3, "item1b": 4, "Item3": 5, "item": 6}
+    assert list(json.loads(hyperjson.dumps(obj, sort_keys="natural"))) == [
+        "Item3",
+        "item",
+        "item1b",
+        "item02",
+        "item2",
+        "item10",
+    ]
+    obj = {"v1.10.0": {"b10": 0, "b9": 0}, "v1.9.2": None}
+    assert hyperjson.dumps(obj, sort_keys="natural") == (
+        '{"v1.9.2":null,"v1.10.0":{"b9":0,"b10":0}}'
+    )
+
+
+def test_sort_keys_key_function():
+    obj = {"b": 1, "A": 2, "c": {"B": 3, "a": 4}}
+    assert hyperjson.dumps(obj, sort_keys=str.lower) == (
+        '{"A":2,"b":1,"c":{"a":4,"B":3}}'
+    )
+    # Names, not the original keys, are passed to the function
+    obj = {10: "a", 9: "b", 100: "c"}
+    assert hyperjson.dumps(obj, sort_keys=int) == '{"9":"b","10":"a","100":"c"}'
+    # Ties keep the order of the dict
+    obj = {"bb": 1, "a": 2, "cc": 3, "d": 4}
+    assert hyperjson.dumps(obj, sort_keys=len) == '{"a":2,"d":4,"bb":1,"cc":3}'
+
+
+def test_sort_keys_key_function_errors():
+    def fail(name):
+        raise KeyError(name)
+
+    with pytest.raises(KeyError) as info:
+        hyperjson.dumps({"a": 1}, sort_keys=fail)
+    assert info.value.args == ("a",)
+    with pytest.raises(KeyError):
+        hyperjson.dumps_bytes([{"b": {"a": 1}}], sort_keys=fail)
+    with pytest.raises(KeyError):
+        hyperjson.dump({"a": 1}, io.StringIO(), sort_keys=fail)
+    with pytest.raises(TypeError, match="'<' not supported"):
+        hyperjson.dumps({"a": 1, "b": 2}, sort_keys={"a": 1, "b": "x"}.get)
+
+
+def test_sort_keys_invalid():
+    with pytest.raises(ValueError):
+        hyperjson.dumps({}, sort_keys="reverse")
+
+
+def test_sort_keys_lines():
+    import io
+
+    fp = io.StringIO()
+    with hyperjson.JsonLinesWriter(fp, sort_keys="natural") as writer:
+        writer.write({"a10": 1, "a9": 2})
+    assert fp.getvalue() == '{"a9":2,"a10":1}\n'
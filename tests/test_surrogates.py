@@ -0,0 +1,76 @@
+import json
+
+import pytest
+import hyperjson
+
+
+def test_surrogates_strict_by_default():
+    with pytest.raises(TypeError):
+        hyperjson.dumps("\ud800")
+    with pytest.raises(ValueError):
+        hyperjson.loads('"\\ud800"')
+
+
+def test_surrogates_invalid_policy():
+    with pytest.raises(ValueError):
+        hyperjson.dumps("a", surrogates="ignore")
+    with pytest.raises(ValueError):
+        hyperjson.loads('"a"', surrogates="ignore")
+
+
+@pytest.mark.parametrize("payload", ["\ud800", "a\udfffb", "\ud83d", ["\udc00", {"k\udbff": "\ud800x"}]])
+def test_dumps_escape(payload):
+    assert hyperjson.dumps(payload, surrogates="escape") == json.dumps(
+        payload, separators=(",", ":"))
+
+
+def test_dumps_escape_with_indent_and_sort_keys():
+    payload = {"b": "\ud800", "a": ["\udc00"]}
+    assert hyperjson.dumps(payload, surrogates="escape", indent=2, sort_keys=True) == json.dumps(
+        payload, indent=2, sort_keys=True)
+
+
+def test_dumps_replace():
+    assert hyperjson.dumps({"\ud800": "a\udfff"}, surrogates="replace") == '{"�":"a�"}'
+
+
+@pytest.mark.parametrize("doc", ['"\\ud800"', '["a\\udfffb", "\\ud83d\\ude00"]', '{"k\\udbff": "\\ud800\\ud800"}'])
+def test_loads_escape(doc):
+    assert hyperjson.loads(doc, surrogates="escape") == json.loads(doc)
+    assert hyperjson.loads(doc.encode(), surrogates="escape") == json.loads(doc)
+
+
+def test_loads_raw_surrogates():
+    doc = '["\ud800", {"\udc00": 1}]'
+    assert hyperjson.loads(doc, surrogates="escape") == json.loads(doc)
+    assert hyperjson.loads(doc, surrogates="replace") == ["�", {"�": 1}]
+
+
+def test_loads_replace():
+    assert hyperjson.loads('["\\ud800", "\\\\ud800"]', surrogates="replace") == ["�", "\\ud800"]
+
+
+def test_escape_roundtrip():
+    payload = {"text": "broken \ud83d pair", "ok": "\U0001f600"}
+    s = hyperjson.dumps(payload, surrogates="escape")
+    assert hyperjson.loads(s, surrogates="escape") == payload
+
+
+@pytest.mark.parametrize("payload", [
+    ["\ud800", "\U0010f800"],
+    {"\udfff": "\U0010ffff", "\U0010ffff": "\udfff"},
+    "a\ud800\n\udc00\"\\\udbff",
+])
+def test_escape_with_private_use_characters(payload):
+    s = hyperjson.dumps(payload, surrogates="escape", sort_keys=True, ensure_ascii=True)
+    assert s == json.dumps(payload, sort_keys=True, separators=(",", ":"))
+    s = hyperjson.dumps(payload, surrogates="escape", sort_keys=True)
+    assert hyperjson.loads(s, surrogates="escape") == payload
+    assert hyperjson.loads(s.encode("utf-16", "surrogatepass"), surrogates="escape") == payload
+
+
+def test_loads_escape_keys():
+    doc = '{"a\\ud800": 1, "a\\ud801": 2, "a": 3, "b": "\\udc00"}'
+    assert hyperjson.loads(doc, surrogates="escape") == json.loads(doc)
+    result = hyperjson.loads(doc, surrogates="escape", fields={"a", "b"})
+    assert result == {"a": 3, "b": "\udc00"}
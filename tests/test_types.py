@@ -0,0 +1,47 @@
+import io
+
+import pytest
+import hyperjson
+
+classes = [
+    hyperjson.JsonLinesWriter,
+    hyperjson.IncrementalDecoder,
+    hyperjson.Validator,
+    type(hyperjson.loads_iter("1")),
+    type(hyperjson.iterparse(io.BytesIO(b"1"))),
+]
+
+
+@pytest.mark.parametrize("cls", classes)
+def test_metaclass(cls):
+    metaclass = type(cls)
+    assert metaclass is type(hyperjson.JsonLinesWriter)
+    assert metaclass is not type
+    assert issubclass(metaclass, type)
+    assert metaclass.__module__ == "hyperjson"
+    assert isinstance(cls, type)
+
+
+@pytest.mark.parametrize("cls", classes)
+def test_not_subclassable(cls):
+    with pytest.raises(TypeError):
+        type("Sub", (cls,), {})
+
+
+@pytest.mark.parametrize("cls", classes)
+def test_immutable(cls):
+    with pytest.raises(TypeError):
+        cls.attribute = 1
+    with pytest.raises(TypeError):
+        del cls.__doc__
+    assert not hasattr(cls, "attribute")
+
+
+def test_repeated_calls():
+    # Calls to classes are specialized once the code is warm
+    for i in range(1000):
+        decoder = hyperjson.IncrementalDecoder()
+        assert type(decoder) is hyperjson.IncrementalDecoder
+        writer = hyperjson.JsonLinesWriter(io.StringIO())
+        writer.write(i)
+        writer.close()
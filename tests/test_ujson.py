@@ -45,7 +45,6 @@ class UltraJSONTests(unittest.TestCase):
         print(decoded)
         self.assertEqual(decoded, 1337.1337)
 
-    @unittest.skip("module 'json' does not support 'encode_html_chars'")
     def test_encodeStringConversion(self):
         input = "A string \\ / \b \f \n \r \t </script> &"
         not_html_encoded = '"A string \\\\ / \\b \\f \\n \\r \\t </script> &"'
@@ -73,9 +72,8 @@ class UltraJSONTests(unittest.TestCase):
         # Do escape forward slashes if disabled.
         helper(not_slashes_escaped, escape_forward_slashes=False)
 
-    @unittest.skip("module 'json' does not support 'encode_html_chars'")
     def testWriteEscapedString(self):
-        self.assertEqual('"\\u003cimg src=\'\\u0026amp;\'\\/\\u003e"',
+        self.assertEqual('"\\u003cimg src=\\u0027\\u0026amp;\\u0027\\/\\u003e"',
                          hyperjson.dumps("<img src='&amp;'/>", encode_html_chars=True))
 
     def test_doubleLongIssue(self):
@@ -643,7 +641,6 @@ class UltraJSONTests(unittest.TestCase):
         for doc, result in zip(docs, results):
             self.assertEqual(hyperjson.loads(doc)['id'], result)
 
-    @unittest.skip("module 'json': TypeError: Object of type 'bytes' is not JSON serializable")
     def test_encodeBigEscape(self):
         for x in range(10):
             if six.PY3:
@@ -651,7 +648,7 @@ class UltraJSONTests(unittest.TestCase):
             else:
                 base = "\xc3\xa5"
             input = base * 1024 * 1024 * 2
-            hyperjson.dumps(input)
+            hyperjson.dumps(input, bytes_mode="utf8")
 
     @unittest.skipIf(sys.version_info < (3, 6), "Bytes input not supported in older Python versions")
     def test_decodeEscape(self):